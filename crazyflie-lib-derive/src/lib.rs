@@ -0,0 +1,200 @@
+//! # Derive macros for the Crazyflie library
+//!
+//! This crate implements the derive macros of the [crazyflie-lib](https://crates.io/crates/crazyflie-lib) crate.
+//! It should not be used directly, the macros are re-exported by crazyflie-lib.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, Ident, LitStr, parse_macro_input};
+
+/// Derive `crazyflie_lib::subsystems::log::TypedLogBlock` for a struct of log variables
+///
+/// See the documentation of the `TypedLogBlock` trait in crazyflie-lib.
+#[proc_macro_derive(LogBlock, attributes(log))]
+pub fn derive_log_block(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    log_block(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derive `crazyflie_lib::subsystems::param::TypedParamGroup` for a struct of parameters
+///
+/// See the documentation of the `TypedParamGroup` trait in crazyflie-lib.
+#[proc_macro_derive(ParamGroup, attributes(param))]
+pub fn derive_param_group(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    param_group(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Field of a log block struct
+enum LogField {
+    /// `#[log("group.name")]`: a log variable
+    Variable(LitStr),
+    /// `#[log(timestamp)]`: the Crazyflie timestamp of the sample
+    Timestamp,
+}
+
+fn log_block(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "LogBlock cannot be derived for generic structs",
+        ));
+    }
+
+    let fields = named_fields(input, "LogBlock")?;
+
+    let mut variables = Vec::new();
+    let mut initializers = Vec::new();
+    for field in fields {
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+
+        match log_field(field)? {
+            LogField::Variable(variable) => {
+                variables.push(quote! {
+                    (#variable, <#ty as ::crazyflie_lib::ValuePrimitive>::VALUE_TYPE)
+                });
+                initializers.push(quote! {
+                    #ident: ::crazyflie_lib::subsystems::log::decode_log_field::<#ty>(data, &mut index)?
+                });
+            }
+            LogField::Timestamp => initializers.push(quote! { #ident: timestamp }),
+        }
+    }
+
+    Ok(quote! {
+        impl ::crazyflie_lib::subsystems::log::TypedLogBlock for #name {
+            fn variables() -> ::std::vec::Vec<(&'static str, ::crazyflie_lib::ValueType)> {
+                ::std::vec![#(#variables),*]
+            }
+
+            #[allow(unused_mut, unused_variables)]
+            fn decode(timestamp: u32, data: &[u8]) -> ::crazyflie_lib::Result<Self> {
+                let mut index = 0;
+                Ok(Self {
+                    #(#initializers),*
+                })
+            }
+        }
+    })
+}
+
+fn param_group(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "ParamGroup cannot be derived for generic structs",
+        ));
+    }
+
+    let group = param_attribute(&input.attrs, "group")?.ok_or_else(|| {
+        syn::Error::new_spanned(&input.ident, "missing #[param(group = \"group\")] attribute")
+    })?;
+
+    let mut parameters = Vec::new();
+    let mut initializers = Vec::new();
+    let mut values = Vec::new();
+    for (index, field) in named_fields(input, "ParamGroup")?.enumerate() {
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let parameter = match param_attribute(&field.attrs, "name")? {
+            Some(parameter) => parameter,
+            None => LitStr::new(&ident.to_string(), ident.span()),
+        };
+
+        parameters.push(quote! {
+            (#parameter, <#ty as ::crazyflie_lib::ValuePrimitive>::VALUE_TYPE)
+        });
+        initializers.push(quote! {
+            #ident: ::crazyflie_lib::subsystems::param::decode_param_field::<#ty>(values, #index)?
+        });
+        values.push(quote! { ::crazyflie_lib::Value::from(self.#ident) });
+    }
+
+    Ok(quote! {
+        impl ::crazyflie_lib::subsystems::param::TypedParamGroup for #name {
+            const GROUP: &'static str = #group;
+
+            fn parameters() -> ::std::vec::Vec<(&'static str, ::crazyflie_lib::ValueType)> {
+                ::std::vec![#(#parameters),*]
+            }
+
+            #[allow(unused_variables)]
+            fn from_values(values: &[::crazyflie_lib::Value]) -> ::crazyflie_lib::Result<Self> {
+                Ok(Self {
+                    #(#initializers),*
+                })
+            }
+
+            fn to_values(&self) -> ::std::vec::Vec<::crazyflie_lib::Value> {
+                ::std::vec![#(#values),*]
+            }
+        }
+    })
+}
+
+/// Value of the `key = "value"` argument of the `#[param(...)]` attributes, if present
+fn param_attribute(attrs: &[syn::Attribute], key: &str) -> syn::Result<Option<LitStr>> {
+    let mut value = None;
+    for attribute in attrs.iter().filter(|attr| attr.path().is_ident("param")) {
+        attribute.parse_nested_meta(|meta| {
+            if meta.path.is_ident(key) {
+                value = Some(meta.value()?.parse::<LitStr>()?);
+                Ok(())
+            } else {
+                Err(meta.error(format!("expected #[param({} = \"...\")]", key)))
+            }
+        })?;
+    }
+    Ok(value)
+}
+
+fn named_fields<'a>(
+    input: &'a DeriveInput,
+    derive: &str,
+) -> syn::Result<impl Iterator<Item = &'a syn::Field>> {
+    match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => Ok(fields.named.iter()),
+            _ => Err(syn::Error::new_spanned(
+                &input.ident,
+                format!("{} can only be derived for structs with named fields", derive),
+            )),
+        },
+        _ => Err(syn::Error::new_spanned(
+            &input.ident,
+            format!("{} can only be derived for structs", derive),
+        )),
+    }
+}
+
+fn log_field(field: &syn::Field) -> syn::Result<LogField> {
+    let attribute = field
+        .attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("log"))
+        .ok_or_else(|| {
+            syn::Error::new_spanned(
+                field,
+                "missing #[log(\"group.name\")] or #[log(timestamp)] attribute",
+            )
+        })?;
+
+    if let Ok(variable) = attribute.parse_args::<LitStr>() {
+        return Ok(LogField::Variable(variable));
+    }
+    match attribute.parse_args::<Ident>() {
+        Ok(ident) if ident == "timestamp" => Ok(LogField::Timestamp),
+        _ => Err(syn::Error::new_spanned(
+            attribute,
+            "expected #[log(\"group.name\")] or #[log(timestamp)]",
+        )),
+    }
+}
@@ -30,6 +30,7 @@ use std::f32::consts::PI;
 use std::sync::Arc;
 use std::time::Duration;
 
+use crazyflie_lib::swarm::{FailurePolicy, Swarm};
 use crazyflie_lib::{Crazyflie, TocCache};
 use tokio::time::sleep;
 
@@ -226,21 +227,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Connect to all Crazyflies
     println!("Connecting to Crazyflies...");
-    let mut crazyflies: HashMap<String, Arc<Crazyflie>> = HashMap::new();
-
-    for uri in &uris {
-        match Crazyflie::connect_from_uri(&link_context, uri, config_toc_cache.clone()).await {
-            Ok(cf) => {
-                println!("Connected to {}", uri);
-                crazyflies.insert(uri.to_string(), Arc::new(cf));
-            }
-            Err(e) => {
-                eprintln!("Failed to connect to {}: {:?}", uri, e);
-            }
-        }
+    let swarm = Swarm::connect_from_uris(
+        &link_context,
+        &uris,
+        config_toc_cache,
+        FailurePolicy::BestEffort,
+    )
+    .await?;
+
+    for (uri, e) in swarm.connect_errors() {
+        eprintln!("Failed to connect to {}: {:?}", uri, e);
     }
 
-    if crazyflies.is_empty() {
+    if swarm.is_empty() {
         eprintln!("No Crazyflies connected!");
         return Ok(());
     }
@@ -249,40 +248,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Arm all Crazyflies in parallel
     println!("Arming...");
-    let arm_futures: Vec<_> = crazyflies
-        .values()
-        .map(|cf| {
-            let cf = Arc::clone(cf);
-            async move { arm(&cf).await }
-        })
-        .collect();
-
-    futures::future::join_all(arm_futures).await;
+    swarm.parallel(async |_, cf| arm(cf).await).await?;
 
     // Run sequence on all Crazyflies in parallel
     println!("Starting sequence...");
-    let sequence_futures: Vec<_> = crazyflies
-        .iter()
-        .map(|(uri, cf)| {
-            let cf = Arc::clone(cf);
-            let uri = uri.clone();
-            async move {
-                if let Err(e) = run_shared_sequence(&cf, &uri, max_takeoff_duration).await {
-                    eprintln!("Sequence failed for {}: {:?}", uri, e);
-                }
-            }
-        })
-        .collect();
-
-    futures::future::join_all(sequence_futures).await;
+    let results = swarm
+        .parallel(async |uri, cf| run_shared_sequence(cf, uri, max_takeoff_duration).await)
+        .await?;
+    for (uri, result) in results {
+        if let Err(e) = result {
+            eprintln!("Sequence failed for {}: {:?}", uri, e);
+        }
+    }
 
     sleep(Duration::from_secs(1)).await;
 
     // Disconnect all Crazyflies
-    for (uri, cf) in crazyflies.iter() {
-        println!("Disconnecting from {}", uri);
-        cf.disconnect().await;
-    }
+    println!("Disconnecting...");
+    swarm.disconnect().await;
 
     println!("Done!");
     Ok(())
@@ -0,0 +1,834 @@
+//! # Blocking API
+//!
+//! Synchronous facade over the async API, for code that does not run in an async runtime like command line scripts
+//! or plugins in synchronous applications. This module is only available with the `blocking` feature.
+//!
+//! The blocking [Crazyflie] owns a multi-threaded Tokio runtime that runs the lib background tasks. Its subsystems
+//! mirror the [async subsystems](crate::subsystems) with blocking methods. Log streams are iterators over the log
+//! data.
+//!
+//! The functionalities not mirrored in this module can be used from the async [Crazyflie](crate::Crazyflie)
+//! returned by [Crazyflie::as_async()] and the [Crazyflie::block_on()] function.
+//!
+//! The blocking functions must not be called from within an async runtime, they panic if they are.
+//!
+//! ```no_run
+//! # use crazyflie_lib::blocking::Crazyflie;
+//! # use crazyflie_lib::subsystems::log::LogPeriod;
+//! # fn example() -> crazyflie_lib::Result<()> {
+//! let cf = Crazyflie::connect_from_uri("radio://0/60/2M/E7E7E7E7E7", crazyflie_lib::NoTocCache)?;
+//!
+//! let kp: f32 = cf.param.get("pid_rate.roll_kp")?;
+//! println!("Roll Kp: {}", kp);
+//!
+//! let mut block = cf.log.create_block()?;
+//! block.add_variable("stateEstimate.z")?;
+//! for data in block.start(LogPeriod::from_millis(100)?)?.take(10) {
+//!     println!("{:?}", data?);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::simulation::SimulatedCrazyflie;
+use crate::subsystems::commander::{FullState, Setpoint, SetpointStream, SetpointStreamOptions};
+use crate::subsystems::log::{LogBlockInfo, LogConfig, LogData, LogPeriod, LogStreamStatistics};
+use crate::subsystems::memory::{FromMemoryBackend, MemoryDevice};
+use crate::subsystems::param::{
+    ParamDifference, ParamEntry, ParamRestoreReport, ParamSnapshot, PersistentImportReport, PersistentParamProfile,
+    PersistentParamState,
+};
+use crate::{Error, Result, TocCache, Value, ValueType};
+use futures::{Stream, StreamExt};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+type AsyncCrazyflie = crate::Crazyflie;
+
+/// # Blocking Crazyflie
+///
+/// Blocking version of [crate::Crazyflie]. The subsystems are available as public fields.
+///
+/// Dropping this object disconnects the Crazyflie and waits for the disconnection to complete.
+///
+/// See the [blocking module documentation](crate::blocking) for more context and information.
+pub struct Crazyflie {
+    /// Log subsystem access
+    pub log: Log,
+    /// Parameter subsystem access
+    pub param: Param,
+    /// Memory subsystem access
+    pub memory: Memory,
+    /// Low level setpoint subsystem access
+    pub commander: Commander,
+    /// High-level commander subsystem access
+    pub high_level_commander: HighLevelCommander,
+    cf: Arc<AsyncCrazyflie>,
+    runtime: Arc<Runtime>,
+    _link_context: Option<crazyflie_link::LinkContext>,
+}
+
+impl Crazyflie {
+    /// Open a Crazyflie connection to a given URI
+    ///
+    /// Blocking version of [crate::Crazyflie::connect_from_uri()].
+    pub fn connect_from_uri<T>(uri: &str, toc_cache: T) -> Result<Self>
+    where
+        T: TocCache + Send + Sync + 'static,
+    {
+        let runtime = new_runtime()?;
+        let (link_context, cf) = runtime.block_on(async {
+            let link_context = crazyflie_link::LinkContext::new();
+            let cf = AsyncCrazyflie::connect_from_uri(&link_context, uri, toc_cache).await?;
+            Ok::<_, Error>((link_context, cf))
+        })?;
+
+        Ok(Self::new(runtime, cf, Some(link_context)))
+    }
+
+    /// Connect to a simulated Crazyflie
+    ///
+    /// Blocking version of [crate::Crazyflie::connect_from_simulation()].
+    pub fn connect_from_simulation<T>(simulation: &SimulatedCrazyflie, toc_cache: T) -> Result<Self>
+    where
+        T: TocCache + Send + Sync + 'static,
+    {
+        let runtime = new_runtime()?;
+        let cf = runtime.block_on(AsyncCrazyflie::connect_from_simulation(simulation, toc_cache))?;
+
+        Ok(Self::new(runtime, cf, None))
+    }
+
+    fn new(
+        runtime: Runtime,
+        cf: AsyncCrazyflie,
+        link_context: Option<crazyflie_link::LinkContext>,
+    ) -> Self {
+        let runtime = Arc::new(runtime);
+        let cf = Arc::new(cf);
+        let handle = Handle {
+            runtime: runtime.clone(),
+            cf: cf.clone(),
+        };
+
+        Self {
+            log: Log(handle.clone()),
+            param: Param(handle.clone()),
+            memory: Memory(handle.clone()),
+            commander: Commander(handle.clone()),
+            high_level_commander: HighLevelCommander(handle),
+            cf,
+            runtime,
+            _link_context: link_context,
+        }
+    }
+
+    /// Disconnect the Crazyflie
+    ///
+    /// Blocking version of [crate::Crazyflie::disconnect()].
+    pub fn disconnect(&self) {
+        self.runtime.block_on(self.cf.disconnect())
+    }
+
+    /// Wait for the Crazyflie to be disconnected
+    ///
+    /// Blocking version of [crate::Crazyflie::wait_disconnect()].
+    pub fn wait_disconnect(&self) -> String {
+        self.runtime.block_on(self.cf.wait_disconnect())
+    }
+
+    /// Async Crazyflie object wrapped by this blocking Crazyflie
+    ///
+    /// Its async functions can be run with [Crazyflie::block_on()].
+    pub fn as_async(&self) -> &crate::Crazyflie {
+        &self.cf
+    }
+
+    /// Run a future to completion on the runtime of this Crazyflie
+    ///
+    /// Allows to call any async function of the lib, for example the functions of the memory types returned by
+    /// [Memory::open_memory()].
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+}
+
+impl Drop for Crazyflie {
+    fn drop(&mut self) {
+        self.runtime.block_on(self.cf.disconnect());
+    }
+}
+
+fn new_runtime() -> Result<Runtime> {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| Error::SystemError(format!("Cannot create the Tokio runtime: {}", e)))
+}
+
+/// Runtime and Crazyflie object shared by the blocking subsystems
+#[derive(Clone)]
+struct Handle {
+    runtime: Arc<Runtime>,
+    cf: Arc<AsyncCrazyflie>,
+}
+
+impl Handle {
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+}
+
+/// # Blocking log subsystem
+///
+/// Blocking version of [crate::subsystems::log::Log].
+pub struct Log(Handle);
+
+impl Log {
+    /// Get the names of all the log variables
+    pub fn names(&self) -> Vec<String> {
+        self.0.cf.log.names()
+    }
+
+    /// Return the type of a log variable or an Error if the variable does not exist
+    pub fn get_type(&self, name: &str) -> Result<ValueType> {
+        self.0.cf.log.get_type(name)
+    }
+
+    /// Create a Log block
+    ///
+    /// Blocking version of [crate::subsystems::log::Log::create_block()].
+    pub fn create_block(&self) -> Result<LogBlock> {
+        let block = self.0.block_on(self.0.cf.log.create_block())?;
+
+        Ok(LogBlock {
+            block,
+            runtime: self.0.runtime.clone(),
+        })
+    }
+
+    /// Get the log blocks currently created in the Crazyflie by this lib
+    ///
+    /// Blocking version of [crate::subsystems::log::Log::active_blocks()].
+    pub fn active_blocks(&self) -> Vec<LogBlockInfo> {
+        self.0.block_on(self.0.cf.log.active_blocks())
+    }
+
+    /// Load log configurations and create the log blocks
+    ///
+    /// Blocking version of [crate::subsystems::log::Log::load_config()].
+    pub fn load_config(&self, path: impl AsRef<Path>) -> Result<Vec<ConfiguredLogBlock>> {
+        let blocks = self.0.block_on(self.0.cf.log.load_config(path))?;
+
+        Ok(blocks
+            .into_iter()
+            .map(|configured| ConfiguredLogBlock {
+                name: configured.name,
+                period: configured.period,
+                block: LogBlock {
+                    block: configured.block,
+                    runtime: self.0.runtime.clone(),
+                },
+            })
+            .collect())
+    }
+
+    /// Save log configurations in the cfclient format
+    ///
+    /// See [crate::subsystems::log::Log::save_config()].
+    pub fn save_config(&self, path: impl AsRef<Path>, configs: &[LogConfig]) -> Result<()> {
+        self.0.cf.log.save_config(path, configs)
+    }
+}
+
+/// # Blocking configured log block
+///
+/// Blocking version of [crate::subsystems::log::ConfiguredLogBlock].
+pub struct ConfiguredLogBlock {
+    /// Name of the configuration
+    pub name: String,
+    /// Period from the configuration
+    pub period: LogPeriod,
+    /// The log block, ready to be started
+    pub block: LogBlock,
+}
+
+impl ConfiguredLogBlock {
+    /// Start the log block at the period of the configuration
+    pub fn start(self) -> Result<LogStream> {
+        self.block.start(self.period)
+    }
+}
+
+/// # Blocking log block
+///
+/// Blocking version of [LogBlock](struct@crate::subsystems::log::LogBlock).
+pub struct LogBlock {
+    block: crate::subsystems::log::LogBlock,
+    runtime: Arc<Runtime>,
+}
+
+impl LogBlock {
+    /// Start log block and return a stream to read the value
+    ///
+    /// Blocking version of [crate::subsystems::log::LogBlock::start()].
+    pub fn start(self, period: LogPeriod) -> Result<LogStream> {
+        let stream = self.runtime.block_on(self.block.start(period))?;
+
+        Ok(LogStream {
+            stream,
+            runtime: self.runtime,
+        })
+    }
+
+    /// Add a variable to the log block
+    ///
+    /// Blocking version of [crate::subsystems::log::LogBlock::add_variable()].
+    pub fn add_variable(&mut self, name: &str) -> Result<()> {
+        self.runtime.block_on(self.block.add_variable(name))
+    }
+
+    /// Add a variable from the Crazyflie memory to the log block
+    ///
+    /// Blocking version of [crate::subsystems::log::LogBlock::add_memory_variable()].
+    pub fn add_memory_variable(&mut self, address: u32, value_type: ValueType) -> Result<()> {
+        self.runtime.block_on(self.block.add_memory_variable(address, value_type))
+    }
+
+    /// Delete the log block in the Crazyflie
+    ///
+    /// Blocking version of [crate::subsystems::log::LogBlock::delete()].
+    pub fn delete(self) -> Result<()> {
+        self.runtime.block_on(self.block.delete())
+    }
+}
+
+/// # Blocking log stream
+///
+/// Blocking version of [crate::subsystems::log::LogStream]. The stream is an iterator over the log data, it ends
+/// when the Crazyflie is disconnected.
+pub struct LogStream {
+    stream: crate::subsystems::log::LogStream,
+    runtime: Arc<Runtime>,
+}
+
+impl LogStream {
+    /// Stops the log block from streaming
+    ///
+    /// Blocking version of [crate::subsystems::log::LogStream::stop()].
+    pub fn stop(self) -> Result<LogBlock> {
+        let block = self.runtime.block_on(self.stream.stop())?;
+
+        Ok(LogBlock {
+            block,
+            runtime: self.runtime,
+        })
+    }
+
+    /// Change the period of the log block
+    ///
+    /// Blocking version of [crate::subsystems::log::LogStream::set_period()].
+    pub fn set_period(&mut self, period: LogPeriod) -> Result<()> {
+        self.runtime.block_on(self.stream.set_period(period))
+    }
+
+    /// Counters of the samples received and missed since the log block was started
+    pub fn statistics(&self) -> LogStreamStatistics {
+        self.stream.statistics()
+    }
+}
+
+impl Iterator for LogStream {
+    type Item = Result<LogData>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.runtime.block_on(self.stream.next()) {
+            Err(Error::Disconnected) => None,
+            result => Some(result),
+        }
+    }
+}
+
+/// # Blocking parameter subsystem
+///
+/// Blocking version of [crate::subsystems::param::Param].
+pub struct Param(Handle);
+
+impl Param {
+    /// Get the names of all the parameters
+    pub fn names(&self) -> Vec<String> {
+        self.0.cf.param.names()
+    }
+
+    /// Return the type of a parameter variable or an Error if the parameter does not exist.
+    pub fn get_type(&self, name: &str) -> Result<ValueType> {
+        self.0.cf.param.get_type(name)
+    }
+
+    /// Return true if the parameter is writable, false otherwise.
+    pub fn is_writable(&self, name: &str) -> Result<bool> {
+        self.0.cf.param.is_writable(name)
+    }
+
+    /// Return true if the parameter has an extended type, false otherwise.
+    pub fn has_extended_type(&self, name: &str) -> Result<bool> {
+        self.0.cf.param.has_extended_type(name)
+    }
+
+    /// Set a parameter value.
+    ///
+    /// Blocking version of [crate::subsystems::param::Param::set()].
+    pub fn set<T: Into<Value>>(&self, param: &str, value: T) -> Result<()> {
+        self.0.block_on(self.0.cf.param.set(param, value))
+    }
+
+    /// Get param value
+    ///
+    /// Blocking version of [crate::subsystems::param::Param::get()].
+    pub fn get<T: TryFrom<Value>>(&self, name: &str) -> Result<T>
+    where
+        <T as TryFrom<Value>>::Error: std::fmt::Debug,
+    {
+        self.0.block_on(self.0.cf.param.get(name))
+    }
+
+    /// Set a parameter from a f64 potentially loosing data
+    ///
+    /// Blocking version of [crate::subsystems::param::Param::set_lossy()].
+    pub fn set_lossy(&self, name: &str, value: f64) -> Result<()> {
+        self.0.block_on(self.0.cf.param.set_lossy(name, value))
+    }
+
+    /// Get a parameter as a `f64` independently of the parameter type
+    ///
+    /// Blocking version of [crate::subsystems::param::Param::get_lossy()].
+    pub fn get_lossy(&self, name: &str) -> Result<f64> {
+        self.0.block_on(self.0.cf.param.get_lossy(name))
+    }
+
+    /// Get notified for all parameter value change
+    ///
+    /// Blocking version of [crate::subsystems::param::Param::watch_change()]. The returned iterator blocks until the
+    /// next parameter change and ends when the Crazyflie is disconnected.
+    pub fn watch_change(&self) -> Result<ParamChanges> {
+        let stream = self.0.block_on(self.0.cf.param.watch_change())?;
+
+        Ok(ParamChanges {
+            stream: Box::pin(stream),
+            runtime: self.0.runtime.clone(),
+        })
+    }
+
+    /// Watch the value of a parameter
+    ///
+    /// Blocking version of [crate::subsystems::param::Param::watch()]. The returned iterator first returns the
+    /// current value, then blocks until the next change of the parameter. It ends when the Crazyflie is disconnected.
+    pub fn watch(&self, name: &str) -> Result<ParamChanges<Value>> {
+        let stream = self.0.block_on(self.0.cf.param.watch(name))?;
+
+        Ok(ParamChanges {
+            stream: Box::pin(stream),
+            runtime: self.0.runtime.clone(),
+        })
+    }
+
+    /// Watch the values of all the parameters of a group
+    ///
+    /// Blocking version of [crate::subsystems::param::Param::watch_group()].
+    pub fn watch_group(&self, group: &str) -> Result<ParamChanges> {
+        let stream = self.0.block_on(self.0.cf.param.watch_group(group))?;
+
+        Ok(ParamChanges {
+            stream: Box::pin(stream),
+            runtime: self.0.runtime.clone(),
+        })
+    }
+
+    /// Wait for a parameter to reach a value
+    ///
+    /// Blocking version of [crate::subsystems::param::Param::wait_for()].
+    pub fn wait_for<T, F>(&self, name: &str, predicate: F, timeout: Duration) -> Result<T>
+    where
+        T: TryFrom<Value>,
+        <T as TryFrom<Value>>::Error: std::fmt::Debug,
+        F: Fn(&T) -> bool,
+    {
+        self.0.block_on(self.0.cf.param.wait_for(name, predicate, timeout))
+    }
+
+    /// Check if a parameter supports persistent storage
+    ///
+    /// Blocking version of [crate::subsystems::param::Param::is_persistent()].
+    pub fn is_persistent(&self, name: &str) -> Result<bool> {
+        self.0.block_on(self.0.cf.param.is_persistent(name))
+    }
+
+    /// Get the extended type of a parameter
+    ///
+    /// Blocking version of [crate::subsystems::param::Param::get_extended_type()].
+    pub fn get_extended_type(&self, name: &str) -> Result<u8> {
+        self.0.block_on(self.0.cf.param.get_extended_type(name))
+    }
+
+    /// Get the default value of a parameter
+    ///
+    /// Blocking version of [crate::subsystems::param::Param::get_default_value()].
+    pub fn get_default_value(&self, name: &str) -> Result<Value> {
+        self.0.block_on(self.0.cf.param.get_default_value(name))
+    }
+
+    /// Get the persistent state of a parameter
+    ///
+    /// Blocking version of [crate::subsystems::param::Param::persistent_get_state()].
+    pub fn persistent_get_state(&self, name: &str) -> Result<PersistentParamState> {
+        self.0.block_on(self.0.cf.param.persistent_get_state(name))
+    }
+
+    /// Store the current value of a parameter to persistent storage
+    ///
+    /// Blocking version of [crate::subsystems::param::Param::persistent_store()].
+    pub fn persistent_store(&self, name: &str) -> Result<()> {
+        self.0.block_on(self.0.cf.param.persistent_store(name))
+    }
+
+    /// Clear the persistent value of a parameter
+    ///
+    /// Blocking version of [crate::subsystems::param::Param::persistent_clear()].
+    pub fn persistent_clear(&self, name: &str) -> Result<()> {
+        self.0.block_on(self.0.cf.param.persistent_clear(name))
+    }
+
+    /// Export the persistent parameter storage
+    ///
+    /// Blocking version of [crate::subsystems::param::Param::persistent_export()].
+    pub fn persistent_export(&self) -> Result<PersistentParamProfile> {
+        self.0.block_on(self.0.cf.param.persistent_export())
+    }
+
+    /// Import a persistent parameter profile
+    ///
+    /// Blocking version of [crate::subsystems::param::Param::persistent_import()].
+    pub fn persistent_import(
+        &self,
+        profile: &PersistentParamProfile,
+        dry_run: bool,
+    ) -> Result<PersistentImportReport> {
+        self.0.block_on(self.0.cf.param.persistent_import(profile, dry_run))
+    }
+
+    /// Get the value of many parameters
+    ///
+    /// Blocking version of [crate::subsystems::param::Param::get_many()].
+    pub fn get_many<S: AsRef<str>>(&self, names: &[S]) -> Result<BTreeMap<String, Value>> {
+        self.0.block_on(self.0.cf.param.get_many(names))
+    }
+
+    /// Get the value of all the parameters
+    ///
+    /// Blocking version of [crate::subsystems::param::Param::get_all()].
+    pub fn get_all(&self) -> Result<BTreeMap<String, Value>> {
+        self.0.block_on(self.0.cf.param.get_all())
+    }
+
+    /// Take a snapshot of the value of all the parameters
+    ///
+    /// Blocking version of [crate::subsystems::param::Param::snapshot()].
+    pub fn snapshot(&self) -> Result<ParamSnapshot> {
+        self.0.block_on(self.0.cf.param.snapshot())
+    }
+
+    /// Differences between the default value of the parameters and their current value
+    ///
+    /// Blocking version of [crate::subsystems::param::Param::diff_against_defaults()].
+    pub fn diff_against_defaults(&self) -> Result<Vec<ParamDifference>> {
+        self.0.block_on(self.0.cf.param.diff_against_defaults())
+    }
+
+    /// Restore the parameter values of a snapshot
+    ///
+    /// Blocking version of [crate::subsystems::param::Param::restore()].
+    pub fn restore(&self, snapshot: &ParamSnapshot) -> Result<ParamRestoreReport> {
+        self.0.block_on(self.0.cf.param.restore(snapshot))
+    }
+
+    /// Get the names of all the parameter groups, in alphabetical order
+    pub fn groups(&self) -> Vec<String> {
+        self.0.cf.param.groups()
+    }
+
+    /// Get the names of the parameters matching a glob pattern
+    ///
+    /// See [crate::subsystems::param::Param::names_matching()].
+    pub fn names_matching(&self, pattern: &str) -> Vec<String> {
+        self.0.cf.param.names_matching(pattern)
+    }
+
+    /// Get all the parameters of a group
+    ///
+    /// Blocking version of [crate::subsystems::param::Param::group()].
+    pub fn group(&self, group: &str) -> Result<Vec<ParamEntry>> {
+        self.0.block_on(self.0.cf.param.group(group))
+    }
+}
+
+/// # Parameter changes
+///
+/// Iterator over the parameter changes returned by [Param::watch_change()], [Param::watch()] and
+/// [Param::watch_group()].
+pub struct ParamChanges<T = (String, Value)> {
+    stream: Pin<Box<dyn Stream<Item = T> + Send>>,
+    runtime: Arc<Runtime>,
+}
+
+impl<T> Iterator for ParamChanges<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.runtime.block_on(self.stream.next())
+    }
+}
+
+/// # Blocking memory subsystem
+///
+/// Blocking version of [crate::subsystems::memory::Memory]. The memory types returned by [Memory::open_memory()]
+/// are the async ones, their functions can be called with [Crazyflie::block_on()].
+pub struct Memory(Handle);
+
+impl Memory {
+    /// Get the list of memories in the Crazyflie, optionally filtered by type.
+    ///
+    /// See [crate::subsystems::memory::Memory::get_memories()].
+    pub fn get_memories(
+        &self,
+        memory_type: Option<crate::subsystems::memory::MemoryType>,
+    ) -> Vec<&MemoryDevice> {
+        self.0.cf.memory.get_memories(memory_type)
+    }
+
+    /// Get a specific memory by its ID
+    ///
+    /// Blocking version of [crate::subsystems::memory::Memory::open_memory()].
+    pub fn open_memory<T: FromMemoryBackend>(&self, memory: MemoryDevice) -> Option<Result<T>> {
+        self.0.block_on(self.0.cf.memory.open_memory(memory))
+    }
+
+    /// Close a memory
+    ///
+    /// Blocking version of [crate::subsystems::memory::Memory::close_memory()].
+    pub fn close_memory<T: FromMemoryBackend>(&self, device: T) -> Result<()> {
+        self.0.block_on(self.0.cf.memory.close_memory(device))
+    }
+
+    /// Get a specific memory by its ID and initialize it according to the defaults
+    ///
+    /// Blocking version of [crate::subsystems::memory::Memory::initialize_memory()].
+    pub fn initialize_memory<T: FromMemoryBackend>(&self, memory: MemoryDevice) -> Option<Result<T>> {
+        self.0.block_on(self.0.cf.memory.initialize_memory(memory))
+    }
+}
+
+/// # Blocking low level setpoint subsystem
+///
+/// Blocking version of [crate::subsystems::commander::Commander].
+pub struct Commander(Handle);
+
+impl Commander {
+    /// Sends a Roll, Pitch, Yawrate, and Thrust setpoint to the Crazyflie
+    ///
+    /// Blocking version of [crate::subsystems::commander::Commander::setpoint_rpyt()].
+    pub fn setpoint_rpyt(&self, roll: f32, pitch: f32, yawrate: f32, thrust: u16) -> Result<()> {
+        self.0.block_on(self.0.cf.commander.setpoint_rpyt(roll, pitch, yawrate, thrust))
+    }
+
+    /// Sends an absolute position setpoint in world coordinates
+    ///
+    /// Blocking version of [crate::subsystems::commander::Commander::setpoint_position()].
+    pub fn setpoint_position(&self, x: f32, y: f32, z: f32, yaw: f32) -> Result<()> {
+        self.0.block_on(self.0.cf.commander.setpoint_position(x, y, z, yaw))
+    }
+
+    /// Sends a velocity setpoint in the world frame
+    ///
+    /// Blocking version of [crate::subsystems::commander::Commander::setpoint_velocity_world()].
+    pub fn setpoint_velocity_world(&self, vx: f32, vy: f32, vz: f32, yawrate: f32) -> Result<()> {
+        self.0.block_on(self.0.cf.commander.setpoint_velocity_world(vx, vy, vz, yawrate))
+    }
+
+    /// Sends a setpoint with absolute height, roll, pitch, and yaw rate commands
+    ///
+    /// Blocking version of [crate::subsystems::commander::Commander::setpoint_zdistance()].
+    pub fn setpoint_zdistance(&self, roll: f32, pitch: f32, yawrate: f32, zdistance: f32) -> Result<()> {
+        self.0.block_on(self.0.cf.commander.setpoint_zdistance(roll, pitch, yawrate, zdistance))
+    }
+
+    /// Sends a setpoint with absolute height and x/y velocity commands in the body-fixed frame
+    ///
+    /// Blocking version of [crate::subsystems::commander::Commander::setpoint_hover()].
+    pub fn setpoint_hover(&self, vx: f32, vy: f32, yawrate: f32, zdistance: f32) -> Result<()> {
+        self.0.block_on(self.0.cf.commander.setpoint_hover(vx, vy, yawrate, zdistance))
+    }
+
+    /// Sends a manual control setpoint for roll, pitch, yaw rate, and thrust percentage
+    ///
+    /// Blocking version of [crate::subsystems::commander::Commander::setpoint_manual()].
+    pub fn setpoint_manual(&self, roll: f32, pitch: f32, yawrate: f32, thrust_percentage: f32, rate: bool) -> Result<()> {
+        self.0.block_on(self.0.cf.commander.setpoint_manual(roll, pitch, yawrate, thrust_percentage, rate))
+    }
+
+    /// Sends a full state setpoint: position, velocity, acceleration, attitude and angular rates
+    ///
+    /// Blocking version of [crate::subsystems::commander::Commander::setpoint_full_state()].
+    pub fn setpoint_full_state(&self, state: &FullState) -> Result<()> {
+        self.0.block_on(self.0.cf.commander.setpoint_full_state(state))
+    }
+
+    /// Sends an altitude-hold setpoint: roll and pitch angles, yaw rate and vertical velocity
+    ///
+    /// Blocking version of [crate::subsystems::commander::Commander::setpoint_alt_hold()].
+    pub fn setpoint_alt_hold(&self, roll: f32, pitch: f32, yawrate: f32, vz: f32) -> Result<()> {
+        self.0.block_on(self.0.cf.commander.setpoint_alt_hold(roll, pitch, yawrate, vz))
+    }
+
+    /// Sends a CPPM emulation setpoint
+    ///
+    /// Blocking version of [crate::subsystems::commander::Commander::setpoint_cppm()].
+    pub fn setpoint_cppm(&self, roll: u16, pitch: u16, yaw: u16, thrust: u16, aux: &[u16]) -> Result<()> {
+        self.0.block_on(self.0.cf.commander.setpoint_cppm(roll, pitch, yaw, thrust, aux))
+    }
+
+    /// Sends a STOP setpoint, immediately stopping the motors
+    ///
+    /// Blocking version of [crate::subsystems::commander::Commander::setpoint_stop()].
+    pub fn setpoint_stop(&self) -> Result<()> {
+        self.0.block_on(self.0.cf.commander.setpoint_stop())
+    }
+
+    /// Notify the firmware that low-level setpoints have stopped
+    ///
+    /// Blocking version of [crate::subsystems::commander::Commander::notify_setpoint_stop()].
+    pub fn notify_setpoint_stop(&self, remain_valid_milliseconds: u32) -> Result<()> {
+        self.0.block_on(self.0.cf.commander.notify_setpoint_stop(remain_valid_milliseconds))
+    }
+
+    /// Sends a setpoint
+    ///
+    /// Blocking version of [crate::subsystems::commander::Commander::send_setpoint()].
+    pub fn send_setpoint(&self, setpoint: Setpoint) -> Result<()> {
+        self.0.block_on(self.0.cf.commander.send_setpoint(setpoint))
+    }
+
+    /// Start streaming setpoints at `rate` Hz with the default options
+    ///
+    /// See [crate::subsystems::commander::Commander::stream()]. The stream runs in the runtime of the blocking
+    /// Crazyflie, the target is set with [SetpointStream::set()] and the stream is stopped when dropped, or with
+    /// [Crazyflie::block_on()] on [SetpointStream::stop()].
+    pub fn stream(&self, rate: f32) -> Result<SetpointStream> {
+        let _runtime = self.0.runtime.enter();
+        self.0.cf.commander.stream(rate)
+    }
+
+    /// Start streaming setpoints at `rate` Hz
+    ///
+    /// See [crate::subsystems::commander::Commander::stream_with_options()] and [Commander::stream()].
+    pub fn stream_with_options(&self, rate: f32, options: SetpointStreamOptions) -> Result<SetpointStream> {
+        let _runtime = self.0.runtime.enter();
+        self.0.cf.commander.stream_with_options(rate, options)
+    }
+}
+
+/// # Blocking high-level commander subsystem
+///
+/// Blocking version of [crate::subsystems::high_level_commander::HighLevelCommander]. Like the async version, the
+/// functions returns once the command has been sent, they do not wait for the movement to be completed.
+pub struct HighLevelCommander(Handle);
+
+impl HighLevelCommander {
+    /// Set the group mask for the high-level commander
+    ///
+    /// Blocking version of [crate::subsystems::high_level_commander::HighLevelCommander::set_group_mask()].
+    pub fn set_group_mask(&self, group_mask: u8) -> Result<()> {
+        self.0.block_on(self.0.cf.high_level_commander.set_group_mask(group_mask))
+    }
+
+    /// Take off vertically from the current x-y position to the given target height
+    ///
+    /// Blocking version of [crate::subsystems::high_level_commander::HighLevelCommander::take_off()].
+    pub fn take_off(&self, height: f32, yaw: Option<f32>, duration: f32, group_mask: Option<u8>) -> Result<()> {
+        self.0.block_on(self.0.cf.high_level_commander.take_off(height, yaw, duration, group_mask))
+    }
+
+    /// Land vertically from the current x-y position to the given target height
+    ///
+    /// Blocking version of [crate::subsystems::high_level_commander::HighLevelCommander::land()].
+    pub fn land(&self, height: f32, yaw: Option<f32>, duration: f32, group_mask: Option<u8>) -> Result<()> {
+        self.0.block_on(self.0.cf.high_level_commander.land(height, yaw, duration, group_mask))
+    }
+
+    /// Stop the current high-level command and disable motors
+    ///
+    /// Blocking version of [crate::subsystems::high_level_commander::HighLevelCommander::stop()].
+    pub fn stop(&self, group_mask: Option<u8>) -> Result<()> {
+        self.0.block_on(self.0.cf.high_level_commander.stop(group_mask))
+    }
+
+    /// Move to an absolute or relative position with smooth path planning
+    ///
+    /// Blocking version of [crate::subsystems::high_level_commander::HighLevelCommander::go_to()].
+    #[allow(clippy::too_many_arguments)]
+    pub fn go_to(&self, x: f32, y: f32, z: f32, yaw: f32, duration: f32, relative: bool, linear: bool, group_mask: Option<u8>) -> Result<()> {
+        self.0.block_on(
+            self.0
+                .cf
+                .high_level_commander
+                .go_to(x, y, z, yaw, duration, relative, linear, group_mask),
+        )
+    }
+
+    /// Fly a spiral segment
+    ///
+    /// Blocking version of [crate::subsystems::high_level_commander::HighLevelCommander::spiral()].
+    #[allow(clippy::too_many_arguments)]
+    pub fn spiral(&self, angle: f32, initial_radius: f32, final_radius: f32, altitude_gain: f32, duration: f32, sideways: bool, clockwise: bool, group_mask: Option<u8>) -> Result<()> {
+        self.0.block_on(self.0.cf.high_level_commander.spiral(
+            angle,
+            initial_radius,
+            final_radius,
+            altitude_gain,
+            duration,
+            sideways,
+            clockwise,
+            group_mask,
+        ))
+    }
+
+    /// Define a trajectory previously uploaded to memory
+    ///
+    /// Blocking version of [crate::subsystems::high_level_commander::HighLevelCommander::define_trajectory()].
+    pub fn define_trajectory(&self, trajectory_id: u8, memory_offset: u32, num_pieces: u8, trajectory_type: Option<u8>) -> Result<()> {
+        self.0.block_on(self.0.cf.high_level_commander.define_trajectory(
+            trajectory_id,
+            memory_offset,
+            num_pieces,
+            trajectory_type,
+        ))
+    }
+
+    /// Start executing a previously defined trajectory
+    ///
+    /// Blocking version of [crate::subsystems::high_level_commander::HighLevelCommander::start_trajectory()].
+    pub fn start_trajectory(&self, trajectory_id: u8, time_scale: f32, relative_position: bool, relative_yaw: bool, reversed: bool, group_mask: Option<u8>) -> Result<()> {
+        self.0.block_on(self.0.cf.high_level_commander.start_trajectory(
+            trajectory_id,
+            time_scale,
+            relative_position,
+            relative_yaw,
+            reversed,
+            group_mask,
+        ))
+    }
+}
@@ -0,0 +1,562 @@
+//! # Firmware flashing over the CRTP bootloader
+//!
+//! The Crazyflie STM32 and nRF51 firmware can be updated over the radio using the bootloaders. A [Bootloader] is
+//! connected either by resetting a running Crazyflie into its bootloader with [Bootloader::reset_to_bootloader()]
+//! (warm boot), or to a Crazyflie started in bootloader mode with [Bootloader::connect()] (cold boot).
+//!
+//! The official firmware releases are zip archives containing a `manifest.json` and the firmware binaries for each
+//! target. They can be loaded with [Release::open()] and flashed with [Bootloader::flash_release()]. The deck
+//! firmware contained in the releases are flashed through the deck memory instead, with a
+//! [DeckUpgrader](crate::subsystems::memory::DeckUpgrader).
+//!
+//! ```no_run
+//! # use crazyflie_lib::bootloader::{Bootloader, Release};
+//! # async fn example() -> crazyflie_lib::Result<()> {
+//! let context = crazyflie_link::LinkContext::new();
+//! let release = Release::open("firmware-cf2-2025.02.zip")?;
+//!
+//! let bootloader = Bootloader::reset_to_bootloader(&context, "radio://0/80/2M/E7E7E7E7E7").await?;
+//! bootloader
+//!     .flash_release(&release, "cf2", |done, total| println!("Flashing {}/{} bytes", done, total))
+//!     .await?;
+//! bootloader.reset_to_firmware().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::crazyflie::{nrf_command_packet, NrfCommand, TARGET_NRF51};
+use crate::crtp_utils::CrtpTransport;
+use crate::{Error, Result};
+use crazyflie_link::{LinkContext, Packet};
+use futures::lock::Mutex;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io::{Read, Seek};
+use std::path::Path;
+use std::time::Duration;
+
+const TARGET_STM32: u8 = 0xFF;
+
+const CMD_GET_INFO: u8 = 0x10;
+const CMD_LOAD_BUFFER: u8 = 0x14;
+const CMD_WRITE_FLASH: u8 = 0x18;
+const CMD_READ_FLASH: u8 = 0x1C;
+
+/// Number of bytes carried by a buffer load or flash read packet
+const CHUNK_SIZE: usize = 25;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_millis(1000);
+const REQUEST_RETRIES: usize = 5;
+
+/// Bootloader packets use the port 15 and channel 3 (header 0xFF)
+const BOOTLOADER_PORT: u8 = 0x0F;
+const BOOTLOADER_CHANNEL: u8 = 0x03;
+
+/// Microcontroller of the Crazyflie that can be flashed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// Main microcontroller, running the Crazyflie firmware
+    Stm32,
+    /// Radio and power management microcontroller
+    Nrf51,
+}
+
+impl Target {
+    fn id(&self) -> u8 {
+        match self {
+            Target::Stm32 => TARGET_STM32,
+            Target::Nrf51 => TARGET_NRF51,
+        }
+    }
+}
+
+/// Flash layout of a bootloader target
+#[derive(Debug, Clone)]
+pub struct FlashInfo {
+    /// Size of a flash page in bytes
+    pub page_size: u16,
+    /// Number of pages that can be loaded in the bootloader RAM buffer
+    pub buffer_pages: u16,
+    /// Total number of flash pages
+    pub flash_pages: u16,
+    /// First page available for the firmware, the pages before are used by the bootloader
+    pub start_page: u16,
+    /// CPU unique ID
+    pub cpu_id: [u8; 12],
+    /// Bootloader protocol version, if reported
+    pub version: Option<u8>,
+}
+
+/// # Crazyflie bootloader
+///
+/// Connection to the Crazyflie bootloaders. See the [bootloader module documentation](crate::bootloader) for more
+/// context and information.
+pub struct Bootloader {
+    link: Box<dyn CrtpTransport>,
+    lock: Mutex<()>,
+}
+
+/// Disable safelink in a radio URI, unless the URI already sets it
+fn without_safelink(uri: &str) -> String {
+    let query = uri.split_once('?').map(|(_, query)| query);
+    let has_safelink = query.is_some_and(|query| query.split('&').any(|option| option.starts_with("safelink=")));
+    match (query, has_safelink) {
+        (_, true) => uri.to_owned(),
+        (Some(_), false) => format!("{}&safelink=0", uri),
+        (None, false) => format!("{}?safelink=0", uri),
+    }
+}
+
+impl Bootloader {
+    /// Reset a Crazyflie running its firmware into the bootloader and connect to it
+    ///
+    /// The Crazyflie is reached at `uri` and restarts in bootloader mode on a dedicated radio address. Only radio
+    /// URIs are supported. No [Crazyflie](crate::Crazyflie) object should be connected to the same Crazyflie.
+    pub async fn reset_to_bootloader(link_context: &LinkContext, uri: &str) -> Result<Self> {
+        let radio = uri
+            .strip_prefix("radio://")
+            .and_then(|rest| rest.split('/').next())
+            .ok_or_else(|| {
+                Error::InvalidArgument("Only radio URIs can be reset to bootloader".to_owned())
+            })?;
+
+        let link = link_context.open_link(&without_safelink(uri)).await?;
+
+        let answer = request(
+            &link,
+            nrf_command_packet(NrfCommand::ResetInit, &[]),
+            &[TARGET_NRF51, NrfCommand::ResetInit as u8],
+        )
+        .await?;
+        if answer.len() < 6 {
+            return Err(Error::ProtocolError(
+                "Malformed reset init answer".to_owned(),
+            ));
+        }
+        // The bootloader address is 0xB1 followed by the 4 bytes of the answer in big-endian
+        let mut address = vec![0xB1];
+        address.extend(answer[2..6].iter().rev());
+
+        // The reset packet is not acknowledged, it is sent a few times to make sure it goes through
+        for _ in 0..10 {
+            link.send_packet(nrf_command_packet(NrfCommand::Reset, &[0])).await?;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        link.close().await;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        let uri = format!(
+            "radio://{}/0/2M/{}?safelink=0&ackfilter=0",
+            radio,
+            hex::encode_upper(address)
+        );
+        Self::connect(link_context, &uri).await
+    }
+
+    /// Connect to a Crazyflie already in bootloader mode
+    ///
+    /// A Crazyflie started in bootloader mode (by holding the power button at startup) listens on
+    /// `radio://0/0/2M/E7E7E7E7E7` or `radio://0/110/2M/E7E7E7E7E7` depending on its bootloader version.
+    pub async fn connect(link_context: &LinkContext, uri: &str) -> Result<Self> {
+        let link = link_context.open_link(uri).await?;
+        Ok(Self::from_transport(link))
+    }
+
+    /// Use the bootloader through a custom transport
+    pub fn from_transport(transport: impl CrtpTransport + 'static) -> Self {
+        Self {
+            link: Box::new(transport),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Read the flash layout of a target
+    ///
+    /// Returns [Error::ProtocolError] if the layout reported by the bootloader is invalid, for example with an empty
+    /// page size.
+    pub async fn flash_info(&self, target: Target) -> Result<FlashInfo> {
+        let _lock = self.lock.lock().await;
+        self.get_info(target).await
+    }
+
+    async fn get_info(&self, target: Target) -> Result<FlashInfo> {
+        let answer = self.request(vec![target.id(), CMD_GET_INFO], 2).await?;
+        if answer.len() < 22 {
+            return Err(Error::ProtocolError(
+                "Malformed bootloader info answer".to_owned(),
+            ));
+        }
+
+        let info = FlashInfo {
+            page_size: u16::from_le_bytes(answer[2..4].try_into()?),
+            buffer_pages: u16::from_le_bytes(answer[4..6].try_into()?),
+            flash_pages: u16::from_le_bytes(answer[6..8].try_into()?),
+            start_page: u16::from_le_bytes(answer[8..10].try_into()?),
+            cpu_id: answer[10..22].try_into()?,
+            version: answer.get(22).copied(),
+        };
+        if info.page_size == 0 || info.flash_pages == 0 || info.start_page >= info.flash_pages {
+            return Err(Error::ProtocolError(format!(
+                "Invalid bootloader flash layout: page size {}, {} pages starting at page {}",
+                info.page_size, info.flash_pages, info.start_page
+            )));
+        }
+
+        Ok(info)
+    }
+
+    /// Read one flash page of a target
+    pub async fn read_page(&self, target: Target, page: u16) -> Result<Vec<u8>> {
+        let _lock = self.lock.lock().await;
+        let info = self.get_info(target).await?;
+        self.read_flash(target, page, info.page_size as usize).await
+    }
+
+    async fn read_flash(&self, target: Target, page: u16, length: usize) -> Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(length);
+        while data.len() < length {
+            let mut request = vec![target.id(), CMD_READ_FLASH];
+            request.extend_from_slice(&page.to_le_bytes());
+            request.extend_from_slice(&(data.len() as u16).to_le_bytes());
+            let answer = self.request(request, 6).await?;
+            if answer.len() <= 6 {
+                return Err(Error::ProtocolError(
+                    "Malformed flash read answer".to_owned(),
+                ));
+            }
+            data.extend_from_slice(&answer[6..]);
+        }
+        data.truncate(length);
+        Ok(data)
+    }
+
+    /// Write data to the flash of a target, starting at `start_page`
+    ///
+    /// The data are loaded in the bootloader buffer, written to flash and read back to verify them. The
+    /// `progress_callback` is called with the number of bytes written and verified so far and the total number of
+    /// bytes to write.
+    ///
+    /// Returns an error if the data do not fit in the flash, if the pages are part of the bootloader, if the
+    /// bootloader reports a write error or if the verification fails.
+    pub async fn write_pages<F>(
+        &self,
+        target: Target,
+        start_page: u16,
+        data: &[u8],
+        mut progress_callback: F,
+    ) -> Result<()>
+    where
+        F: FnMut(usize, usize),
+    {
+        let _lock = self.lock.lock().await;
+        let info = self.get_info(target).await?;
+        let page_size = info.page_size as usize;
+        let page_count = data.len().div_ceil(page_size);
+
+        if start_page < info.start_page
+            || start_page as usize + page_count > info.flash_pages as usize
+        {
+            return Err(Error::InvalidArgument(format!(
+                "Cannot write {} pages at page {}: the firmware area is pages {} to {}",
+                page_count,
+                start_page,
+                info.start_page,
+                info.flash_pages - 1
+            )));
+        }
+
+        progress_callback(0, data.len());
+        let pages_per_write = (info.buffer_pages as usize).max(1) * page_size;
+        for (i, chunk) in data.chunks(pages_per_write).enumerate() {
+            let first_page = start_page + (i * pages_per_write / page_size) as u16;
+
+            for (buffer_page, page_data) in chunk.chunks(page_size).enumerate() {
+                self.load_buffer(target, buffer_page as u16, page_data).await?;
+            }
+            self.write_flash(target, first_page, chunk.len().div_ceil(page_size) as u16)
+                .await?;
+
+            for (page, page_data) in chunk.chunks(page_size).enumerate() {
+                let flash = self
+                    .read_flash(target, first_page + page as u16, page_data.len())
+                    .await?;
+                if flash != page_data {
+                    return Err(Error::MemoryError(format!(
+                        "Verification failed for flash page {}",
+                        first_page as usize + page
+                    )));
+                }
+            }
+
+            progress_callback(i * pages_per_write + chunk.len(), data.len());
+        }
+
+        Ok(())
+    }
+
+    /// Flash a firmware image on a target
+    ///
+    /// The image is written at the start of the firmware area, see [Bootloader::write_pages()].
+    pub async fn flash<F>(&self, target: Target, image: &[u8], progress_callback: F) -> Result<()>
+    where
+        F: FnMut(usize, usize),
+    {
+        let info = self.flash_info(target).await?;
+        self.write_pages(target, info.start_page, image, progress_callback)
+            .await
+    }
+
+    /// Flash the STM32 and nRF51 firmware contained in a release for a platform
+    ///
+    /// Only the firmware of `platform`, for example "cf2" for a Crazyflie 2.x or "bolt" for a Crazyflie Bolt, are
+    /// flashed. The platform cannot be detected by the bootloader, it must be the platform of the connected device.
+    /// The deck firmware of the release are ignored. The `progress_callback` is called with the number of bytes
+    /// flashed so far and the total number of bytes to flash for all the targets.
+    ///
+    /// Returns [Error::InvalidArgument] if the release contains no firmware for the platform, or more than one
+    /// firmware for the same target.
+    pub async fn flash_release<F>(&self, release: &Release, platform: &str, mut progress_callback: F) -> Result<()>
+    where
+        F: FnMut(usize, usize),
+    {
+        let mut images: Vec<(Target, &Vec<u8>)> = Vec::new();
+        for file in release.files.iter().filter(|file| file.platform == platform) {
+            let Some(target) = file.target() else {
+                continue;
+            };
+            if images.iter().any(|(flashed, _)| *flashed == target) {
+                return Err(Error::InvalidArgument(format!(
+                    "Release contains more than one {:?} firmware for platform {}",
+                    target, platform
+                )));
+            }
+            images.push((target, &file.data));
+        }
+        if images.is_empty() {
+            return Err(Error::InvalidArgument(format!(
+                "Release contains no firmware for platform {}",
+                platform
+            )));
+        }
+        let total = images.iter().map(|(_, data)| data.len()).sum();
+
+        let mut flashed = 0;
+        for (target, data) in images {
+            self.flash(target, data, |done, _| progress_callback(flashed + done, total))
+                .await?;
+            flashed += data.len();
+        }
+
+        Ok(())
+    }
+
+    /// Reset the Crazyflie to its firmware and close the bootloader connection
+    pub async fn reset_to_firmware(self) -> Result<()> {
+        {
+            let _lock = self.lock.lock().await;
+            request(
+                self.link.as_ref(),
+                nrf_command_packet(NrfCommand::ResetInit, &[]),
+                &[TARGET_NRF51, NrfCommand::ResetInit as u8],
+            )
+            .await?;
+            self.link
+                .send_packet(nrf_command_packet(NrfCommand::Reset, &[1]))
+                .await?;
+        }
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        self.link.close().await;
+        Ok(())
+    }
+
+    async fn load_buffer(&self, target: Target, buffer_page: u16, data: &[u8]) -> Result<()> {
+        for (i, chunk) in data.chunks(CHUNK_SIZE).enumerate() {
+            let mut packet = vec![0xFF, target.id(), CMD_LOAD_BUFFER];
+            packet.extend_from_slice(&buffer_page.to_le_bytes());
+            packet.extend_from_slice(&((i * CHUNK_SIZE) as u16).to_le_bytes());
+            packet.extend_from_slice(chunk);
+            self.link.send_packet(packet.into()).await?;
+        }
+        Ok(())
+    }
+
+    async fn write_flash(&self, target: Target, flash_page: u16, page_count: u16) -> Result<()> {
+        let mut request = vec![target.id(), CMD_WRITE_FLASH];
+        request.extend_from_slice(&0u16.to_le_bytes());
+        request.extend_from_slice(&flash_page.to_le_bytes());
+        request.extend_from_slice(&page_count.to_le_bytes());
+
+        let answer = self.request(request, 2).await?;
+        if answer.len() < 4 {
+            return Err(Error::ProtocolError(
+                "Malformed flash write answer".to_owned(),
+            ));
+        }
+        if answer[2] != 1 {
+            return Err(Error::MemoryError(format!(
+                "Error writing flash page {}: {}",
+                flash_page, answer[3]
+            )));
+        }
+        Ok(())
+    }
+
+    /// Send a request and return the answer data, the answer starts with the first `prefix_len` bytes of the request
+    async fn request(&self, data: Vec<u8>, prefix_len: usize) -> Result<Vec<u8>> {
+        let prefix = data[..prefix_len].to_vec();
+        let mut packet = vec![0xFF];
+        packet.extend(data);
+        request(self.link.as_ref(), packet.into(), &prefix).await
+    }
+}
+
+/// Send a bootloader packet and wait for the answer starting with `prefix`, retrying on timeout
+async fn request(link: &dyn CrtpTransport, packet: Packet, prefix: &[u8]) -> Result<Vec<u8>> {
+    for _ in 0..REQUEST_RETRIES {
+        link.send_packet(packet.clone()).await?;
+
+        let answer = tokio::time::timeout(REQUEST_TIMEOUT, async {
+            loop {
+                let answer = link.recv_packet().await?;
+                if answer.get_port() == BOOTLOADER_PORT
+                    && answer.get_channel() == BOOTLOADER_CHANNEL
+                    && answer.get_data().starts_with(prefix)
+                {
+                    return Ok::<_, Error>(answer);
+                }
+            }
+        })
+        .await;
+
+        match answer {
+            Ok(answer) => return Ok(answer?.get_data().clone()),
+            Err(_) => continue,
+        }
+    }
+
+    Err(Error::Timeout)
+}
+
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    files: BTreeMap<String, ManifestFile>,
+    #[serde(default)]
+    release: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ManifestFile {
+    platform: String,
+    target: String,
+    #[serde(rename = "type")]
+    file_type: String,
+}
+
+/// # Firmware release
+///
+/// Content of an official firmware release archive: a zip file containing a `manifest.json` describing the
+/// firmware binaries it contains.
+#[derive(Debug, Clone)]
+pub struct Release {
+    /// Release name, for example "2025.02"
+    pub release: Option<String>,
+    /// Firmware files of the release
+    pub files: Vec<ReleaseFile>,
+}
+
+/// A firmware file from a [Release]
+#[derive(Debug, Clone)]
+pub struct ReleaseFile {
+    /// Name of the file in the archive
+    pub name: String,
+    /// Platform of the firmware: "cf2", "bolt", ... or "deck" for deck firmware
+    pub platform: String,
+    /// Target of the firmware: "stm32" and "nrf51" for the Crazyflie or the deck memory section name for
+    /// deck firmware, for example "bcAI:gap8"
+    pub target: String,
+    /// Type of the file, "fw" for firmware
+    pub file_type: String,
+    /// Content of the file
+    pub data: Vec<u8>,
+}
+
+impl ReleaseFile {
+    /// Bootloader target of this file, `None` for deck firmware
+    pub fn target(&self) -> Option<Target> {
+        if self.platform == "deck" || self.file_type != "fw" {
+            return None;
+        }
+        match self.target.as_str() {
+            "stm32" => Some(Target::Stm32),
+            "nrf51" => Some(Target::Nrf51),
+            _ => None,
+        }
+    }
+}
+
+impl Release {
+    /// Open a release zip file
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_reader(std::fs::File::open(path)?)
+    }
+
+    /// Read a release zip archive
+    ///
+    /// Returns an error if the archive does not contain a valid `manifest.json` or if a file listed in the manifest
+    /// is missing.
+    pub fn from_reader(reader: impl Read + Seek) -> Result<Self> {
+        let mut archive = zip::ZipArchive::new(reader).map_err(zip_error)?;
+
+        let manifest: Manifest = {
+            let manifest = archive.by_name("manifest.json").map_err(zip_error)?;
+            serde_json::from_reader(manifest).map_err(|e| {
+                Error::InvalidArgument(format!("Invalid release manifest: {}", e))
+            })?
+        };
+
+        let mut files = Vec::new();
+        for (name, file) in manifest.files {
+            let mut data = Vec::new();
+            archive
+                .by_name(&name)
+                .map_err(zip_error)?
+                .read_to_end(&mut data)?;
+
+            files.push(ReleaseFile {
+                name,
+                platform: file.platform,
+                target: file.target,
+                file_type: file.file_type,
+                data,
+            });
+        }
+
+        Ok(Self {
+            release: manifest.release,
+            files,
+        })
+    }
+}
+
+fn zip_error(error: zip::result::ZipError) -> Error {
+    Error::InvalidArgument(format!("Invalid release archive: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn safelink_disabled_once() {
+        assert_eq!(without_safelink("radio://0/80/2M/E7E7E7E7E7"), "radio://0/80/2M/E7E7E7E7E7?safelink=0");
+        assert_eq!(
+            without_safelink("radio://0/80/2M/E7E7E7E7E7?ackfilter=0"),
+            "radio://0/80/2M/E7E7E7E7E7?ackfilter=0&safelink=0"
+        );
+        assert_eq!(
+            without_safelink("radio://0/80/2M/E7E7E7E7E7?safelink=0"),
+            "radio://0/80/2M/E7E7E7E7E7?safelink=0"
+        );
+    }
+}
@@ -0,0 +1,374 @@
+//! # CRTP packet capture and replay
+//!
+//! The packets exchanged with a Crazyflie can be recorded to a file, either for the whole connection with
+//! [Crazyflie::connect_from_transport_with_capture()](crate::Crazyflie::connect_from_transport_with_capture) or from
+//! a point in time with [Crazyflie::start_capture()](crate::Crazyflie::start_capture). The capture is written by
+//! the lib uplink and downlink tasks, so it contains exactly the packets sent to and received from the link.
+//!
+//! A capture of a whole connection can be replayed with a [ReplayTransport]: the recorded downlink packets are fed
+//! back to a new [Crazyflie](crate::Crazyflie) instance. This allows to debug log decoding or parameter handling
+//! offline, with exactly the data sent by the Crazyflie. A capture can also be read packet by packet with a
+//! [CaptureReader].
+//!
+//! ```no_run
+//! # use crazyflie_lib::{Crazyflie, NoTocCache};
+//! # use crazyflie_lib::capture::ReplayTransport;
+//! # async fn example() -> crazyflie_lib::Result<()> {
+//! let context = crazyflie_link::LinkContext::new();
+//! let link = context.open_link("radio://0/60/2M/E7E7E7E7E7").await?;
+//! let cf = Crazyflie::connect_from_transport_with_capture(link, NoTocCache, "flight.crtp").await?;
+//! // ...
+//! cf.disconnect().await;
+//!
+//! // Later, offline
+//! let replay = ReplayTransport::open("flight.crtp")?;
+//! let cf = Crazyflie::connect_from_transport(replay, NoTocCache).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ## File format
+//!
+//! A capture file starts with a 16 bytes header followed by one record per packet. All numbers are little-endian.
+//!
+//! | Header field | Size | Content |
+//! |--------------|------|---------|
+//! | magic | 7 | `CRTPCAP` in ASCII |
+//! | version | 1 | Format version, currently `1` |
+//! | start time | 8 | Capture start time in microseconds since the UNIX epoch |
+//!
+//! | Record field | Size | Content |
+//! |--------------|------|---------|
+//! | timestamp | 8 | Host time in microseconds since the capture start |
+//! | direction | 1 | `0` for uplink (sent to the Crazyflie), `1` for downlink (received from the Crazyflie) |
+//! | header | 1 | CRTP header byte, `port << 4 \| channel` |
+//! | length | 1 | Length of the packet data |
+//! | data | length | Packet data |
+
+use crate::crtp_utils::CrtpTransport;
+use crate::{Error, Result};
+use async_trait::async_trait;
+use crazyflie_link::Packet;
+use flume as channel;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+const MAGIC: &[u8; 7] = b"CRTPCAP";
+const VERSION: u8 = 1;
+
+/// Maximum time a replayed packet waits for the lib to send the packets preceding it in the capture
+const REPLAY_UPLINK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Direction of a captured packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Packet sent to the Crazyflie
+    Uplink,
+    /// Packet received from the Crazyflie
+    Downlink,
+}
+
+/// A packet read from a capture
+#[derive(Debug, Clone)]
+pub struct CaptureRecord {
+    /// Host time since the start of the capture
+    pub timestamp: Duration,
+    /// Direction of the packet
+    pub direction: Direction,
+    /// The packet
+    pub packet: Packet,
+}
+
+/// # Capture writer
+///
+/// Writes packets in the capture [file format](crate::capture#file-format).
+pub struct CaptureWriter {
+    writer: Box<dyn Write + Send>,
+    start: Instant,
+}
+
+impl CaptureWriter {
+    /// Create a capture file, replacing it if it exists
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+
+    /// Start a capture on any writer
+    ///
+    /// The capture header is written immediately.
+    pub fn new(mut writer: impl Write + Send + 'static) -> Result<Self> {
+        let start_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        writer.write_all(&start_time.to_le_bytes())?;
+
+        Ok(Self {
+            writer: Box::new(writer),
+            start: Instant::now(),
+        })
+    }
+
+    /// Record a packet, timestamped with the current host time
+    pub fn write_packet(&mut self, direction: Direction, packet: &Packet) -> Result<()> {
+        let timestamp = self.start.elapsed().as_micros() as u64;
+        let direction = match direction {
+            Direction::Uplink => 0,
+            Direction::Downlink => 1,
+        };
+
+        let mut record = timestamp.to_le_bytes().to_vec();
+        record.extend_from_slice(&[direction, packet.get_header(), packet.get_data().len() as u8]);
+        record.extend_from_slice(packet.get_data());
+        self.writer.write_all(&record)?;
+
+        Ok(())
+    }
+
+    /// Flush the buffered records to the underlying writer
+    pub fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+/// # Capture reader
+///
+/// Reads a capture file. The reader is an iterator over the captured packets.
+pub struct CaptureReader {
+    reader: Box<dyn Read + Send>,
+    start_time: SystemTime,
+}
+
+impl CaptureReader {
+    /// Open a capture file
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+
+    /// Read a capture from any reader
+    ///
+    /// Returns an error if the capture header is not valid.
+    pub fn new(mut reader: impl Read + Send + 'static) -> Result<Self> {
+        let mut header = [0; 16];
+        reader.read_exact(&mut header)?;
+
+        if &header[0..7] != MAGIC {
+            return Err(Error::InvalidArgument("Not a CRTP capture".to_owned()));
+        }
+        if header[7] != VERSION {
+            return Err(Error::InvalidArgument(format!(
+                "Unsupported CRTP capture version: {}",
+                header[7]
+            )));
+        }
+        let start_time = u64::from_le_bytes(header[8..16].try_into()?);
+
+        Ok(Self {
+            reader: Box::new(reader),
+            start_time: UNIX_EPOCH + Duration::from_micros(start_time),
+        })
+    }
+
+    /// Host time at which the capture was started
+    pub fn start_time(&self) -> SystemTime {
+        self.start_time
+    }
+
+    fn read_record(&mut self) -> Result<Option<CaptureRecord>> {
+        let mut header = [0; 11];
+        match self.reader.read_exact(&mut header) {
+            Ok(()) => (),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+
+        let timestamp = Duration::from_micros(u64::from_le_bytes(header[0..8].try_into()?));
+        let direction = match header[8] {
+            0 => Direction::Uplink,
+            1 => Direction::Downlink,
+            d => {
+                return Err(Error::ProtocolError(format!(
+                    "Invalid packet direction in capture: {}",
+                    d
+                )))
+            }
+        };
+
+        let mut packet = vec![header[9]];
+        packet.resize(1 + header[10] as usize, 0);
+        self.reader.read_exact(&mut packet[1..])?;
+
+        Ok(Some(CaptureRecord {
+            timestamp,
+            direction,
+            packet: packet.into(),
+        }))
+    }
+}
+
+impl Iterator for CaptureReader {
+    type Item = Result<CaptureRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+/// Capture shared between the Crazyflie object and its uplink and downlink tasks
+#[derive(Clone, Default)]
+pub(crate) struct CaptureSlot(Arc<Mutex<Option<CaptureWriter>>>);
+
+impl CaptureSlot {
+    pub(crate) fn record(&self, direction: Direction, packet: &Packet) {
+        let mut capture = self.0.lock().unwrap();
+        if let Some(writer) = capture.as_mut()
+            && let Err(e) = writer.write_packet(direction, packet)
+        {
+            println!("Error writing CRTP capture, capture stopped: {}", e);
+            *capture = None;
+        }
+    }
+
+    pub(crate) fn start(&self, writer: CaptureWriter) -> Result<()> {
+        let previous = self.0.lock().unwrap().replace(writer);
+        match previous {
+            Some(mut previous) => previous.flush(),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn stop(&self) -> Result<()> {
+        let previous = self.0.lock().unwrap().take();
+        match previous {
+            Some(mut previous) => previous.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// # Replay transport
+///
+/// [CrtpTransport] feeding the downlink packets of a capture back to the lib. The packets are delivered with the
+/// same timing as when they were captured, counted from the opening of the transport. The content of the packets
+/// sent by the lib is ignored, so the code driving the replayed [Crazyflie](crate::Crazyflie) is expected to make
+/// the same requests as when the capture was made.
+///
+/// A downlink packet is not delivered before the lib has sent as many packets as were sent before it in the capture,
+/// so that answers do not arrive before their request when the replay runs slower than the capture. This wait is
+/// limited to one second after the captured time of the packet.
+///
+/// The transport closes once all the captured packets have been delivered.
+pub struct ReplayTransport {
+    downlink: channel::Receiver<Packet>,
+    closed: watch::Sender<Option<String>>,
+    sent: watch::Sender<usize>,
+    task: JoinHandle<()>,
+}
+
+impl ReplayTransport {
+    /// Open a capture file for replay
+    ///
+    /// The replay starts immediately, this function must be called from within a Tokio runtime.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::new(CaptureReader::open(path)?)
+    }
+
+    /// Replay the packets of a capture reader
+    ///
+    /// The whole capture is read before starting the replay, an error is returned if it is malformed.
+    pub fn new(reader: CaptureReader) -> Result<Self> {
+        // Downlink packets with the number of uplink packets sent before them
+        let mut records = Vec::new();
+        let mut uplink_count = 0;
+        for record in reader {
+            let record = record?;
+            match record.direction {
+                Direction::Uplink => uplink_count += 1,
+                Direction::Downlink => records.push((uplink_count, record)),
+            }
+        }
+
+        let (downlink_tx, downlink) = channel::unbounded();
+        let (closed, _) = watch::channel(None);
+        let closed_task = closed.clone();
+        let (sent, _) = watch::channel(0);
+        let mut sent_task = sent.subscribe();
+
+        let task = tokio::spawn(async move {
+            let start = tokio::time::Instant::now();
+            for (uplink_count, record) in records {
+                let deadline = start + record.timestamp;
+                let _ = tokio::time::timeout_at(
+                    deadline + REPLAY_UPLINK_TIMEOUT,
+                    sent_task.wait_for(|&sent| sent >= uplink_count),
+                )
+                .await;
+                tokio::time::sleep_until(deadline).await;
+                if downlink_tx.send_async(record.packet).await.is_err() {
+                    break;
+                }
+            }
+            drop(downlink_tx);
+            closed_task.send_replace(Some("End of capture".to_owned()));
+        });
+
+        Ok(Self {
+            downlink,
+            closed,
+            sent,
+            task,
+        })
+    }
+}
+
+#[async_trait]
+impl CrtpTransport for ReplayTransport {
+    async fn send_packet(&self, _packet: Packet) -> Result<()> {
+        if self.closed.borrow().is_some() {
+            return Err(Error::Disconnected);
+        }
+        self.sent.send_modify(|sent| *sent += 1);
+        Ok(())
+    }
+
+    async fn recv_packet(&self) -> Result<Packet> {
+        self.downlink
+            .recv_async()
+            .await
+            .map_err(|_| Error::Disconnected)
+    }
+
+    async fn close(&self) {
+        self.task.abort();
+        self.closed.send_if_modified(|closed| {
+            if closed.is_none() {
+                *closed = Some("Replay closed".to_owned());
+                true
+            } else {
+                false
+            }
+        });
+    }
+
+    async fn wait_close(&self) -> String {
+        let mut closed = self.closed.subscribe();
+        match closed.wait_for(|closed| closed.is_some()).await {
+            Ok(reason) => reason.clone().unwrap_or_default(),
+            Err(_) => "Replay closed".to_owned(),
+        }
+    }
+}
+
+impl Drop for ReplayTransport {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
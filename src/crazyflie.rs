@@ -7,12 +7,22 @@ use crate::subsystems::log::Log;
 use crate::subsystems::memory::Memory;
 use crate::subsystems::param::Param;
 
-use crate::crtp_utils::{CrtpDispatch, TocCache};
+use crate::capture::{CaptureSlot, CaptureWriter, Direction};
+use crate::crtp_utils::{CrtpDispatch, CrtpTransport, TocCache};
+use crate::events::{ConnectionEvent, EventSender};
+use crate::reconnect::{LinkState, ReconnectPolicy, ReconnectingTransport};
+use crate::simulation::SimulatedCrazyflie;
+use crate::subsystems::log::LogRestorer;
+use crate::subsystems::param::ParamRestorer;
 use crate::subsystems::platform::Platform;
 use crate::{Error, Result};
 use crate::{MIN_SUPPORTED_PROTOCOL_VERSION, MAX_SUPPORTED_PROTOCOL_VERSION};
 use flume as channel;
 use futures::lock::Mutex;
+use futures::Stream;
+use std::future::Future;
+use std::path::Path;
+use tokio::sync::watch;
 use tokio::task::JoinHandle;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering::Relaxed;
@@ -64,15 +74,29 @@ impl Drop for ConnectGuard {
     }
 }
 
+/// Bootloader target ID of the nRF51, used to send commands to the nRF51 firmware and bootloader
+pub(crate) const TARGET_NRF51: u8 = 0xFE;
+
 #[repr(u8)]
 #[derive(Debug, Clone, Copy)]
-enum NrfCommand {
+pub(crate) enum NrfCommand {
     /// Power off nRF51, STM32, and decks. Named `AllOff` in firmware.
     PowerOffAll = 0x01,
     /// Power off STM32 and decks, keeping nRF51 alive. Named `SysOff` in firmware.
     PowerOffStm32Domain = 0x02,
     /// Power on STM32 and decks. Named `SysOn` in firmware.
     PowerOnStm32Domain = 0x03,
+    /// Reset the system. The argument selects the image to boot: 0 for the bootloader and 1 for the firmware.
+    Reset = 0xF0,
+    /// Prepare a reset, the answer contains the address used by the bootloader.
+    ResetInit = 0xFF,
+}
+
+/// Build a packet sending a command to the nRF51
+pub(crate) fn nrf_command_packet(cmd: NrfCommand, args: &[u8]) -> crazyflie_link::Packet {
+    let mut packet = vec![0xFF, TARGET_NRF51, cmd as u8];
+    packet.extend_from_slice(args);
+    packet.into()
 }
 
 // CRTP ports
@@ -93,6 +117,10 @@ pub(crate) const LINK_PORT: u8 = 15;
 /// by the lib user or as a result of a connection loss, the object cannot be reconnected. A new one need to be created
 /// to connect again.
 ///
+/// Alternatively, connecting with [Crazyflie::connect_from_uri_with_reconnect()] makes the object re-open the link
+/// automatically when the connection is lost. The running log blocks are then restored and the existing
+/// [LogStream](crate::subsystems::log::LogStream) continue yielding data after a gap.
+///
 /// See the [crazyflie-lib crate root documentation](crate) for more context and information.
 pub struct Crazyflie {
     /// Log subsystem access
@@ -115,8 +143,11 @@ pub struct Crazyflie {
     pub link_service: LinkService,
     uplink_task: Mutex<Option<JoinHandle<()>>>,
     dispatch_task: Mutex<Option<JoinHandle<()>>>,
+    restore_task: Option<JoinHandle<()>>,
     disconnect: Arc<AtomicBool>,
-    link: Arc<crazyflie_link::Connection>,
+    link: Arc<dyn CrtpTransport>,
+    events: EventSender,
+    capture: CaptureSlot,
 }
 
 impl Crazyflie {
@@ -153,19 +184,155 @@ impl Crazyflie {
         link: crazyflie_link::Connection,
         toc_cache: T,
     ) -> Result<Self>
+    where
+        T: TocCache + Send + Sync + 'static,
+    {
+        Self::connect_from_transport(link, toc_cache).await
+    }
+
+    /// Connect to a simulated Crazyflie
+    ///
+    /// Opens an in-process link to the [SimulatedCrazyflie] and connects to it exactly like to a real Crazyflie.
+    /// This allows to run code that uses the [Crazyflie] object in tests, without a radio.
+    ///
+    /// See the [simulation module documentation](crate::simulation) for more information.
+    pub async fn connect_from_simulation<T>(
+        simulation: &SimulatedCrazyflie,
+        toc_cache: T,
+    ) -> Result<Self>
+    where
+        T: TocCache + Send + Sync + 'static,
+    {
+        Self::connect_from_transport(simulation.open_link(), toc_cache).await
+    }
+
+    /// Open a Crazyflie connection to a given URI that automatically reconnects
+    ///
+    /// Works like [Crazyflie::connect_from_uri()], except that when the connection is lost the link is re-opened in
+    /// the background following the reconnection `policy`. The TOCs fetched at connection time are reused: after
+    /// reconnecting, the log blocks are created and started again and the parameter values are read again when next
+    /// accessed. The Crazyflie firmware is expected to be the same as when first connected.
+    ///
+    /// While reconnecting, requests to the Crazyflie wait for the link to be back. The Crazyflie is disconnected, and
+    /// [Crazyflie::wait_disconnect()] returns, only when [Crazyflie::disconnect()] is called or when the reconnection
+    /// gives up after `policy.max_attempts` attempts.
+    ///
+    /// An error is returned if the link cannot be opened the first time or if the Crazyflie connection fails.
+    pub async fn connect_from_uri_with_reconnect<T>(
+        link_context: Arc<crazyflie_link::LinkContext>,
+        uri: &str,
+        toc_cache: T,
+        policy: ReconnectPolicy,
+    ) -> Result<Self>
+    where
+        T: TocCache + Send + Sync + 'static,
+    {
+        let uri = uri.to_owned();
+        let open = move || {
+            let link_context = link_context.clone();
+            let uri = uri.clone();
+            async move { Ok(link_context.open_link(&uri).await?) }
+        };
+
+        Self::connect_with_reconnect(open, toc_cache, policy).await
+    }
+
+    /// Connect a Crazyflie that automatically reconnects, using a custom transport
+    ///
+    /// The `open` function is called to open the transport, first at connection time and then every time the
+    /// transport fails. See [Crazyflie::connect_from_uri_with_reconnect()] for more information.
+    pub async fn connect_with_reconnect<F, Fut, L, T>(
+        open: F,
+        toc_cache: T,
+        policy: ReconnectPolicy,
+    ) -> Result<Self>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<L>> + Send + 'static,
+        L: CrtpTransport + 'static,
+        T: TocCache + Send + Sync + 'static,
+    {
+        let open = Box::new(move || {
+            let transport = open();
+            Box::pin(async move {
+                let transport: Arc<dyn CrtpTransport> = Arc::new(transport.await?);
+                Ok(transport)
+            }) as futures::future::BoxFuture<'static, Result<Arc<dyn CrtpTransport>>>
+        });
+        let transport = ReconnectingTransport::new(open, policy).await?;
+        let link_state = transport.subscribe();
+
+        Self::connect(Arc::new(transport), toc_cache, Some(link_state), CaptureSlot::default()).await
+    }
+
+    /// Connect a Crazyflie using a custom transport
+    ///
+    /// Connect a Crazyflie over any packet transport implementing the [CrtpTransport] trait. This allows to
+    /// connect the lib to a Crazyflie or a simulator using another medium than the ones supported by the
+    /// [crazyflie-link](crazyflie_link) crate.
+    ///
+    /// This function will return an error if anything goes wrong in the connection process.
+    pub async fn connect_from_transport<L, T>(
+        transport: L,
+        toc_cache: T,
+    ) -> Result<Self>
+    where
+        L: CrtpTransport + 'static,
+        T: TocCache + Send + Sync + 'static,
+    {
+        Self::connect(Arc::new(transport), toc_cache, None, CaptureSlot::default()).await
+    }
+
+    /// Connect a Crazyflie using a custom transport and capture all the packets to a file
+    ///
+    /// Works like [Crazyflie::connect_from_transport()] and records all the packets exchanged from the start of the
+    /// connection in the file at `path`. The capture can be replayed with a
+    /// [ReplayTransport](crate::capture::ReplayTransport), see the [capture module documentation](crate::capture).
+    ///
+    /// The capture lasts until [Crazyflie::stop_capture()] is called or the Crazyflie is disconnected.
+    pub async fn connect_from_transport_with_capture<L, T>(
+        transport: L,
+        toc_cache: T,
+        path: impl AsRef<Path>,
+    ) -> Result<Self>
+    where
+        L: CrtpTransport + 'static,
+        T: TocCache + Send + Sync + 'static,
+    {
+        let capture = CaptureSlot::default();
+        capture.start(CaptureWriter::create(path)?)?;
+
+        Self::connect(Arc::new(transport), toc_cache, None, capture).await
+    }
+
+    async fn connect<T>(
+        link: Arc<dyn CrtpTransport>,
+        toc_cache: T,
+        link_state: Option<watch::Receiver<LinkState>>,
+        capture: CaptureSlot,
+    ) -> Result<Self>
     where
         T: TocCache + Send + Sync + 'static,
     {
         let disconnect = Arc::new(AtomicBool::new(false));
 
+        let events = EventSender::new();
+        events.emit(ConnectionEvent::Connecting);
+
         // Downlink dispatcher
-        let link = Arc::new(link);
-        let mut dispatcher = CrtpDispatch::new(link.clone(), disconnect.clone());
+        let mut dispatcher = CrtpDispatch::new(
+            link.clone(),
+            disconnect.clone(),
+            events.clone(),
+            capture.clone(),
+        );
 
         // Uplink queue
         let disconnect_uplink = disconnect.clone();
         let (uplink, rx) = channel::unbounded();
         let link_uplink = link.clone();
+        let events_uplink = events.clone();
+        let capture_uplink = capture.clone();
         let uplink_task = tokio::spawn(async move {
                 while !disconnect_uplink.load(Relaxed) {
                     match tokio::time::timeout(
@@ -173,7 +340,9 @@ impl Crazyflie {
                         ).await
                     {
                         Ok(Ok(pk)) => {
+                            capture_uplink.record(Direction::Uplink, &pk);
                             if link_uplink.send_packet(pk).await.is_err() {
+                                events_uplink.link_closed(link_uplink.as_ref()).await;
                                 return;
                             }
                         }
@@ -216,8 +385,8 @@ impl Crazyflie {
         // Create subsystems one by one
         // The future is passed to join!() later down so that all modules initializes at the same time
         // The get_port_receiver calls are guaranteed to work if the same port is not used twice (any way to express that at compile time?)
-        let log_future = Log::new(log_downlink, uplink.clone(), toc_cache.clone());
-        let param_future = Param::new(param_downlink, uplink.clone(), toc_cache.clone());
+        let log_future = Log::new(log_downlink, uplink.clone(), toc_cache.clone(), &events);
+        let param_future = Param::new(param_downlink, uplink.clone(), toc_cache.clone(), &events);
         let memory_future = Memory::new(memory_downlink, uplink.clone());
 
         let commander = Commander::new(uplink.clone());
@@ -231,7 +400,19 @@ impl Crazyflie {
         let param = param?;
         let memory = memory?;
 
+        // Restore the subsystems state each time the link is re-opened
+        let restore_task = link_state.map(|link_state| {
+            Self::spawn_restore_task(
+                link_state,
+                log.restorer(),
+                param.restorer(),
+                events.clone(),
+                protocol_version,
+            )
+        });
+
         let (uplink_task, dispatch_task) = guard.disarm();
+        events.emit(ConnectionEvent::Connected { protocol_version });
         Ok(Crazyflie {
             log,
             param,
@@ -244,8 +425,36 @@ impl Crazyflie {
             link_service,
             uplink_task: Mutex::new(Some(uplink_task)),
             dispatch_task: Mutex::new(Some(dispatch_task)),
+            restore_task,
             disconnect,
             link,
+            events,
+            capture,
+        })
+    }
+
+    fn spawn_restore_task(
+        mut link_state: watch::Receiver<LinkState>,
+        log: LogRestorer,
+        param: ParamRestorer,
+        events: EventSender,
+        protocol_version: u8,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            while link_state.changed().await.is_ok() {
+                let state = link_state.borrow_and_update().clone();
+                match state {
+                    LinkState::Connected => {
+                        param.restore().await;
+                        if let Err(e) = log.restore().await {
+                            println!("Error restoring log blocks after reconnection: {}", e);
+                        }
+                        events.emit(ConnectionEvent::Connected { protocol_version });
+                    }
+                    LinkState::Reconnecting => events.emit(ConnectionEvent::Connecting),
+                    LinkState::Closed(_) => break,
+                }
+            }
         })
     }
 
@@ -260,6 +469,10 @@ impl Crazyflie {
         // Set disconnect to true, will make both uplink and dispatcher task quit
         self.disconnect.store(true, Relaxed);
 
+        if let Some(restore_task) = &self.restore_task {
+            restore_task.abort();
+        }
+
         // Wait for both task to finish
         if let Some(uplink_task) = self.uplink_task.lock().await.take() {
             uplink_task.await.expect("Uplink task failed");
@@ -269,6 +482,57 @@ impl Crazyflie {
         }
 
         self.link.close().await;
+        self.events.link_closed(self.link.as_ref()).await;
+
+        if let Err(e) = self.capture.stop() {
+            println!("Error closing CRTP capture: {}", e);
+        }
+    }
+
+    /// Start capturing the packets exchanged with the Crazyflie to a file
+    ///
+    /// All the packets sent and received from now on are recorded in the file at `path`, see the
+    /// [capture module documentation](crate::capture) for the file format. A capture already running is stopped.
+    ///
+    /// A capture started after the connection cannot be replayed since it lacks the connection sequence, use
+    /// [Crazyflie::connect_from_transport_with_capture()] to capture a whole connection.
+    pub fn start_capture(&self, path: impl AsRef<Path>) -> Result<()> {
+        self.capture.start(CaptureWriter::create(path)?)
+    }
+
+    /// Stop the running packet capture, if any
+    ///
+    /// Returns an error if the last captured packets cannot be written to the file.
+    pub fn stop_capture(&self) -> Result<()> {
+        self.capture.stop()
+    }
+
+    /// Get a stream of the connection events
+    ///
+    /// The stream first produces the events of the initial connection and the last event that happened since, and
+    /// then produces the new events as they happen. It is meant to follow the connection state, for example to
+    /// display it in a GUI, without polling
+    /// [LinkService::get_statistics()](crate::subsystems::link_service::LinkService::get_statistics) or waiting on
+    /// [Crazyflie::wait_disconnect()].
+    ///
+    /// ```no_run
+    /// # use crazyflie_lib::{Crazyflie, ConnectionEvent};
+    /// # use futures::StreamExt;
+    /// # async fn example(cf: &Crazyflie) {
+    /// let mut events = cf.events();
+    /// while let Some(event) = events.next().await {
+    ///     match event {
+    ///         ConnectionEvent::LinkQualityDegraded { link_quality } => {
+    ///             println!("Poor link: {:.0}%", link_quality * 100.0)
+    ///         }
+    ///         ConnectionEvent::Disconnected { reason } => println!("Disconnected: {}", reason),
+    ///         _ => (),
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn events(&self) -> impl Stream<Item = ConnectionEvent> + use<> {
+        self.events.subscribe()
     }
 
     /// Power off the STM32 and deck subsystem
@@ -309,11 +573,8 @@ impl Crazyflie {
         uri: &str,
         cmd: NrfCommand,
     ) -> Result<()> {
-        const TARGET_NRF51: u8 = 0xFE;
-
         let link = link_context.open_link(uri).await?;
-        let packet: crazyflie_link::Packet = vec![0xFF, TARGET_NRF51, cmd as u8].into();
-        link.send_packet(packet).await?;
+        link.send_packet(nrf_command_packet(cmd, &[])).await?;
         tokio::time::sleep(Duration::from_millis(500)).await;
         // Best-effort close: ignore errors in case the target has already powered down.
         let _ = link.close().await;
@@ -339,5 +600,8 @@ impl Crazyflie {
 impl Drop for Crazyflie {
     fn drop(&mut self) {
         self.disconnect.store(true, Relaxed);
+        if let Some(restore_task) = &self.restore_task {
+            restore_task.abort();
+        }
     }
 }
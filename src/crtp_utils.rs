@@ -3,6 +3,8 @@
 //! These functionalities are currently all private, some might be useful for the user code as well, lets make them
 //! public when needed.
 
+use crate::capture::{CaptureSlot, Direction};
+use crate::events::{ConnectionEvent, EventSender, LINK_QUALITY_DEGRADED_THRESHOLD};
 use crate::{Error, Result};
 use async_trait::async_trait;
 use crazyflie_link::Packet;
@@ -13,28 +15,129 @@ use tokio::task::JoinHandle;
 use std::collections::BTreeMap;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering::Relaxed;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::{
     convert::{TryFrom, TryInto},
     sync::Arc,
 };
 
+/// A transport able to carry CRTP packets to and from a Crazyflie
+///
+/// The [Crazyflie](crate::Crazyflie) object only needs a bidirectional packet pipe to communicate with the Crazyflie.
+/// This trait is implemented for [crazyflie_link::Connection], which covers radio and USB, and can be implemented
+/// to connect the lib over any other medium: a TCP or UDP connection to a simulator, a UART bridge or a recorded
+/// capture for example. A transport is connected with [Crazyflie::connect_from_transport()](crate::Crazyflie::connect_from_transport).
+///
+/// The transport is expected to be reliable and to deliver packets in order, as is the case of the Crazyflie radio
+/// link with safelink enabled.
+///
+/// # Example
+///
+/// ```rust
+/// use async_trait::async_trait;
+/// use crazyflie_lib::{CrtpTransport, Error, Result};
+/// use crazyflie_link::Packet;
+///
+/// /// Transport over a pair of channels, for example connected to a bridge task
+/// struct ChannelTransport {
+///     tx: flume::Sender<Packet>,
+///     rx: flume::Receiver<Packet>,
+/// }
+///
+/// #[async_trait]
+/// impl CrtpTransport for ChannelTransport {
+///     async fn send_packet(&self, packet: Packet) -> Result<()> {
+///         self.tx.send_async(packet).await.map_err(|_| Error::Disconnected)
+///     }
+///
+///     async fn recv_packet(&self) -> Result<Packet> {
+///         self.rx.recv_async().await.map_err(|_| Error::Disconnected)
+///     }
+///
+///     async fn close(&self) {}
+///
+///     async fn wait_close(&self) -> String {
+///         futures::future::pending().await
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait CrtpTransport: Send + Sync {
+    /// Send a packet to the Crazyflie
+    ///
+    /// Returns an error if the transport is closed.
+    async fn send_packet(&self, packet: Packet) -> Result<()>;
+
+    /// Receive the next packet from the Crazyflie
+    ///
+    /// Returns an error if the transport is closed.
+    async fn recv_packet(&self) -> Result<Packet>;
+
+    /// Close the transport
+    ///
+    /// Once this function returns, [CrtpTransport::send_packet()] and [CrtpTransport::recv_packet()] should return
+    /// errors and [CrtpTransport::wait_close()] should return.
+    async fn close(&self);
+
+    /// Wait for the transport to be closed and return a human-readable reason for the disconnection
+    async fn wait_close(&self) -> String;
+
+    /// Get radio link statistics
+    ///
+    /// Only relevant for radio transports, the default implementation returns `None`.
+    async fn link_statistics(&self) -> Option<crazyflie_link::RadioLinkStatistics> {
+        None
+    }
+}
+
+#[async_trait]
+impl CrtpTransport for crazyflie_link::Connection {
+    async fn send_packet(&self, packet: Packet) -> Result<()> {
+        Ok(crazyflie_link::Connection::send_packet(self, packet).await?)
+    }
+
+    async fn recv_packet(&self) -> Result<Packet> {
+        Ok(crazyflie_link::Connection::recv_packet(self).await?)
+    }
+
+    async fn close(&self) {
+        crazyflie_link::Connection::close(self).await
+    }
+
+    async fn wait_close(&self) -> String {
+        crazyflie_link::Connection::wait_close(self).await
+    }
+
+    async fn link_statistics(&self) -> Option<crazyflie_link::RadioLinkStatistics> {
+        crazyflie_link::Connection::link_statistics(self).await
+    }
+}
+
 pub struct CrtpDispatch {
-    link: Arc<crazyflie_link::Connection>,
+    link: Arc<dyn CrtpTransport>,
     // port_callbacks: [Arc<Mutex<Option<Sender<Packet>>>>; 15]
     port_channels: BTreeMap<u8, Sender<Packet>>,
-    disconnect: Arc<AtomicBool>
+    disconnect: Arc<AtomicBool>,
+    events: EventSender,
+    capture: CaptureSlot,
 }
 
+/// Interval at which the dispatcher checks the radio link quality
+const LINK_QUALITY_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
 impl CrtpDispatch {
     pub fn new(
-        link: Arc<crazyflie_link::Connection>,
+        link: Arc<dyn CrtpTransport>,
         disconnect: Arc<AtomicBool>,
+        events: EventSender,
+        capture: CaptureSlot,
     ) -> Self {
         CrtpDispatch {
             link,
             port_channels: BTreeMap::new(),
-            disconnect
+            disconnect,
+            events,
+            capture,
         }
     }
 
@@ -53,11 +156,27 @@ impl CrtpDispatch {
         let link = self.link.clone();
         Ok(tokio::spawn(async move {
                 let _ = &self;
+                let mut last_quality_check = Instant::now();
+                let mut link_degraded = false;
                 while !self.disconnect.load(Relaxed) {                  
+                    if last_quality_check.elapsed() >= LINK_QUALITY_CHECK_INTERVAL {
+                        last_quality_check = Instant::now();
+                        if let Some(statistics) = link.link_statistics().await {
+                            let degraded = statistics.link_quality < LINK_QUALITY_DEGRADED_THRESHOLD;
+                            if degraded && !link_degraded {
+                                self.events.emit(ConnectionEvent::LinkQualityDegraded {
+                                    link_quality: statistics.link_quality,
+                                });
+                            }
+                            link_degraded = degraded;
+                        }
+                    }
+
                     match tokio::time::timeout(Duration::from_millis(200), link.recv_packet())
                         .await
                     {
                         Ok(Ok(packet)) => {
+                            self.capture.record(Direction::Downlink, &packet);
                             if packet.get_port() < 16 {
                                 let channel = self.port_channels.get(&packet.get_port()); // get(packet.get_port()).lock().await;
                                 if let Some(channel) = channel.as_ref() {
@@ -66,7 +185,11 @@ impl CrtpDispatch {
                             }
                         }
                         Err(_) => continue,
-                        Ok(Err(_)) => return, // Other side of the channel disappeared, link closed
+                        Ok(Err(_)) => {
+                            // Other side of the channel disappeared, link closed
+                            self.events.link_closed(link.as_ref()).await;
+                            return;
+                        }
                     }
                 }
             })
@@ -110,6 +233,7 @@ pub(crate) async fn fetch_toc<C, T, E>(
     uplink: channel::Sender<Packet>,
     downlink: channel::Receiver<Packet>,
     toc_cache: C,
+    events: &EventSender,
 ) -> Result<std::collections::BTreeMap<String, (u16, T)>>
 where
     C: TocCache,
@@ -134,6 +258,7 @@ where
     // Check cache first
     if let Some(toc_str) = toc_cache.get_toc(&cache_key) {
         toc = serde_json::from_str(&toc_str).map_err(|e| Error::InvalidParameter(format!("Failed to deserialize TOC cache: {}", e)))?;
+        events.emit(ConnectionEvent::TocFetched { port, from_cache: true });
         return Ok(toc);
     }
 
@@ -164,6 +289,7 @@ where
     let toc_str = serde_json::to_string(&toc).map_err(|e| Error::InvalidParameter(format!("Failed to serialize TOC: {}", e)))?;
     toc_cache.store_toc(&cache_key, &toc_str);
 
+    events.emit(ConnectionEvent::TocFetched { port, from_cache: false });
     Ok(toc)
 }
 
@@ -47,6 +47,18 @@ pub enum Error {
     MemoryError(String),
     /// Invalid parameter provided to a function. The String contains the reason.
     InvalidParameter(String),
+    /// File or stream input/output error. Returns the [std::io::Error].
+    IoError(std::io::Error),
+    /// Operation failed on more Crazyflies of a [Swarm](crate::swarm::Swarm) than accepted by its
+    /// [FailurePolicy](crate::swarm::FailurePolicy).
+    SwarmError {
+        /// Number of Crazyflies the operation was run on
+        total: usize,
+        /// Error of each Crazyflie that failed, with its URI
+        errors: Vec<(String, Error)>,
+    },
+    /// Command rejected by a [SafetyEnvelope](crate::safety::SafetyEnvelope). The String contains the reason.
+    SafetyError(String),
 }
 
 impl std::fmt::Display for Error {
@@ -68,6 +80,12 @@ impl std::fmt::Display for Error {
             Error::Timeout => write!(f, "Operation timed out"),
             Error::MemoryError(msg) => write!(f, "Memory error: {}", msg),
             Error::InvalidParameter(msg) => write!(f, "Invalid parameter: {}", msg),
+            Error::IoError(e) => write!(f, "IO error: {}", e),
+            Error::SwarmError { total, errors } => {
+                let errors: Vec<_> = errors.iter().map(|(uri, e)| format!("{}: {}", uri, e)).collect();
+                write!(f, "Swarm error: {} of {} Crazyflies failed ({})", errors.len(), total, errors.join(", "))
+            }
+            Error::SafetyError(msg) => write!(f, "Safety error: {}", msg),
         }
     }
 }
@@ -86,6 +104,12 @@ impl From<crazyflie_link::Error> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Self::IoError(error)
+    }
+}
+
 impl From<SpawnError> for Error {
     fn from(error: SpawnError) -> Self {
         Self::SystemError(format!("{}", error))
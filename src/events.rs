@@ -0,0 +1,115 @@
+//! Connection events
+//!
+//! The connection process and the background tasks of the [Crazyflie](crate::Crazyflie) object report the state of
+//! the connection as [ConnectionEvent]s. They can be received with [Crazyflie::events()](crate::Crazyflie::events).
+
+use crate::crtp_utils::CrtpTransport;
+use async_broadcast::{broadcast, InactiveReceiver, Sender};
+use futures::{Stream, StreamExt};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Link quality under which a [ConnectionEvent::LinkQualityDegraded] event is emitted
+pub(crate) const LINK_QUALITY_DEGRADED_THRESHOLD: f32 = 0.8;
+
+/// # Connection event
+///
+/// Events emitted during the lifetime of a Crazyflie connection.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionEvent {
+    /// The link is open and the connection to the Crazyflie is being established
+    ///
+    /// Also emitted when the link is being re-opened after a connection loss, when connected with
+    /// [Crazyflie::connect_from_uri_with_reconnect()](crate::Crazyflie::connect_from_uri_with_reconnect).
+    Connecting,
+    /// The Table Of Content of a subsystem has been fetched
+    TocFetched {
+        /// CRTP port of the subsystem (2 for param and 5 for log)
+        port: u8,
+        /// `true` if the TOC has been loaded from the TOC cache instead of being downloaded from the Crazyflie
+        from_cache: bool,
+    },
+    /// The Crazyflie is connected and all the subsystems are initialized
+    Connected {
+        /// CRTP protocol version of the Crazyflie
+        protocol_version: u8,
+    },
+    /// The radio link quality went under 80%
+    ///
+    /// Emitted once when crossing the threshold, it is emitted again only after the link quality has recovered.
+    LinkQualityDegraded {
+        /// Ratio of acknowledged radio packets, from 0.0 to 1.0
+        link_quality: f32,
+    },
+    /// The Crazyflie is disconnected. This is the last event of a connection, the event streams end after it.
+    Disconnected {
+        /// Human-readable reason for the disconnection
+        reason: String,
+    },
+}
+
+/// Emits the connection events, shared by the Crazyflie object and its background tasks
+#[derive(Debug, Clone)]
+pub(crate) struct EventSender {
+    /// Events replayed to the new subscribers: the events up to the first [ConnectionEvent::Connected] and the last
+    /// event after it, so that the history stays bounded on a link that keeps dropping
+    history: Arc<Mutex<Vec<ConnectionEvent>>>,
+    sender: Sender<ConnectionEvent>,
+    receiver: InactiveReceiver<ConnectionEvent>,
+}
+
+impl EventSender {
+    pub(crate) fn new() -> Self {
+        let (mut sender, receiver) = broadcast(100);
+        // Enable overflow mode so old events are dropped instead of blocking
+        sender.set_overflow(true);
+
+        Self {
+            history: Arc::default(),
+            sender,
+            receiver: receiver.deactivate(),
+        }
+    }
+
+    pub(crate) fn emit(&self, event: ConnectionEvent) {
+        let mut history = self.history.lock().unwrap();
+
+        // Nothing happens to a connection after it is disconnected
+        if matches!(history.last(), Some(ConnectionEvent::Disconnected { .. })) {
+            return;
+        }
+
+        // Only the last event is kept once connected, it replaces the previous one
+        if let Some(connected) = history
+            .iter()
+            .position(|event| matches!(event, ConnectionEvent::Connected { .. }))
+        {
+            history.truncate(connected + 1);
+        }
+
+        let disconnected = matches!(event, ConnectionEvent::Disconnected { .. });
+        history.push(event.clone());
+        // Fails if nobody is listening, the event is in the history anyway
+        let _ = self.sender.try_broadcast(event);
+
+        // Ends the event streams
+        if disconnected {
+            self.sender.close();
+        }
+    }
+
+    /// Emit the disconnected event with the reason reported by the link
+    pub(crate) async fn link_closed(&self, link: &dyn CrtpTransport) {
+        let reason = tokio::time::timeout(Duration::from_millis(500), link.wait_close())
+            .await
+            .unwrap_or_else(|_| "Link closed".to_owned());
+        self.emit(ConnectionEvent::Disconnected { reason });
+    }
+
+    pub(crate) fn subscribe(&self) -> impl Stream<Item = ConnectionEvent> + use<> {
+        let history = self.history.lock().unwrap();
+        let history_stream = futures::stream::iter(history.clone());
+
+        history_stream.chain(self.receiver.activate_cloned())
+    }
+}
@@ -32,8 +32,8 @@
 //!
 //! The basic procedure to use the lib is:
 //!  - Find the link URI to connect, either by scanning or as a config or user input
-//!  - Create a Crazyflie object from the URI or a connected Link, this will connect to the Crazyflie and initializes
-//!    the subsystems
+//!  - Create a Crazyflie object from the URI, a connected Link or any [CrtpTransport], this will connect to the
+//!    Crazyflie and initializes the subsystems
 //!  - Subsystems are available as public fields of the [Crazyflie] struct.
 //!  - Use the subsystems in the Crazyflie object to control the Crazyflie
 //!  - Drop the Crazyflie object or call [crazyflie::Crazyflie::disconnect()]
@@ -41,6 +41,15 @@
 //! All subsystems functions are only taking an un-mutable reference to self (`&self`), the intention is for the
 //! Crazyflie object to be shared between tasks using `Arc<>` or `Rc<>`.
 //!
+//! For synchronous code, the [blocking] module (enabled by the `blocking` feature) mirrors the main subsystems with
+//! blocking functions.
+//!
+//! To control many Crazyflies at once, the [swarm] module connects them in parallel and runs the same code on all of
+//! them.
+//!
+//! The [safety] module keeps the setpoints and high-level commands inside a geofence and velocity, tilt and altitude
+//! limits, and stops or lands the Crazyflie if its estimated position leaves these limits.
+//!
 //! For example:
 //! ``` no_run
 //! # async fn test() -> Result<(), Box<dyn std::error::Error>> {
@@ -72,6 +81,13 @@
 //! # }
 //! ```
 //!
+//! ## Testing without a Crazyflie
+//!
+//! The [simulation] module implements an in-process simulated Crazyflie that can be connected with
+//! [Crazyflie::connect_from_simulation()]. It allows to test code using this crate without a radio.
+//!
+//! The packets exchanged with a real Crazyflie can also be recorded and replayed later with the [capture] module.
+//!
 //! [crazyflie-link]: https://crates.io/crates/crazyflie-link
 
 #![warn(missing_docs)]
@@ -79,15 +95,27 @@
 mod crazyflie;
 mod crtp_utils;
 mod error;
+mod events;
+mod reconnect;
 mod value;
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod bootloader;
+pub mod capture;
+pub mod safety;
+pub mod simulation;
 pub mod subsystems;
+pub mod swarm;
 
 pub use crate::crazyflie::Crazyflie;
 pub use crate::error::{Error, Result};
-pub use crate::value::{Value, ValueType};
+pub use crate::events::ConnectionEvent;
+pub use crate::value::{Value, ValuePrimitive, ValueType};
 pub use crate::crtp_utils::TocCache;
 pub use crate::crtp_utils::NoTocCache;
+pub use crate::crtp_utils::CrtpTransport;
+pub use crate::reconnect::ReconnectPolicy;
 
 /// Minimum supported protocol version
 ///
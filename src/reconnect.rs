@@ -0,0 +1,232 @@
+//! Automatic reconnection of the CRTP transport
+//!
+//! The [ReconnectingTransport] wraps a function able to open a transport to the Crazyflie. When the current
+//! transport fails, it is re-opened in the background following a [ReconnectPolicy]. Packets sent and received by
+//! the lib wait for the reconnection instead of failing, this way the subsystems do not see the connection loss
+//! other than as a gap in communication.
+
+use crate::crtp_utils::CrtpTransport;
+use crate::{Error, Result};
+use async_trait::async_trait;
+use crazyflie_link::Packet;
+use futures::future::BoxFuture;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// # Automatic reconnection policy
+///
+/// Configures how a lost connection is re-opened when the Crazyflie is connected with
+/// [Crazyflie::connect_from_uri_with_reconnect()](crate::Crazyflie::connect_from_uri_with_reconnect).
+///
+/// The reconnection is attempted with an exponential backoff: the first attempt is made after `initial_delay`, the
+/// delay is then doubled after each failed attempt up to `max_delay`.
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    /// Delay before the first reconnection attempt
+    pub initial_delay: Duration,
+    /// Maximum delay between two reconnection attempts
+    pub max_delay: Duration,
+    /// Maximum number of reconnection attempts before giving up. `None` retries until
+    /// [Crazyflie::disconnect()](crate::Crazyflie::disconnect) is called.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(2),
+            max_attempts: None,
+        }
+    }
+}
+
+/// Function opening a new transport to the Crazyflie
+pub(crate) type OpenTransport =
+    Box<dyn Fn() -> BoxFuture<'static, Result<Arc<dyn CrtpTransport>>> + Send + Sync>;
+
+/// State of a reconnecting transport
+#[derive(Debug, Clone)]
+pub(crate) enum LinkState {
+    /// A transport is connected
+    Connected,
+    /// The transport has been lost and is being re-opened
+    Reconnecting,
+    /// The transport is closed for good, the string contains the reason
+    Closed(String),
+}
+
+struct Inner {
+    open: OpenTransport,
+    policy: ReconnectPolicy,
+    link: Mutex<(u64, Arc<dyn CrtpTransport>)>,
+    state: watch::Sender<LinkState>,
+}
+
+/// Transport re-opening the connection when it is lost
+pub(crate) struct ReconnectingTransport {
+    inner: Arc<Inner>,
+}
+
+impl ReconnectingTransport {
+    /// Open the transport a first time
+    ///
+    /// No reconnection is attempted if this first opening fails.
+    pub async fn new(open: OpenTransport, policy: ReconnectPolicy) -> Result<Self> {
+        let link = open().await?;
+        let (state, _) = watch::channel(LinkState::Connected);
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                open,
+                policy,
+                link: Mutex::new((0, link)),
+                state,
+            }),
+        })
+    }
+
+    /// Subscribe to the state of the transport
+    pub fn subscribe(&self) -> watch::Receiver<LinkState> {
+        self.inner.state.subscribe()
+    }
+
+    /// Wait for a transport to be connected and return it with its generation
+    async fn wait_link(&self) -> Result<(u64, Arc<dyn CrtpTransport>)> {
+        let mut state = self.inner.state.subscribe();
+        loop {
+            let current = state.borrow_and_update().clone();
+            match current {
+                LinkState::Connected => return Ok(self.inner.link.lock().unwrap().clone()),
+                LinkState::Closed(_) => return Err(Error::Disconnected),
+                LinkState::Reconnecting => {
+                    if state.changed().await.is_err() {
+                        return Err(Error::Disconnected);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Start reconnecting if the transport of this generation is still the current one
+    fn link_lost(&self, generation: u64) {
+        let current = self.inner.link.lock().unwrap().0;
+        if current != generation {
+            return;
+        }
+
+        let start = self.inner.state.send_if_modified(|state| {
+            if matches!(state, LinkState::Connected) {
+                *state = LinkState::Reconnecting;
+                true
+            } else {
+                false
+            }
+        });
+
+        if start {
+            tokio::spawn(reconnect(self.inner.clone(), generation));
+        }
+    }
+}
+
+async fn reconnect(inner: Arc<Inner>, generation: u64) {
+    let lost_link = inner.link.lock().unwrap().1.clone();
+    lost_link.close().await;
+
+    let mut state = inner.state.subscribe();
+    let mut delay = inner.policy.initial_delay;
+    let mut attempts = 0;
+
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => (),
+            _ = state.wait_for(|s| matches!(s, LinkState::Closed(_))) => return,
+        }
+
+        attempts += 1;
+        match (inner.open)().await {
+            Ok(link) => {
+                *inner.link.lock().unwrap() = (generation + 1, link.clone());
+                let connected = inner.state.send_if_modified(|state| {
+                    if matches!(state, LinkState::Reconnecting) {
+                        *state = LinkState::Connected;
+                        true
+                    } else {
+                        false
+                    }
+                });
+                // The transport has been closed while opening the link
+                if !connected {
+                    link.close().await;
+                }
+                return;
+            }
+            Err(e) => {
+                if inner.policy.max_attempts.is_some_and(|max| attempts >= max) {
+                    inner.state.send_replace(LinkState::Closed(format!(
+                        "Reconnection failed after {} attempts: {}",
+                        attempts, e
+                    )));
+                    return;
+                }
+            }
+        }
+
+        delay = std::cmp::min(delay * 2, inner.policy.max_delay);
+    }
+}
+
+#[async_trait]
+impl CrtpTransport for ReconnectingTransport {
+    async fn send_packet(&self, packet: Packet) -> Result<()> {
+        loop {
+            let (generation, link) = self.wait_link().await?;
+            match link.send_packet(packet.clone()).await {
+                Ok(()) => return Ok(()),
+                Err(_) => self.link_lost(generation),
+            }
+        }
+    }
+
+    async fn recv_packet(&self) -> Result<Packet> {
+        loop {
+            let (generation, link) = self.wait_link().await?;
+            match link.recv_packet().await {
+                Ok(packet) => return Ok(packet),
+                Err(_) => self.link_lost(generation),
+            }
+        }
+    }
+
+    async fn close(&self) {
+        self.inner.state.send_if_modified(|state| {
+            if matches!(state, LinkState::Closed(_)) {
+                false
+            } else {
+                *state = LinkState::Closed("Link closed".to_owned());
+                true
+            }
+        });
+
+        let link = self.inner.link.lock().unwrap().1.clone();
+        link.close().await;
+    }
+
+    async fn wait_close(&self) -> String {
+        let mut state = self.inner.state.subscribe();
+        match state.wait_for(|s| matches!(s, LinkState::Closed(_))).await {
+            Ok(state) => match &*state {
+                LinkState::Closed(reason) => reason.clone(),
+                _ => unreachable!(),
+            },
+            Err(_) => "Link closed".to_owned(),
+        }
+    }
+
+    async fn link_statistics(&self) -> Option<crazyflie_link::RadioLinkStatistics> {
+        let link = self.inner.link.lock().unwrap().1.clone();
+        link.link_statistics().await
+    }
+}
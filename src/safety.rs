@@ -0,0 +1,884 @@
+//! # Safety envelope
+//!
+//! A [SafetyEnvelope] sits in front of the [Commander] and the [HighLevelCommander] and keeps the Crazyflie inside a
+//! flight envelope: a [Geofence], a maximum altitude, a maximum velocity and a maximum tilt, see [SafetyLimits].
+//! Setpoints and high-level commands sent through the envelope are checked against the limits and, depending on the
+//! [ViolationPolicy], either clamped to the envelope or rejected.
+//!
+//! The envelope also monitors the position estimate of the Crazyflie with the log subsystem. If the Crazyflie leaves
+//! the envelope anyway, for example because of a disturbance or of a command not sent through the envelope, the
+//! [BreachAction] is triggered: emergency stop or landing. The envelope is then tripped and rejects all the following
+//! commands.
+//!
+//! All the violations are reported as [SafetyReport]s, see [SafetyEnvelope::reports()].
+//!
+//! ```no_run
+//! # use crazyflie_lib::safety::{Geofence, SafetyEnvelope, SafetyLimits};
+//! # use crazyflie_lib::subsystems::commander::Setpoint;
+//! # async fn example(cf: crazyflie_lib::Crazyflie) -> crazyflie_lib::Result<()> {
+//! let limits = SafetyLimits {
+//!     geofence: Some(Geofence::Box { min: [-1.0, -1.0, 0.0], max: [1.0, 1.0, 1.5] }),
+//!     max_velocity: Some(1.0),
+//!     max_tilt: Some(20.0),
+//!     ..Default::default()
+//! };
+//! let envelope = SafetyEnvelope::new(&cf, limits).await?;
+//!
+//! // Sent as a position setpoint at x = 1.0 m
+//! let violations = envelope.send_setpoint(Setpoint::Position { x: 3.0, y: 0.0, z: 0.5, yaw: 0.0 }).await?;
+//! println!("Clamped: {:?}", violations);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The envelope is a client-side guard rail: it only checks the commands sent through it and it relies on the radio
+//! link to stop the Crazyflie. It does not replace the supervisor of the firmware.
+//!
+//! [Commander]: crate::subsystems::commander::Commander
+//! [HighLevelCommander]: crate::subsystems::high_level_commander::HighLevelCommander
+
+use crate::subsystems::commander::{Commander, Setpoint};
+use crate::subsystems::high_level_commander::HighLevelCommander;
+use crate::subsystems::localization::EmergencyControl;
+use crate::subsystems::log::{LogPeriod, LogStream};
+use crate::{Crazyflie, Error, Result};
+use async_broadcast::{broadcast, InactiveReceiver, Sender};
+use futures::Stream;
+use std::sync::atomic::{AtomicBool, Ordering::Relaxed};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Log variables of the estimated position monitored by the envelope
+const POSITION_VARIABLES: [&str; 3] = ["stateEstimate.x", "stateEstimate.y", "stateEstimate.z"];
+
+/// # Geofence
+///
+/// Volume the Crazyflie must stay in, in the world frame.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Geofence {
+    /// Axis-aligned box
+    Box {
+        /// Minimum corner `[x, y, z]` (meters)
+        min: [f32; 3],
+        /// Maximum corner `[x, y, z]` (meters)
+        max: [f32; 3],
+    },
+    /// Vertical prism with a polygonal base
+    Polygon {
+        /// Vertices `[x, y]` of the polygon (meters), in order. The polygon is closed and must not self-intersect.
+        vertices: Vec<[f32; 2]>,
+        /// Bottom of the prism (meters)
+        min_z: f32,
+        /// Top of the prism (meters)
+        max_z: f32,
+    },
+}
+
+impl Geofence {
+    /// Return true if the position is inside the geofence
+    pub fn contains(&self, position: [f32; 3]) -> bool {
+        self.clamp(position) == position
+    }
+
+    /// Closest position inside the geofence
+    pub fn clamp(&self, position: [f32; 3]) -> [f32; 3] {
+        match self {
+            Geofence::Box { min, max } => [0, 1, 2].map(|i| position[i].clamp(min[i], max[i])),
+            Geofence::Polygon { vertices, min_z, max_z } => {
+                let [x, y] = closest_in_polygon(vertices, [position[0], position[1]]);
+                [x, y, position[2].clamp(*min_z, *max_z)]
+            }
+        }
+    }
+
+    /// Distance from the position to the geofence (meters), 0 if the position is inside
+    pub fn distance(&self, position: [f32; 3]) -> f32 {
+        let clamped = self.clamp(position);
+        norm([0, 1, 2].map(|i| position[i] - clamped[i]))
+    }
+
+    fn max_z(&self) -> f32 {
+        match self {
+            Geofence::Box { max, .. } => max[2],
+            Geofence::Polygon { max_z, .. } => *max_z,
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        let valid = match self {
+            Geofence::Box { min, max } => (0..3).all(|i| min[i] <= max[i]),
+            Geofence::Polygon { vertices, min_z, max_z } => vertices.len() >= 3 && min_z <= max_z,
+        };
+        if !valid {
+            return Err(Error::InvalidArgument(format!("Invalid geofence {:?}", self)));
+        }
+        Ok(())
+    }
+}
+
+fn norm<const N: usize>(vector: [f32; N]) -> f32 {
+    vector.iter().map(|v| v * v).sum::<f32>().sqrt()
+}
+
+/// Closest point inside a polygon, the point itself if it is inside
+fn closest_in_polygon(vertices: &[[f32; 2]], point: [f32; 2]) -> [f32; 2] {
+    let mut inside = false;
+    let mut closest = point;
+    let mut closest_distance = f32::INFINITY;
+
+    for (i, a) in vertices.iter().enumerate() {
+        let b = vertices[(i + 1) % vertices.len()];
+
+        // Even-odd rule: count the edges crossed by a ray going towards +x
+        if (a[1] > point[1]) != (b[1] > point[1])
+            && point[0] < a[0] + (point[1] - a[1]) / (b[1] - a[1]) * (b[0] - a[0])
+        {
+            inside = !inside;
+        }
+
+        let edge = [b[0] - a[0], b[1] - a[1]];
+        let length = edge[0] * edge[0] + edge[1] * edge[1];
+        let t = if length > 0.0 {
+            (((point[0] - a[0]) * edge[0] + (point[1] - a[1]) * edge[1]) / length).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let candidate = [a[0] + t * edge[0], a[1] + t * edge[1]];
+        let distance = norm([candidate[0] - point[0], candidate[1] - point[1]]);
+        if distance < closest_distance {
+            closest = candidate;
+            closest_distance = distance;
+        }
+    }
+
+    if inside { point } else { closest }
+}
+
+/// # Violation policy
+///
+/// What a [SafetyEnvelope] does with a command that leaves the envelope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ViolationPolicy {
+    /// Send the command clamped to the envelope
+    #[default]
+    Clamp,
+    /// Do not send the command and return an [Error::SafetyError]
+    Reject,
+}
+
+/// # Breach action
+///
+/// Action triggered by a [SafetyEnvelope] when the estimated position of the Crazyflie is outside the envelope.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum BreachAction {
+    /// Stop the motors immediately, see [EmergencyControl::send_emergency_stop()]
+    ///
+    /// The Crazyflie falls and needs to be rebooted before it can fly again.
+    #[default]
+    EmergencyStop,
+    /// Land with the high-level commander, see [HighLevelCommander::land()]
+    ///
+    /// The low-level setpoints are stopped first so that the high-level commander takes over. The high-level
+    /// commander must be enabled in the Crazyflie.
+    Land {
+        /// Duration of the landing (seconds)
+        duration: f32,
+    },
+}
+
+/// # Safety limits
+///
+/// Limits enforced by a [SafetyEnvelope]. Each limit is optional, the default limits do not restrict anything but
+/// monitor the position every 100 ms.
+#[derive(Debug, Clone)]
+pub struct SafetyLimits {
+    /// Volume the Crazyflie must stay in
+    pub geofence: Option<Geofence>,
+    /// Maximum altitude (meters)
+    pub max_altitude: Option<f32>,
+    /// Maximum velocity (meters/second)
+    pub max_velocity: Option<f32>,
+    /// Maximum roll and pitch angles (degrees)
+    pub max_tilt: Option<f32>,
+    /// What to do with the commands leaving the envelope
+    pub on_violation: ViolationPolicy,
+    /// What to do when the estimated position is outside the envelope
+    pub on_breach: BreachAction,
+    /// Period of the position monitoring, `None` disables the monitoring
+    pub monitor_period: Option<Duration>,
+    /// Distance the estimated position can be outside the envelope before triggering the [BreachAction] (meters)
+    pub breach_margin: f32,
+}
+
+impl Default for SafetyLimits {
+    fn default() -> Self {
+        Self {
+            geofence: None,
+            max_altitude: None,
+            max_velocity: None,
+            max_tilt: None,
+            on_violation: ViolationPolicy::default(),
+            on_breach: BreachAction::default(),
+            monitor_period: Some(Duration::from_millis(100)),
+            breach_margin: 0.1,
+        }
+    }
+}
+
+impl SafetyLimits {
+    fn validate(&self) -> Result<()> {
+        if let Some(geofence) = &self.geofence {
+            geofence.validate()?;
+        }
+        for (limit, name) in [
+            (self.max_altitude, "maximum altitude"),
+            (self.max_velocity, "maximum velocity"),
+            (self.max_tilt, "maximum tilt"),
+            (Some(self.breach_margin), "breach margin"),
+        ] {
+            if let Some(limit) = limit
+                && (limit.is_nan() || limit < 0.0)
+            {
+                return Err(Error::InvalidArgument(format!(
+                    "The {} must be positive, got {}",
+                    name, limit
+                )));
+            }
+        }
+        // A null maximum velocity would make every movement infinitely long
+        if let Some(max_velocity) = self.max_velocity
+            && max_velocity <= 0.0
+        {
+            return Err(Error::InvalidArgument(format!(
+                "The maximum velocity must be greater than zero, got {}",
+                max_velocity
+            )));
+        }
+        if let BreachAction::Land { duration } = self.on_breach
+            && (duration.is_nan() || duration <= 0.0)
+        {
+            return Err(Error::InvalidArgument(format!(
+                "Landing duration must be positive, got {}",
+                duration
+            )));
+        }
+        Ok(())
+    }
+
+    /// Limit violated by an estimated position, only when it is more than the breach margin outside the limit
+    fn breach(&self, position: [f32; 3]) -> Option<SafetyViolation> {
+        if let Some(geofence) = &self.geofence
+            && geofence.distance(position) > self.breach_margin
+        {
+            return Some(SafetyViolation::Geofence { position });
+        }
+        if let Some(max_altitude) = self.max_altitude
+            && position[2] > max_altitude + self.breach_margin
+        {
+            return Some(SafetyViolation::Altitude { altitude: position[2] });
+        }
+        None
+    }
+}
+
+/// # Safety violation
+///
+/// Limit of a [SafetyEnvelope] exceeded by a command or by the estimated position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SafetyViolation {
+    /// Position outside the geofence
+    Geofence {
+        /// Position `[x, y, z]` (meters)
+        position: [f32; 3],
+    },
+    /// Altitude above the maximum altitude, or above the top of the geofence for the setpoints with a height
+    Altitude {
+        /// Altitude (meters)
+        altitude: f32,
+    },
+    /// Velocity above the maximum velocity
+    Velocity {
+        /// Velocity (meters/second)
+        velocity: f32,
+    },
+    /// Roll or pitch angle above the maximum tilt
+    Tilt {
+        /// Roll or pitch angle (degrees)
+        angle: f32,
+    },
+}
+
+impl std::fmt::Display for SafetyViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SafetyViolation::Geofence { position } => write!(f, "position {:?} outside the geofence", position),
+            SafetyViolation::Altitude { altitude } => write!(f, "altitude {} m above the limit", altitude),
+            SafetyViolation::Velocity { velocity } => write!(f, "velocity {} m/s above the limit", velocity),
+            SafetyViolation::Tilt { angle } => write!(f, "tilt {} degrees above the limit", angle),
+        }
+    }
+}
+
+/// # Safety action
+///
+/// Action taken by a [SafetyEnvelope] because of a [SafetyViolation].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SafetyAction {
+    /// The command has been clamped to the envelope and sent
+    Clamped,
+    /// The command has been rejected
+    Rejected,
+    /// The estimated position was outside the envelope, the motors have been emergency stopped
+    EmergencyStop,
+    /// The estimated position was outside the envelope, the Crazyflie has been landed
+    Land,
+}
+
+/// # Safety report
+///
+/// Report of a violation of the envelope, see [SafetyEnvelope::reports()].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SafetyReport {
+    /// Violated limit
+    pub violation: SafetyViolation,
+    /// Action taken
+    pub action: SafetyAction,
+}
+
+/// State shared with the monitoring task
+struct Shared {
+    tripped: AtomicBool,
+    position: Mutex<Option<[f32; 3]>>,
+    reports: Sender<SafetyReport>,
+}
+
+impl Shared {
+    fn report(&self, violation: SafetyViolation, action: SafetyAction) {
+        let _ = self.reports.try_broadcast(SafetyReport { violation, action });
+    }
+}
+
+/// Checks the values of a command, collecting the violations
+struct Check<'a> {
+    limits: &'a SafetyLimits,
+    violations: Vec<SafetyViolation>,
+}
+
+impl Check<'_> {
+    fn position(&mut self, mut position: [f32; 3]) -> [f32; 3] {
+        if let Some(geofence) = &self.limits.geofence
+            && !geofence.contains(position)
+        {
+            self.violations.push(SafetyViolation::Geofence { position });
+            position = geofence.clamp(position);
+        }
+        if let Some(max_altitude) = self.limits.max_altitude
+            && position[2] > max_altitude
+        {
+            self.violations.push(SafetyViolation::Altitude { altitude: position[2] });
+            position[2] = max_altitude;
+        }
+        position
+    }
+
+    fn height(&self) -> Option<f32> {
+        let geofence = self.limits.geofence.as_ref().map(Geofence::max_z);
+        match (self.limits.max_altitude, geofence) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Height above the ground, only the top of the envelope applies
+    fn zdistance(&mut self, zdistance: f32) -> f32 {
+        match self.height() {
+            Some(max) if zdistance > max => {
+                self.violations.push(SafetyViolation::Altitude { altitude: zdistance });
+                max
+            }
+            _ => zdistance,
+        }
+    }
+
+    fn velocity<const N: usize>(&mut self, velocity: [f32; N]) -> [f32; N] {
+        let magnitude = norm(velocity);
+        match self.limits.max_velocity {
+            Some(max) if magnitude > max => {
+                self.violations.push(SafetyViolation::Velocity { velocity: magnitude });
+                velocity.map(|v| v * max / magnitude)
+            }
+            _ => velocity,
+        }
+    }
+
+    fn tilt(&mut self, angle: f32) -> f32 {
+        match self.limits.max_tilt {
+            Some(max) if angle.abs() > max => {
+                self.violations.push(SafetyViolation::Tilt { angle });
+                angle.clamp(-max, max)
+            }
+            _ => angle,
+        }
+    }
+
+    /// Clamp the roll and pitch of a quaternion `[x, y, z, w]`, keeping its yaw
+    ///
+    /// The quaternion is normalized first, as it is by the Crazyflie, so that the angles checked are the ones flown.
+    fn orientation(&mut self, orientation: [f32; 4]) -> Result<[f32; 4]> {
+        let magnitude = norm(orientation);
+        if magnitude == 0.0 {
+            return Err(Error::InvalidArgument(
+                "The orientation quaternion must not be zero".to_owned(),
+            ));
+        }
+        let [x, y, z, w] = orientation.map(|v| v / magnitude);
+        let roll = (2.0 * (w * x + y * z)).atan2(1.0 - 2.0 * (x * x + y * y));
+        let pitch = (2.0 * (w * y - z * x)).clamp(-1.0, 1.0).asin();
+        let yaw = (2.0 * (w * z + x * y)).atan2(1.0 - 2.0 * (y * y + z * z));
+
+        let violations = self.violations.len();
+        let roll = self.tilt(roll.to_degrees()).to_radians();
+        let pitch = self.tilt(pitch.to_degrees()).to_radians();
+        if self.violations.len() == violations {
+            return Ok(orientation);
+        }
+
+        let (sr, cr) = (roll / 2.0).sin_cos();
+        let (sp, cp) = (pitch / 2.0).sin_cos();
+        let (sy, cy) = (yaw / 2.0).sin_cos();
+        Ok([
+            sr * cp * cy - cr * sp * sy,
+            cr * sp * cy + sr * cp * sy,
+            cr * cp * sy - sr * sp * cy,
+            cr * cp * cy + sr * sp * sy,
+        ])
+    }
+
+    fn setpoint(&mut self, setpoint: Setpoint) -> Result<Setpoint> {
+        let setpoint = match setpoint {
+            Setpoint::Rpyt { roll, pitch, yawrate, thrust } => Setpoint::Rpyt {
+                roll: self.tilt(roll),
+                pitch: self.tilt(pitch),
+                yawrate,
+                thrust,
+            },
+            Setpoint::Position { x, y, z, yaw } => {
+                let [x, y, z] = self.position([x, y, z]);
+                Setpoint::Position { x, y, z, yaw }
+            }
+            Setpoint::VelocityWorld { vx, vy, vz, yawrate } => {
+                let [vx, vy, vz] = self.velocity([vx, vy, vz]);
+                Setpoint::VelocityWorld { vx, vy, vz, yawrate }
+            }
+            Setpoint::ZDistance { roll, pitch, yawrate, zdistance } => Setpoint::ZDistance {
+                roll: self.tilt(roll),
+                pitch: self.tilt(pitch),
+                yawrate,
+                zdistance: self.zdistance(zdistance),
+            },
+            Setpoint::Hover { vx, vy, yawrate, zdistance } => {
+                let [vx, vy] = self.velocity([vx, vy]);
+                Setpoint::Hover { vx, vy, yawrate, zdistance: self.zdistance(zdistance) }
+            }
+            // In rate mode, roll and pitch are angular rates
+            Setpoint::Manual { roll, pitch, yawrate, thrust_percentage, rate: false } => Setpoint::Manual {
+                roll: self.tilt(roll),
+                pitch: self.tilt(pitch),
+                yawrate,
+                thrust_percentage,
+                rate: false,
+            },
+            Setpoint::FullState(mut state) => {
+                state.position = self.position(state.position);
+                state.velocity = self.velocity(state.velocity);
+                state.orientation = self.orientation(state.orientation)?;
+                Setpoint::FullState(state)
+            }
+            Setpoint::AltHold { roll, pitch, yawrate, vz } => {
+                let [vz] = self.velocity([vz]);
+                Setpoint::AltHold { roll: self.tilt(roll), pitch: self.tilt(pitch), yawrate, vz }
+            }
+            setpoint => setpoint,
+        };
+        Ok(setpoint)
+    }
+}
+
+/// # Safety envelope
+///
+/// Sends setpoints and high-level commands to a Crazyflie, enforcing [SafetyLimits]. See the [safety module
+/// documentation](crate::safety) for more context and information.
+///
+/// The position monitoring stops when the envelope is dropped.
+pub struct SafetyEnvelope {
+    limits: SafetyLimits,
+    commander: Commander,
+    high_level_commander: HighLevelCommander,
+    shared: Arc<Shared>,
+    reports: InactiveReceiver<SafetyReport>,
+    monitor: Option<JoinHandle<()>>,
+}
+
+impl SafetyEnvelope {
+    /// Create a safety envelope for a Crazyflie
+    ///
+    /// If the position monitoring is enabled, a log block is created for the estimated position, which requires the
+    /// `stateEstimate.x`, `stateEstimate.y` and `stateEstimate.z` log variables.
+    ///
+    /// Return an error if the limits are invalid or if the log block cannot be created.
+    pub async fn new(cf: &Crazyflie, limits: SafetyLimits) -> Result<Self> {
+        limits.validate()?;
+
+        let (mut sender, receiver) = broadcast(100);
+        // Enable overflow mode so old reports are dropped instead of blocking
+        sender.set_overflow(true);
+        let shared = Arc::new(Shared {
+            tripped: AtomicBool::new(false),
+            position: Mutex::new(None),
+            reports: sender,
+        });
+
+        let monitor = match limits.monitor_period {
+            Some(period) => {
+                let period = LogPeriod::try_from(period)?;
+                let mut block = cf.log.create_block().await?;
+                for name in POSITION_VARIABLES {
+                    block.add_variable(name).await?;
+                }
+                let stream = block.start(period).await?;
+                Some(tokio::spawn(monitor_task(
+                    stream,
+                    limits.clone(),
+                    shared.clone(),
+                    cf.commander.clone(),
+                    cf.high_level_commander.clone(),
+                    cf.localization.emergency.clone(),
+                )))
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            limits,
+            commander: cf.commander.clone(),
+            high_level_commander: cf.high_level_commander.clone(),
+            shared,
+            reports: receiver.deactivate(),
+            monitor,
+        })
+    }
+
+    /// Limits enforced by the envelope
+    pub fn limits(&self) -> &SafetyLimits {
+        &self.limits
+    }
+
+    /// Last estimated position `[x, y, z]` received by the position monitoring, if any
+    pub fn position(&self) -> Option<[f32; 3]> {
+        *self.shared.position.lock().unwrap()
+    }
+
+    /// Return true if the estimated position has breached the envelope
+    ///
+    /// Once tripped, the envelope rejects all the commands.
+    pub fn is_tripped(&self) -> bool {
+        self.shared.tripped.load(Relaxed)
+    }
+
+    /// Get a stream of the reports of the violations of the envelope
+    ///
+    /// The stream produces the reports generated after this function is called.
+    pub fn reports(&self) -> impl Stream<Item = SafetyReport> + use<> {
+        self.reports.activate_cloned()
+    }
+
+    /// Apply the violation policy to the violations of a command
+    ///
+    /// Return an error if the command must not be sent.
+    fn enforce(&self, violations: &[SafetyViolation]) -> Result<()> {
+        let action = match self.limits.on_violation {
+            _ if violations.is_empty() => return Ok(()),
+            ViolationPolicy::Clamp => SafetyAction::Clamped,
+            ViolationPolicy::Reject => SafetyAction::Rejected,
+        };
+        for violation in violations {
+            self.shared.report(*violation, action);
+        }
+
+        if action == SafetyAction::Rejected {
+            let violations: Vec<_> = violations.iter().map(|v| v.to_string()).collect();
+            return Err(Error::SafetyError(format!(
+                "Command rejected: {}",
+                violations.join(", ")
+            )));
+        }
+        Ok(())
+    }
+
+    fn check_tripped(&self) -> Result<()> {
+        if self.is_tripped() {
+            return Err(Error::SafetyError(
+                "The envelope has been breached, commands are not accepted anymore".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Send a setpoint, see [Commander::send_setpoint()]
+    ///
+    /// The position, height, velocity and tilt targets of the setpoint are checked against the limits. The CPPM
+    /// emulation setpoints, and the roll and pitch rates of the manual setpoints in rate mode, cannot be checked and
+    /// are sent as is.
+    ///
+    /// Return the violations that have been clamped, or an error if the setpoint has been rejected. Setpoints with
+    /// NaN or infinite values, or with a zero orientation quaternion, are always rejected with
+    /// [Error::InvalidArgument].
+    pub async fn send_setpoint(&self, setpoint: Setpoint) -> Result<Vec<SafetyViolation>> {
+        self.check_tripped()?;
+        validate_finite(&setpoint_values(&setpoint))?;
+
+        let mut check = Check { limits: &self.limits, violations: Vec::new() };
+        let setpoint = check.setpoint(setpoint)?;
+        self.enforce(&check.violations)?;
+
+        self.commander.send_setpoint(setpoint).await?;
+        Ok(check.violations)
+    }
+
+    /// Take off, see [HighLevelCommander::take_off()]
+    ///
+    /// The height is checked against the top of the envelope and the duration is extended if the average vertical
+    /// velocity is above the maximum velocity.
+    ///
+    /// Return the violations that have been clamped, or an error if the command has been rejected, if a value is NaN
+    /// or infinite or if the duration is not a positive number of seconds.
+    pub async fn take_off(&self, height: f32, yaw: Option<f32>, duration: f32, group_mask: Option<u8>) -> Result<Vec<SafetyViolation>> {
+        self.check_tripped()?;
+        validate_finite(&[height, yaw.unwrap_or(0.0)])?;
+        validate_duration(duration)?;
+
+        let mut check = Check { limits: &self.limits, violations: Vec::new() };
+        let height = check.zdistance(height);
+        let start = self.position().map(|p| p[2]).unwrap_or(0.0);
+        let duration = check_duration(&mut check, (height - start).abs(), duration);
+        self.enforce(&check.violations)?;
+
+        self.high_level_commander.take_off(height, yaw, duration, group_mask).await?;
+        Ok(check.violations)
+    }
+
+    /// Go to a position, see [HighLevelCommander::go_to()]
+    ///
+    /// The target is checked against the geofence and maximum altitude and the duration is extended if the average
+    /// velocity is above the maximum velocity. For an absolute target, the velocity can only be checked once the
+    /// position is known from the monitoring. A relative target needs the position to be checked against the
+    /// geofence and maximum altitude: it is rejected if these limits are set and the position is not known.
+    ///
+    /// Return the violations that have been clamped, or an error if the command has been rejected, if a value is NaN
+    /// or infinite or if the duration is not a positive number of seconds.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn go_to(&self, x: f32, y: f32, z: f32, yaw: f32, duration: f32, relative: bool, linear: bool, group_mask: Option<u8>) -> Result<Vec<SafetyViolation>> {
+        self.check_tripped()?;
+        validate_finite(&[x, y, z, yaw])?;
+        validate_duration(duration)?;
+
+        let position = self.position();
+        let limit_position = self.limits.geofence.is_some() || self.limits.max_altitude.is_some();
+        if relative && limit_position && position.is_none() {
+            return Err(Error::SafetyError(
+                "Relative go_to rejected: the position of the Crazyflie is not known".to_owned(),
+            ));
+        }
+
+        let mut check = Check { limits: &self.limits, violations: Vec::new() };
+        let mut target = [x, y, z];
+        if let Some(position) = position {
+            let absolute = if relative { [0, 1, 2].map(|i| position[i] + target[i]) } else { target };
+            let absolute = check.position(absolute);
+            target = if relative { [0, 1, 2].map(|i| absolute[i] - position[i]) } else { absolute };
+        } else if !relative {
+            target = check.position(target);
+        }
+
+        let distance = match (relative, position) {
+            (true, _) => Some(norm(target)),
+            (false, Some(position)) => Some(norm([0, 1, 2].map(|i| target[i] - position[i]))),
+            (false, None) => None,
+        };
+        let duration = match distance {
+            Some(distance) => check_duration(&mut check, distance, duration),
+            None => duration,
+        };
+        self.enforce(&check.violations)?;
+
+        let [x, y, z] = target;
+        self.high_level_commander.go_to(x, y, z, yaw, duration, relative, linear, group_mask).await?;
+        Ok(check.violations)
+    }
+}
+
+/// Check that the values of a command are finite, they cannot be checked against the limits otherwise
+fn validate_finite(values: &[f32]) -> Result<()> {
+    match values.iter().find(|value| !value.is_finite()) {
+        Some(value) => Err(Error::InvalidArgument(format!(
+            "Command values must be finite numbers, got {}",
+            value
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// All the floating point values of a setpoint
+fn setpoint_values(setpoint: &Setpoint) -> Vec<f32> {
+    match *setpoint {
+        Setpoint::Rpyt { roll, pitch, yawrate, .. } => vec![roll, pitch, yawrate],
+        Setpoint::Position { x, y, z, yaw } => vec![x, y, z, yaw],
+        Setpoint::VelocityWorld { vx, vy, vz, yawrate } => vec![vx, vy, vz, yawrate],
+        Setpoint::ZDistance { roll, pitch, yawrate, zdistance } => vec![roll, pitch, yawrate, zdistance],
+        Setpoint::Hover { vx, vy, yawrate, zdistance } => vec![vx, vy, yawrate, zdistance],
+        Setpoint::Manual { roll, pitch, yawrate, thrust_percentage, .. } => vec![roll, pitch, yawrate, thrust_percentage],
+        Setpoint::FullState(state) => [
+            &state.position[..],
+            &state.velocity,
+            &state.acceleration,
+            &state.orientation,
+            &state.angular_rates,
+        ]
+        .concat(),
+        Setpoint::AltHold { roll, pitch, yawrate, vz } => vec![roll, pitch, yawrate, vz],
+        Setpoint::Cppm { .. } | Setpoint::Stop => Vec::new(),
+    }
+}
+
+/// Check that the duration of a movement is a positive number of seconds
+fn validate_duration(duration: f32) -> Result<()> {
+    if !duration.is_finite() || duration <= 0.0 {
+        return Err(Error::InvalidArgument(format!(
+            "Command duration must be a positive number of seconds, got {}",
+            duration
+        )));
+    }
+    Ok(())
+}
+
+/// Extend the duration of a movement so that its average velocity is within the limit
+fn check_duration(check: &mut Check, distance: f32, duration: f32) -> f32 {
+    match check.limits.max_velocity {
+        Some(max) if distance > max * duration => {
+            check.violations.push(SafetyViolation::Velocity { velocity: distance / duration });
+            distance / max
+        }
+        _ => duration,
+    }
+}
+
+impl Drop for SafetyEnvelope {
+    fn drop(&mut self) {
+        if let Some(monitor) = self.monitor.take() {
+            monitor.abort();
+        }
+    }
+}
+
+async fn monitor_task(
+    stream: LogStream,
+    limits: SafetyLimits,
+    shared: Arc<Shared>,
+    commander: Commander,
+    high_level_commander: HighLevelCommander,
+    emergency: EmergencyControl,
+) {
+    // The stream ends with an error when the Crazyflie is disconnected
+    while let Ok(data) = stream.next().await {
+        let position = POSITION_VARIABLES.map(|name| data.data.get(name).and_then(|v| f32::try_from(*v).ok()));
+        let [Some(x), Some(y), Some(z)] = position else {
+            continue;
+        };
+        let position = [x, y, z];
+        *shared.position.lock().unwrap() = Some(position);
+
+        if shared.tripped.load(Relaxed) {
+            continue;
+        }
+        let Some(violation) = limits.breach(position) else {
+            continue;
+        };
+        shared.tripped.store(true, Relaxed);
+
+        let result = match limits.on_breach {
+            BreachAction::EmergencyStop => {
+                shared.report(violation, SafetyAction::EmergencyStop);
+                emergency.send_emergency_stop().await
+            }
+            BreachAction::Land { duration } => {
+                shared.report(violation, SafetyAction::Land);
+                match commander.notify_setpoint_stop(0).await {
+                    Ok(()) => high_level_commander.land(0.0, None, duration, None).await,
+                    Err(e) => Err(e),
+                }
+            }
+        };
+        if result.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn polygon_geofence() {
+        // L-shaped room
+        let geofence = Geofence::Polygon {
+            vertices: vec![[0.0, 0.0], [2.0, 0.0], [2.0, 1.0], [1.0, 1.0], [1.0, 2.0], [0.0, 2.0]],
+            min_z: 0.0,
+            max_z: 1.0,
+        };
+        assert!(geofence.contains([0.5, 1.5, 0.5]));
+        assert!(geofence.contains([1.5, 0.5, 0.5]));
+        assert!(!geofence.contains([1.5, 1.5, 0.5]));
+        assert!(!geofence.contains([0.5, 0.5, 1.5]));
+
+        assert_eq!(geofence.clamp([1.5, 1.2, 0.5]), [1.5, 1.0, 0.5]);
+        assert_eq!(geofence.clamp([-1.0, 0.5, 2.0]), [0.0, 0.5, 1.0]);
+        assert_eq!(geofence.distance([3.0, 0.5, 0.5]), 1.0);
+        assert_eq!(geofence.distance([0.5, 0.5, 0.5]), 0.0);
+    }
+
+    #[test]
+    fn tilt_clamping_keeps_yaw() {
+        let limits = SafetyLimits { max_tilt: Some(10.0), ..Default::default() };
+        let mut check = Check { limits: &limits, violations: Vec::new() };
+
+        // 30 degrees roll, 90 degrees yaw
+        let (sr, cr) = 15f32.to_radians().sin_cos();
+        let (sy, cy) = 45f32.to_radians().sin_cos();
+        let orientation = [sr * cy, sr * sy, cr * sy, cr * cy];
+        let [x, y, z, w] = check.orientation(orientation).unwrap();
+
+        assert_eq!(check.violations.len(), 1);
+        let roll = (2.0 * (w * x + y * z)).atan2(1.0 - 2.0 * (x * x + y * y)).to_degrees();
+        let yaw = (2.0 * (w * z + x * y)).atan2(1.0 - 2.0 * (y * y + z * z)).to_degrees();
+        assert!((roll - 10.0).abs() < 1e-3, "roll {}", roll);
+        assert!((yaw - 90.0).abs() < 1e-3, "yaw {}", yaw);
+    }
+
+    #[test]
+    fn tilt_of_unnormalized_quaternion() {
+        let limits = SafetyLimits { max_tilt: Some(10.0), ..Default::default() };
+        let mut check = Check { limits: &limits, violations: Vec::new() };
+
+        // 30 degrees roll, scaled by 0.5
+        let (sr, cr) = 15f32.to_radians().sin_cos();
+        let [x, y, z, w] = check.orientation([0.5 * sr, 0.0, 0.0, 0.5 * cr]).unwrap();
+
+        assert!(matches!(check.violations[..], [SafetyViolation::Tilt { angle }] if (angle - 30.0).abs() < 1e-3));
+        let roll = (2.0 * (w * x + y * z)).atan2(1.0 - 2.0 * (x * x + y * y)).to_degrees();
+        assert!((roll - 10.0).abs() < 1e-3, "roll {}", roll);
+
+        assert!(matches!(check.orientation([0.0; 4]), Err(Error::InvalidArgument(_))));
+    }
+}
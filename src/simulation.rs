@@ -0,0 +1,1012 @@
+//! # Simulated Crazyflie
+//!
+//! This module implements a virtual Crazyflie that speaks CRTP in-process. A [SimulatedCrazyflie] can be connected
+//! with [Crazyflie::connect_from_simulation()](crate::Crazyflie::connect_from_simulation) exactly like a real
+//! Crazyflie, which allows to test code that uses the lib without a radio or a drone.
+//!
+//! The simulated Crazyflie implements the following services:
+//!  - Platform: protocol version, firmware version and device type queries. App channel packets are echoed back.
+//!  - Log: TOC, log blocks creation, start, stop and delete. The log data are synthetic, each variable value is
+//!    generated from the Crazyflie timestamp. Memory variables read the values set with
+//!    [SimulatedCrazyflie::set_ram_value()].
+//!  - Param: TOC, read, write and default values. Parameter changes can be pushed from the Crazyflie side with
+//!    [SimulatedCrazyflie::set_param()]. The parameters added with [SimulatedCrazyflie::add_persistent_param()]
+//!    support the persistent storage commands.
+//!  - Memory: memory info, read and write.
+//!  - Link service: echo, source and sink.
+//!
+//! The packets sent to the commander ports are recorded and can be inspected with
+//! [SimulatedCrazyflie::take_commander_packets()]. Packets sent to any other port (localization, ...) are silently
+//! dropped.
+//!
+//! The TOCs and memories must be setup before connecting. Parameter values and memory content are kept between
+//! connections, the log blocks are cleared when the link is closed.
+//!
+//! ```
+//! # use crazyflie_lib::{Crazyflie, Value, ValueType, NoTocCache};
+//! # use crazyflie_lib::simulation::SimulatedCrazyflie;
+//! # #[tokio::main]
+//! # async fn main() -> crazyflie_lib::Result<()> {
+//! let sim = SimulatedCrazyflie::new();
+//! sim.add_log_variable("stateEstimate.z", ValueType::F32);
+//! sim.add_param("pid_rate.roll_kp", Value::F32(250.0), true);
+//!
+//! let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+//!
+//! let kp: f32 = cf.param.get("pid_rate.roll_kp").await?;
+//! assert_eq!(kp, 250.0);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::crtp_utils::CrtpTransport;
+use crate::crazyflie::{
+    COMMANDER_PORT, GENERIC_SETPOINT_PORT, HL_COMMANDER_PORT, LINK_PORT, LOG_PORT, MEMORY_PORT, PARAM_PORT,
+    PLATFORM_PORT,
+};
+use crate::subsystems::memory::MemoryType;
+use crate::{Error, Result, Value, ValueType, MAX_SUPPORTED_PROTOCOL_VERSION};
+use async_trait::async_trait;
+use crazyflie_link::Packet;
+use flume as channel;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+// TOC access, common to log and param
+const TOC_CHANNEL: u8 = 0;
+const TOC_GET_ITEM: u8 = 2;
+const TOC_INFO: u8 = 3;
+
+// Log
+const LOG_CONTROL_CHANNEL: u8 = 1;
+const LOG_DATA_CHANNEL: u8 = 2;
+const LOG_DELETE_BLOCK: u8 = 2;
+const LOG_START_BLOCK: u8 = 3;
+const LOG_STOP_BLOCK: u8 = 4;
+const LOG_RESET: u8 = 5;
+const LOG_CREATE_BLOCK_V2: u8 = 6;
+const LOG_APPEND_BLOCK_V2: u8 = 7;
+const LOG_MAX_BLOCKS: usize = 16;
+const LOG_MAX_PAYLOAD: usize = 26;
+const LOG_MEMORY_VARIABLE_ID: u16 = 0xFFFF;
+
+// Param
+const PARAM_READ_CHANNEL: u8 = 1;
+const PARAM_WRITE_CHANNEL: u8 = 2;
+const PARAM_MISC_CHANNEL: u8 = 3;
+const PARAM_MISC_VALUE_UPDATED: u8 = 1;
+const PARAM_MISC_PERSISTENT_STORE: u8 = 3;
+const PARAM_MISC_PERSISTENT_GET_STATE: u8 = 4;
+const PARAM_MISC_PERSISTENT_CLEAR: u8 = 5;
+const PARAM_MISC_GET_EXTENDED_TYPE: u8 = 7;
+const PARAM_MISC_GET_DEFAULT_VALUE: u8 = 8;
+const PARAM_EXTENDED_TYPE_PERSISTENT: u8 = 0x01;
+const PARAM_PERSISTENT_NOT_STORED: u8 = 0;
+const PARAM_PERSISTENT_STORED: u8 = 1;
+const PARAM_PERSISTENT_NOT_FOUND: u8 = 2;
+
+// Memory
+const MEMORY_INFO_CHANNEL: u8 = 0;
+const MEMORY_READ_CHANNEL: u8 = 1;
+const MEMORY_WRITE_CHANNEL: u8 = 2;
+const MEMORY_CMD_INFO_NBR: u8 = 1;
+const MEMORY_CMD_INFO_DETAILS: u8 = 2;
+
+// Platform
+const VERSION_CHANNEL: u8 = 1;
+const APP_CHANNEL: u8 = 2;
+const VERSION_GET_PROTOCOL: u8 = 0;
+const VERSION_GET_FIRMWARE: u8 = 1;
+const VERSION_GET_DEVICE_TYPE: u8 = 2;
+
+// Link service
+const ECHO_CHANNEL: u8 = 0;
+const SOURCE_CHANNEL: u8 = 1;
+const SOURCE_ANSWER: &[u8] = b"Bitcraze Crazyflie simulation";
+
+/// Function generating the value of a simulated log variable from the Crazyflie timestamp in milliseconds
+pub type LogGenerator = Arc<dyn Fn(u32) -> Value + Send + Sync>;
+
+/// # Simulated Crazyflie
+///
+/// In-process virtual Crazyflie. The object is a handle to the simulation state: it can be cloned and kept by the
+/// test code to setup the simulation and inspect its state while a [Crazyflie](crate::Crazyflie) object is
+/// connected to it.
+///
+/// See the [simulation module documentation](crate::simulation) for more context and information.
+#[derive(Clone)]
+pub struct SimulatedCrazyflie {
+    state: Arc<Mutex<SimState>>,
+}
+
+struct SimLogVariable {
+    name: String,
+    value_type: ValueType,
+    generator: LogGenerator,
+}
+
+struct SimParam {
+    name: String,
+    value: Value,
+    default: Value,
+    writable: bool,
+    persistent: bool,
+    /// Value in the persistent storage
+    stored: Option<Value>,
+}
+
+struct SimMemory {
+    memory_type: MemoryType,
+    data: Vec<u8>,
+}
+
+/// Source of a logged value: TOC variable index or RAM address
+enum SimLogSource {
+    Toc(usize),
+    Memory(u32),
+}
+
+struct SimLogBlock {
+    variables: Vec<(SimLogSource, ValueType)>,
+    task: Option<JoinHandle<()>>,
+}
+
+struct SimLinkState {
+    downlink: channel::Sender<Packet>,
+    closed: Arc<watch::Sender<Option<String>>>,
+}
+
+struct SimState {
+    protocol_version: u8,
+    firmware_version: String,
+    device_type: String,
+    start: Instant,
+    log_variables: Vec<SimLogVariable>,
+    ram: BTreeMap<u32, Value>,
+    params: Vec<SimParam>,
+    memories: Vec<SimMemory>,
+    log_blocks: BTreeMap<u8, SimLogBlock>,
+    log_packets_to_drop: usize,
+    commander_packets: Vec<Packet>,
+    link: Option<SimLinkState>,
+}
+
+impl Default for SimulatedCrazyflie {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimulatedCrazyflie {
+    /// Create a simulated Crazyflie with empty log and param TOCs and no memories
+    ///
+    /// The simulated Crazyflie reports the [maximum protocol version](crate::MAX_SUPPORTED_PROTOCOL_VERSION)
+    /// supported by the lib.
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(SimState {
+                protocol_version: MAX_SUPPORTED_PROTOCOL_VERSION,
+                firmware_version: "simulation".to_owned(),
+                device_type: "Simulated Crazyflie".to_owned(),
+                start: Instant::now(),
+                log_variables: Vec::new(),
+                ram: BTreeMap::new(),
+                params: Vec::new(),
+                memories: Vec::new(),
+                log_blocks: BTreeMap::new(),
+                log_packets_to_drop: 0,
+                commander_packets: Vec::new(),
+                link: None,
+            })),
+        }
+    }
+
+    /// Set the CRTP protocol version reported by the simulated Crazyflie
+    pub fn set_protocol_version(&self, version: u8) {
+        self.state.lock().unwrap().protocol_version = version;
+    }
+
+    /// Set the firmware version string reported by the simulated Crazyflie
+    pub fn set_firmware_version(&self, version: &str) {
+        self.state.lock().unwrap().firmware_version = version.to_owned();
+    }
+
+    /// Add a log variable to the log TOC
+    ///
+    /// The variable value is a ramp: the Crazyflie timestamp in milliseconds divided by 10, converted to the
+    /// variable type with [Value::from_f64_lossy()].
+    ///
+    /// # Panics
+    /// Panics if the name is not formatted as "group.name" or if the type cannot be logged (ie. 64 bits types).
+    pub fn add_log_variable(&self, name: &str, value_type: ValueType) {
+        self.add_log_variable_with(
+            name,
+            value_type,
+            Arc::new(move |timestamp| Value::from_f64_lossy(value_type, (timestamp / 10) as f64)),
+        );
+    }
+
+    /// Add a log variable to the log TOC with a custom value generator
+    ///
+    /// The generator is called with the Crazyflie timestamp in milliseconds each time the variable is sampled.
+    /// It must return a value of type `value_type`.
+    ///
+    /// # Panics
+    /// Panics if the name is not formatted as "group.name" or if the type cannot be logged (ie. 64 bits types).
+    pub fn add_log_variable_with(&self, name: &str, value_type: ValueType, generator: LogGenerator) {
+        assert!(name.contains('.'), "Log variable name must be formatted as group.name");
+        assert!(
+            log_type_id(value_type).is_some(),
+            "Value type {:?} not handled by log",
+            value_type
+        );
+
+        self.state.lock().unwrap().log_variables.push(SimLogVariable {
+            name: name.to_owned(),
+            value_type,
+            generator,
+        });
+    }
+
+    /// Set the value of a log variable to a constant
+    ///
+    /// # Panics
+    /// Panics if the variable does not exist.
+    pub fn set_log_value(&self, name: &str, value: Value) {
+        let mut state = self.state.lock().unwrap();
+        let variable = state
+            .log_variables
+            .iter_mut()
+            .find(|v| v.name == name)
+            .expect("Log variable not found");
+        variable.generator = Arc::new(move |_| value);
+    }
+
+    /// Set the value stored at an address of the simulated Crazyflie RAM
+    ///
+    /// The value can be logged as a memory variable, the addresses that have not been set read as 0.
+    pub fn set_ram_value(&self, address: u32, value: Value) {
+        self.state.lock().unwrap().ram.insert(address, value);
+    }
+
+    /// Add a parameter to the param TOC
+    ///
+    /// The type of the parameter is the type of `value`, which is also the default value of the parameter.
+    ///
+    /// # Panics
+    /// Panics if the name is not formatted as "group.name".
+    pub fn add_param(&self, name: &str, value: Value, writable: bool) {
+        assert!(name.contains('.'), "Param name must be formatted as group.name");
+
+        self.state.lock().unwrap().params.push(SimParam {
+            name: name.to_owned(),
+            value,
+            default: value,
+            writable,
+            persistent: false,
+            stored: None,
+        });
+    }
+
+    /// Add a persistent parameter to the param TOC
+    ///
+    /// Persistent parameters are writable and their value can be stored in the simulated persistent storage. The type
+    /// of the parameter is the type of `value`, which is also the default value of the parameter.
+    ///
+    /// # Panics
+    /// Panics if the name is not formatted as "group.name".
+    pub fn add_persistent_param(&self, name: &str, value: Value) {
+        assert!(name.contains('.'), "Param name must be formatted as group.name");
+
+        self.state.lock().unwrap().params.push(SimParam {
+            name: name.to_owned(),
+            value,
+            default: value,
+            writable: true,
+            persistent: true,
+            stored: None,
+        });
+    }
+
+    /// Get the value stored in the persistent storage for a parameter, if any
+    pub fn stored_param(&self, name: &str) -> Option<Value> {
+        let state = self.state.lock().unwrap();
+        state.params.iter().find(|p| p.name == name).and_then(|p| p.stored)
+    }
+
+    /// Get the current value of a parameter in the simulated Crazyflie
+    pub fn param(&self, name: &str) -> Option<Value> {
+        let state = self.state.lock().unwrap();
+        state.params.iter().find(|p| p.name == name).map(|p| p.value)
+    }
+
+    /// Set a parameter from the Crazyflie side
+    ///
+    /// This simulates a parameter being modified by the firmware: the value is updated and a parameter update
+    /// notification is sent to the connected lib.
+    ///
+    /// Returns an error if the parameter does not exist or if the type of `value` does not match.
+    pub fn set_param(&self, name: &str, value: Value) -> Result<()> {
+        self.update_param(name, value, true)
+    }
+
+    /// Set a parameter from the Crazyflie side without notifying the lib
+    ///
+    /// This simulates a parameter that is modified by the firmware without a parameter update notification, like the
+    /// read-only deck detection parameters. The new value is only seen by the lib when it reads the parameter.
+    ///
+    /// Returns an error if the parameter does not exist or if the type of `value` does not match.
+    pub fn set_param_silently(&self, name: &str, value: Value) -> Result<()> {
+        self.update_param(name, value, false)
+    }
+
+    fn update_param(&self, name: &str, value: Value, notify: bool) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let (id, param) = state
+            .params
+            .iter_mut()
+            .enumerate()
+            .find(|(_, p)| p.name == name)
+            .ok_or(Error::VariableNotFound)?;
+
+        if ValueType::from(param.value) != ValueType::from(value) {
+            return Err(Error::InvalidArgument(format!(
+                "Parameter {} is type {:?}",
+                name,
+                ValueType::from(param.value)
+            )));
+        }
+        param.value = value;
+
+        if notify {
+            let mut data = vec![PARAM_MISC_VALUE_UPDATED];
+            data.extend_from_slice(&(id as u16).to_le_bytes());
+            data.append(&mut value.into());
+            state.send(Packet::new(PARAM_PORT, PARAM_MISC_CHANNEL, data));
+        }
+
+        Ok(())
+    }
+
+    /// Add a memory to the simulated Crazyflie
+    ///
+    /// The size of the memory is the length of `data`. Returns the ID of the memory.
+    pub fn add_memory(&self, memory_type: MemoryType, data: Vec<u8>) -> u8 {
+        let mut state = self.state.lock().unwrap();
+        state.memories.push(SimMemory { memory_type, data });
+        (state.memories.len() - 1) as u8
+    }
+
+    /// Get a copy of the content of a memory
+    pub fn memory(&self, memory_id: u8) -> Option<Vec<u8>> {
+        let state = self.state.lock().unwrap();
+        state.memories.get(memory_id as usize).map(|m| m.data.clone())
+    }
+
+    /// Get the IDs of the log blocks currently created in the simulated Crazyflie
+    pub fn log_blocks(&self) -> Vec<u8> {
+        self.state.lock().unwrap().log_blocks.keys().copied().collect()
+    }
+
+    /// Drop the next `count` log data packets, to simulate radio packet loss
+    pub fn drop_log_packets(&self, count: usize) {
+        self.state.lock().unwrap().log_packets_to_drop = count;
+    }
+
+    /// Take the commander packets received since the last call
+    ///
+    /// The packets sent to the commander, generic setpoint and high-level commander ports are recorded in order, which
+    /// allows to check the setpoints sent to the simulated Crazyflie.
+    pub fn take_commander_packets(&self) -> Vec<Packet> {
+        std::mem::take(&mut self.state.lock().unwrap().commander_packets)
+    }
+
+    /// Simulate a connection loss
+    ///
+    /// Closes the current link, if any, with the given reason. The reason is returned by
+    /// [Crazyflie::wait_disconnect()](crate::Crazyflie::wait_disconnect).
+    pub fn disconnect(&self, reason: &str) {
+        if let Some(link) = self.state.lock().unwrap().link.take() {
+            let _ = link.closed.send(Some(reason.to_owned()));
+        }
+    }
+
+    /// Open a new link to the simulated Crazyflie, closing the previous one if any
+    ///
+    /// The returned transport can be connected with
+    /// [Crazyflie::connect_from_transport()](crate::Crazyflie::connect_from_transport), this is useful to wrap the
+    /// simulated link into another transport, for example to inject faults. Otherwise
+    /// [Crazyflie::connect_from_simulation()](crate::Crazyflie::connect_from_simulation) does it in one call.
+    pub fn open_link(&self) -> impl CrtpTransport + use<> {
+        self.disconnect("Link replaced");
+
+        let (uplink, uplink_rx) = channel::unbounded();
+        let (downlink_tx, downlink) = channel::unbounded();
+        let (closed, closed_rx) = watch::channel(None);
+        let closed = Arc::new(closed);
+
+        {
+            let mut state = self.state.lock().unwrap();
+            state.clear_log_blocks();
+            state.link = Some(SimLinkState {
+                downlink: downlink_tx.clone(),
+                closed: closed.clone(),
+            });
+        }
+
+        let state = self.state.clone();
+        let task_link = closed.clone();
+        let mut task_closed = closed_rx.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    packet = uplink_rx.recv_async() => match packet {
+                        Ok(packet) => handle_packet(&state, packet, &downlink_tx),
+                        Err(_) => break,
+                    },
+                    _ = task_closed.wait_for(Option::is_some) => break,
+                }
+            }
+
+            // Only cleanup if the link has not been replaced in the meantime
+            let mut state = state.lock().unwrap();
+            if state.link.as_ref().is_none_or(|link| Arc::ptr_eq(&link.closed, &task_link)) {
+                state.clear_log_blocks();
+            }
+        });
+
+        SimulatedLink {
+            uplink,
+            downlink,
+            closed,
+            closed_rx,
+        }
+    }
+}
+
+impl SimState {
+    fn clear_log_blocks(&mut self) {
+        for (_, block) in std::mem::take(&mut self.log_blocks) {
+            if let Some(task) = block.task {
+                task.abort();
+            }
+        }
+    }
+
+    /// Send a packet to the lib, the packet is dropped if no link is connected
+    fn send(&self, packet: Packet) {
+        if let Some(link) = &self.link {
+            let _ = link.downlink.send(packet);
+        }
+    }
+
+    /// Timestamp in milliseconds of an instant, as the Crazyflie time since boot
+    fn timestamp(&self, instant: Instant) -> u32 {
+        instant.saturating_duration_since(self.start).as_millis() as u32
+    }
+
+    fn log_data_packet(&self, block_id: u8, instant: Instant) -> Option<Packet> {
+        let block = self.log_blocks.get(&block_id)?;
+        let timestamp = self.timestamp(instant);
+
+        let mut data = vec![block_id];
+        data.extend_from_slice(&timestamp.to_le_bytes()[0..3]);
+        for (source, fetch_type) in &block.variables {
+            let value = match source {
+                SimLogSource::Toc(variable_id) => (self.log_variables[*variable_id].generator)(timestamp),
+                SimLogSource::Memory(address) => self.ram.get(address).copied().unwrap_or(Value::U8(0)),
+            };
+            let value = Value::from_f64_lossy(*fetch_type, value.to_f64_lossy());
+            data.append(&mut value.into());
+        }
+
+        Some(Packet::new(LOG_PORT, LOG_DATA_CHANNEL, data))
+    }
+}
+
+fn handle_packet(state: &Arc<Mutex<SimState>>, packet: Packet, downlink: &channel::Sender<Packet>) {
+    let answer = match packet.get_port() {
+        PLATFORM_PORT => handle_platform(&state.lock().unwrap(), &packet),
+        LOG_PORT => handle_log(state, &packet, downlink),
+        PARAM_PORT => handle_param(&mut state.lock().unwrap(), &packet),
+        MEMORY_PORT => handle_memory(&mut state.lock().unwrap(), &packet),
+        LINK_PORT => handle_link_service(&packet),
+        COMMANDER_PORT | GENERIC_SETPOINT_PORT | HL_COMMANDER_PORT => {
+            state.lock().unwrap().commander_packets.push(packet);
+            None
+        }
+        _ => None,
+    };
+
+    if let Some(answer) = answer {
+        let _ = downlink.send(answer);
+    }
+}
+
+fn handle_platform(state: &SimState, packet: &Packet) -> Option<Packet> {
+    let data = packet.get_data();
+    match (packet.get_channel(), data.first()) {
+        (VERSION_CHANNEL, Some(&VERSION_GET_PROTOCOL)) => Some(Packet::new(
+            PLATFORM_PORT,
+            VERSION_CHANNEL,
+            vec![VERSION_GET_PROTOCOL, state.protocol_version],
+        )),
+        (VERSION_CHANNEL, Some(&VERSION_GET_FIRMWARE)) => {
+            let mut answer = vec![VERSION_GET_FIRMWARE];
+            answer.extend_from_slice(state.firmware_version.as_bytes());
+            Some(Packet::new(PLATFORM_PORT, VERSION_CHANNEL, answer))
+        }
+        (VERSION_CHANNEL, Some(&VERSION_GET_DEVICE_TYPE)) => {
+            let mut answer = vec![VERSION_GET_DEVICE_TYPE];
+            answer.extend_from_slice(state.device_type.as_bytes());
+            Some(Packet::new(PLATFORM_PORT, VERSION_CHANNEL, answer))
+        }
+        (APP_CHANNEL, _) => Some(Packet::new(PLATFORM_PORT, APP_CHANNEL, data.clone())),
+        _ => None,
+    }
+}
+
+/// Handle TOC requests for a list of (name, type id) items
+fn handle_toc(port: u8, items: &[(&str, u8)], data: &[u8]) -> Option<Packet> {
+    match data.first() {
+        Some(&TOC_INFO) => {
+            let mut hasher = crc32fast::Hasher::new();
+            for (name, type_id) in items {
+                hasher.update(&[*type_id]);
+                hasher.update(name.as_bytes());
+            }
+
+            let mut answer = vec![TOC_INFO];
+            answer.extend_from_slice(&(items.len() as u16).to_le_bytes());
+            answer.extend_from_slice(&hasher.finalize().to_le_bytes());
+            Some(Packet::new(port, TOC_CHANNEL, answer))
+        }
+        Some(&TOC_GET_ITEM) if data.len() >= 3 => {
+            let id = u16::from_le_bytes([data[1], data[2]]);
+            let (name, type_id) = items.get(id as usize)?;
+            let (group, name) = name.split_once('.')?;
+
+            let mut answer = vec![TOC_GET_ITEM, data[1], data[2], *type_id];
+            answer.extend_from_slice(group.as_bytes());
+            answer.push(0);
+            answer.extend_from_slice(name.as_bytes());
+            answer.push(0);
+            Some(Packet::new(port, TOC_CHANNEL, answer))
+        }
+        _ => None,
+    }
+}
+
+fn handle_log(
+    state: &Arc<Mutex<SimState>>,
+    packet: &Packet,
+    downlink: &channel::Sender<Packet>,
+) -> Option<Packet> {
+    let mut locked_state = state.lock().unwrap();
+    let data = packet.get_data();
+
+    match packet.get_channel() {
+        TOC_CHANNEL => {
+            let items: Vec<_> = locked_state
+                .log_variables
+                .iter()
+                .map(|v| (v.name.as_str(), log_type_id(v.value_type).unwrap()))
+                .collect();
+            handle_toc(LOG_PORT, &items, data)
+        }
+        LOG_CONTROL_CHANNEL if !data.is_empty() => {
+            let command = data[0];
+            let block_id = data.get(1).copied().unwrap_or(0);
+            let status = match command {
+                // All the commands but reset need a block ID
+                LOG_CREATE_BLOCK_V2 | LOG_APPEND_BLOCK_V2 | LOG_DELETE_BLOCK | LOG_START_BLOCK | LOG_STOP_BLOCK
+                    if data.len() < 2 =>
+                {
+                    libc::EINVAL as u8
+                }
+                LOG_RESET => {
+                    locked_state.clear_log_blocks();
+                    0
+                }
+                LOG_CREATE_BLOCK_V2 => {
+                    if locked_state.log_blocks.contains_key(&block_id) {
+                        libc::EEXIST as u8
+                    } else if locked_state.log_blocks.len() >= LOG_MAX_BLOCKS {
+                        libc::ENOMEM as u8
+                    } else {
+                        locked_state.log_blocks.insert(
+                            block_id,
+                            SimLogBlock {
+                                variables: Vec::new(),
+                                task: None,
+                            },
+                        );
+                        0
+                    }
+                }
+                LOG_APPEND_BLOCK_V2 => log_append(&mut locked_state, block_id, &data[2..]),
+                LOG_DELETE_BLOCK => match locked_state.log_blocks.remove(&block_id) {
+                    Some(block) => {
+                        if let Some(task) = block.task {
+                            task.abort();
+                        }
+                        0
+                    }
+                    None => libc::ENOENT as u8,
+                },
+                LOG_START_BLOCK if data.len() >= 3 && data[2] != 0 => {
+                    let period = Duration::from_millis(data[2] as u64 * 10);
+                    match locked_state.log_blocks.get_mut(&block_id) {
+                        Some(block) => {
+                            if let Some(task) = block.task.take() {
+                                task.abort();
+                            }
+                            block.task = Some(spawn_log_block(state.clone(), block_id, period, downlink.clone()));
+                            0
+                        }
+                        None => libc::ENOENT as u8,
+                    }
+                }
+                // Missing or zero period
+                LOG_START_BLOCK => libc::EINVAL as u8,
+                LOG_STOP_BLOCK => match locked_state.log_blocks.get_mut(&block_id) {
+                    Some(block) => {
+                        if let Some(task) = block.task.take() {
+                            task.abort();
+                        }
+                        0
+                    }
+                    None => libc::ENOENT as u8,
+                },
+                _ => return None,
+            };
+
+            Some(Packet::new(
+                LOG_PORT,
+                LOG_CONTROL_CHANNEL,
+                vec![command, block_id, status],
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Append variables to a log block, returns the protocol status code
+fn log_append(state: &mut SimState, block_id: u8, mut operations: &[u8]) -> u8 {
+    let mut new_variables = Vec::new();
+    while operations.len() >= 3 {
+        let fetch_type = match log_type_from_id(operations[0] & 0x0f) {
+            Some(t) => t,
+            None => return libc::EINVAL as u8,
+        };
+        let variable_id = u16::from_le_bytes([operations[1], operations[2]]);
+        // The TOC ID 0xFFFF indicates a memory variable, followed by its address
+        if variable_id == LOG_MEMORY_VARIABLE_ID {
+            if operations.len() < 7 {
+                return libc::EINVAL as u8;
+            }
+            let address = u32::from_le_bytes([operations[3], operations[4], operations[5], operations[6]]);
+            new_variables.push((SimLogSource::Memory(address), fetch_type));
+            operations = &operations[7..];
+        } else {
+            if variable_id as usize >= state.log_variables.len() {
+                return libc::ENOENT as u8;
+            }
+            new_variables.push((SimLogSource::Toc(variable_id as usize), fetch_type));
+            operations = &operations[3..];
+        }
+    }
+
+    let Some(block) = state.log_blocks.get_mut(&block_id) else {
+        return libc::ENOENT as u8;
+    };
+
+    let payload_length: usize = block
+        .variables
+        .iter()
+        .chain(new_variables.iter())
+        .map(|(_, t)| t.byte_length())
+        .sum();
+    if payload_length > LOG_MAX_PAYLOAD {
+        return libc::E2BIG as u8;
+    }
+
+    block.variables.append(&mut new_variables);
+    0
+}
+
+fn spawn_log_block(
+    state: Arc<Mutex<SimState>>,
+    block_id: u8,
+    period: Duration,
+    downlink: channel::Sender<Packet>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            // Timestamp the samples with the scheduled tick so that they are strictly periodic, even when late
+            // ticks fire back to back
+            let tick = interval.tick().await;
+            let packet = {
+                let mut state = state.lock().unwrap();
+                let packet = state.log_data_packet(block_id, tick.into_std());
+                if packet.is_some() && state.log_packets_to_drop > 0 {
+                    state.log_packets_to_drop -= 1;
+                    continue;
+                }
+                packet
+            };
+            let Some(packet) = packet else { break };
+            if downlink.send(packet).is_err() {
+                break;
+            }
+        }
+    })
+}
+
+fn handle_param(state: &mut SimState, packet: &Packet) -> Option<Packet> {
+    let data = packet.get_data();
+
+    match packet.get_channel() {
+        TOC_CHANNEL => {
+            let items: Vec<_> = state
+                .params
+                .iter()
+                .map(|p| (p.name.as_str(), param_type_id(p.value.into(), p.writable, p.persistent)))
+                .collect();
+            handle_toc(PARAM_PORT, &items, data)
+        }
+        PARAM_READ_CHANNEL if data.len() >= 2 => {
+            let id = u16::from_le_bytes([data[0], data[1]]) as usize;
+            let mut answer = vec![data[0], data[1]];
+            match state.params.get(id) {
+                Some(param) => {
+                    answer.push(0);
+                    answer.append(&mut param.value.into());
+                }
+                None => answer.push(libc::ENOENT as u8),
+            }
+            Some(Packet::new(PARAM_PORT, PARAM_READ_CHANNEL, answer))
+        }
+        PARAM_WRITE_CHANNEL if data.len() >= 2 => {
+            let id = u16::from_le_bytes([data[0], data[1]]) as usize;
+            let mut answer = vec![data[0], data[1]];
+            match state.params.get_mut(id) {
+                Some(param) if param.writable => {
+                    match Value::from_le_bytes(&data[2..], param.value.into()) {
+                        Ok(value) => {
+                            param.value = value;
+                            answer.extend_from_slice(&data[2..]);
+                        }
+                        Err(_) => answer.push(libc::EINVAL as u8),
+                    }
+                }
+                Some(_) => answer.push(libc::EACCES as u8),
+                None => answer.push(libc::ENOENT as u8),
+            }
+            Some(Packet::new(PARAM_PORT, PARAM_WRITE_CHANNEL, answer))
+        }
+        PARAM_MISC_CHANNEL if data.len() >= 3 => handle_param_misc(state, data),
+        _ => None,
+    }
+}
+
+fn handle_param_misc(state: &mut SimState, data: &[u8]) -> Option<Packet> {
+    let id = u16::from_le_bytes([data[1], data[2]]) as usize;
+    let mut answer = data[..3].to_vec();
+    let param = state.params.get_mut(id);
+
+    match data[0] {
+        PARAM_MISC_GET_DEFAULT_VALUE => match param {
+            // The firmware has no default value for read-only parameters
+            Some(param) if param.writable => {
+                answer.push(0);
+                answer.append(&mut param.default.into());
+            }
+            _ => answer.push(libc::ENOENT as u8),
+        },
+        PARAM_MISC_GET_EXTENDED_TYPE => match param {
+            Some(param) if param.persistent => {
+                answer.push(0);
+                answer.push(PARAM_EXTENDED_TYPE_PERSISTENT);
+            }
+            _ => answer.push(libc::ENOENT as u8),
+        },
+        PARAM_MISC_PERSISTENT_GET_STATE => match param {
+            Some(param) if param.persistent => match param.stored {
+                Some(stored) => {
+                    answer.push(PARAM_PERSISTENT_STORED);
+                    answer.append(&mut param.default.into());
+                    answer.append(&mut stored.into());
+                }
+                None => {
+                    answer.push(PARAM_PERSISTENT_NOT_STORED);
+                    answer.append(&mut param.default.into());
+                }
+            },
+            _ => answer.push(PARAM_PERSISTENT_NOT_FOUND),
+        },
+        // The current value is stored
+        PARAM_MISC_PERSISTENT_STORE => match param {
+            Some(param) if param.persistent => {
+                param.stored = Some(param.value);
+                answer.push(0);
+            }
+            _ => answer.push(libc::ENOENT as u8),
+        },
+        PARAM_MISC_PERSISTENT_CLEAR => match param {
+            Some(param) if param.persistent => {
+                param.stored = None;
+                answer.push(0);
+            }
+            _ => answer.push(libc::ENOENT as u8),
+        },
+        _ => return None,
+    }
+
+    Some(Packet::new(PARAM_PORT, PARAM_MISC_CHANNEL, answer))
+}
+
+fn handle_memory(state: &mut SimState, packet: &Packet) -> Option<Packet> {
+    let data = packet.get_data();
+
+    match packet.get_channel() {
+        MEMORY_INFO_CHANNEL => match data.first() {
+            Some(&MEMORY_CMD_INFO_NBR) => Some(Packet::new(
+                MEMORY_PORT,
+                MEMORY_INFO_CHANNEL,
+                vec![MEMORY_CMD_INFO_NBR, state.memories.len() as u8],
+            )),
+            Some(&MEMORY_CMD_INFO_DETAILS) if data.len() >= 2 => {
+                let memory = state.memories.get(data[1] as usize)?;
+                let mut answer = vec![MEMORY_CMD_INFO_DETAILS, data[1], memory.memory_type as u8];
+                answer.extend_from_slice(&(memory.data.len() as u32).to_le_bytes());
+                answer.extend_from_slice(&[0; 8]);
+                Some(Packet::new(MEMORY_PORT, MEMORY_INFO_CHANNEL, answer))
+            }
+            _ => None,
+        },
+        MEMORY_READ_CHANNEL if data.len() >= 6 => {
+            let memory = state.memories.get(data[0] as usize)?;
+            let address = u32::from_le_bytes(data[1..5].try_into().unwrap()) as usize;
+            let length = data[5] as usize;
+
+            let mut answer = data[0..5].to_vec();
+            match memory.data.get(address..address + length) {
+                Some(content) => {
+                    answer.push(0);
+                    answer.extend_from_slice(content);
+                }
+                None => answer.push(libc::EIO as u8),
+            }
+            Some(Packet::new(MEMORY_PORT, MEMORY_READ_CHANNEL, answer))
+        }
+        MEMORY_WRITE_CHANNEL if data.len() >= 5 => {
+            let memory = state.memories.get_mut(data[0] as usize)?;
+            let address = u32::from_le_bytes(data[1..5].try_into().unwrap()) as usize;
+            let content = &data[5..];
+
+            let mut answer = data[0..5].to_vec();
+            match memory.data.get_mut(address..address + content.len()) {
+                Some(target) => {
+                    target.copy_from_slice(content);
+                    answer.push(0);
+                }
+                None => answer.push(libc::EIO as u8),
+            }
+            Some(Packet::new(MEMORY_PORT, MEMORY_WRITE_CHANNEL, answer))
+        }
+        _ => None,
+    }
+}
+
+fn handle_link_service(packet: &Packet) -> Option<Packet> {
+    match packet.get_channel() {
+        ECHO_CHANNEL => Some(Packet::new(LINK_PORT, ECHO_CHANNEL, packet.get_data().clone())),
+        SOURCE_CHANNEL => Some(Packet::new(LINK_PORT, SOURCE_CHANNEL, SOURCE_ANSWER.to_vec())),
+        _ => None,
+    }
+}
+
+fn log_type_id(value_type: ValueType) -> Option<u8> {
+    match value_type {
+        ValueType::U8 => Some(1),
+        ValueType::U16 => Some(2),
+        ValueType::U32 => Some(3),
+        ValueType::I8 => Some(4),
+        ValueType::I16 => Some(5),
+        ValueType::I32 => Some(6),
+        ValueType::F32 => Some(7),
+        ValueType::F16 => Some(8),
+        _ => None,
+    }
+}
+
+fn log_type_from_id(id: u8) -> Option<ValueType> {
+    match id {
+        1 => Some(ValueType::U8),
+        2 => Some(ValueType::U16),
+        3 => Some(ValueType::U32),
+        4 => Some(ValueType::I8),
+        5 => Some(ValueType::I16),
+        6 => Some(ValueType::I32),
+        7 => Some(ValueType::F32),
+        8 => Some(ValueType::F16),
+        _ => None,
+    }
+}
+
+fn param_type_id(value_type: ValueType, writable: bool, extended: bool) -> u8 {
+    let type_id = match value_type {
+        ValueType::U8 => 0x08,
+        ValueType::U16 => 0x09,
+        ValueType::U32 => 0x0A,
+        ValueType::U64 => 0x0B,
+        ValueType::I8 => 0x00,
+        ValueType::I16 => 0x01,
+        ValueType::I32 => 0x02,
+        ValueType::I64 => 0x03,
+        ValueType::F16 => 0x05,
+        ValueType::F32 => 0x06,
+        ValueType::F64 => 0x07,
+    };
+
+    let type_id = if extended { type_id | (1 << 4) } else { type_id };
+
+    if writable {
+        type_id
+    } else {
+        type_id | (1 << 6)
+    }
+}
+
+/// Lib side of a link to a [SimulatedCrazyflie]
+struct SimulatedLink {
+    uplink: channel::Sender<Packet>,
+    downlink: channel::Receiver<Packet>,
+    closed: Arc<watch::Sender<Option<String>>>,
+    closed_rx: watch::Receiver<Option<String>>,
+}
+
+#[async_trait]
+impl CrtpTransport for SimulatedLink {
+    async fn send_packet(&self, packet: Packet) -> Result<()> {
+        if self.closed_rx.borrow().is_some() {
+            return Err(Error::Disconnected);
+        }
+        self.uplink
+            .send_async(packet)
+            .await
+            .map_err(|_| Error::Disconnected)
+    }
+
+    async fn recv_packet(&self) -> Result<Packet> {
+        let mut closed = self.closed_rx.clone();
+        tokio::select! {
+            packet = self.downlink.recv_async() => packet.map_err(|_| Error::Disconnected),
+            _ = closed.wait_for(Option::is_some) => Err(Error::Disconnected),
+        }
+    }
+
+    async fn close(&self) {
+        self.closed.send_if_modified(|reason| {
+            if reason.is_none() {
+                *reason = Some("Link closed".to_owned());
+                true
+            } else {
+                false
+            }
+        });
+    }
+
+    async fn wait_close(&self) -> String {
+        let mut closed = self.closed_rx.clone();
+        match closed.wait_for(Option::is_some).await {
+            Ok(reason) => reason.clone().unwrap_or_default(),
+            Err(_) => "Link closed".to_owned(),
+        }
+    }
+}
@@ -0,0 +1,580 @@
+//! # Low level setpoint subsystem
+//!
+//! This subsystem allows to send low-level setpoint. The setpoints are described as low-level in the sense that they
+//! are setting the instant target state. As such they likely need to be send very often to have the crazyflie
+//! follow the wanted flight profile.
+//!
+//! The Crazyflie has a couple of safety mechanisms that one needs to be aware of in order to send setpoints:
+//!  - When using the [Commander::setpoint_rpyt()] function, a setpoint with thrust=0 must be sent once to unlock the thrust
+//!  - There is a priority for setpoints in the Crazyflie, this allows app and other internal subsystem like the high-level
+//!    commander to set setpoints in parallel, only the higher priority setpoint is taken into account.
+//!  - In no setpoint are received for 1 seconds, the Crazyflie will reset roll/pitch/yawrate to 0/0/0 and after 2 seconds
+//!    will fallback fallback to a lower-priority setpoint which in most case will cut the motors.
+//!
+//! The following example code would drive the motors in a ramp and then stop:
+//! ``` no_run
+//! # use tokio::time::{sleep, Duration};
+//! # async fn ramp(crazyflie: crazyflie_lib::Crazyflie) -> Result<(), Box<dyn std::error::Error>> {
+//! // Unlock the commander
+//! crazyflie.commander.setpoint_rpyt(0.0, 0.0, 0.0, 0).await?;
+//!
+//! // Ramp!
+//! for thrust in (0..20_000).step_by(1_000) {
+//!     crazyflie.commander.setpoint_rpyt(0.0, 0.0, 0.0, thrust).await?;
+//!     sleep(Duration::from_millis(100)).await;
+//! }
+//!
+//! // Stop the motors
+//! crazyflie.commander.setpoint_rpyt(0.0, 0.0, 0.0, 0).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! To avoid having to re-send setpoints in a loop, a [SetpointStream] re-sends the latest [Setpoint] at a fixed rate
+//! from a background task and takes care of stopping, or landing, the Crazyflie when it is not updated anymore. See
+//! [Commander::stream()].
+
+use crazyflie_link::Packet;
+use flume::Sender;
+
+use crate::{Error, Result};
+
+use crate::crazyflie::COMMANDER_PORT;
+use crate::crazyflie::GENERIC_SETPOINT_PORT;
+
+mod stream;
+
+pub use stream::*;
+
+// Channels
+const RPYT_CHANNEL: u8 = 0;
+const GENERIC_SETPOINT_CHANNEL: u8 = 0;
+const GENERIC_CMD_CHANNEL: u8 = 1;
+
+// Setpoint type identifiers
+const TYPE_CPPM_EMU: u8 = 3;
+const TYPE_ALT_HOLD: u8 = 4;
+const TYPE_FULL_STATE: u8 = 6;
+const TYPE_POSITION: u8 = 7;
+const TYPE_VELOCITY_WORLD: u8 = 8;
+const TYPE_ZDISTANCE: u8 = 9;
+const TYPE_HOVER: u8 = 10;
+const TYPE_MANUAL: u8 = 11;
+const TYPE_STOP: u8 = 0;
+const TYPE_META_COMMAND_NOTIFY_SETPOINT_STOP: u8 = 0;
+
+/// Maximum number of auxiliary channels of a CPPM emulation setpoint
+pub const CPPM_MAX_AUX_CHANNELS: usize = 10;
+
+/// # Low level setpoint
+///
+/// Any of the setpoints that can be sent with the [Commander], see the corresponding `setpoint_*` function for the
+/// meaning of the fields. Setpoints can be sent with [Commander::send_setpoint()] or streamed with a
+/// [SetpointStream].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Setpoint {
+    /// See [Commander::setpoint_rpyt()]
+    Rpyt {
+        /// Roll angle (degrees)
+        roll: f32,
+        /// Pitch angle (degrees)
+        pitch: f32,
+        /// Yaw rate (degrees/second)
+        yawrate: f32,
+        /// Thrust as a 16-bit value
+        thrust: u16,
+    },
+    /// See [Commander::setpoint_position()]
+    Position {
+        /// x position (meters, world frame)
+        x: f32,
+        /// y position (meters, world frame)
+        y: f32,
+        /// z position (meters, world frame)
+        z: f32,
+        /// Yaw angle (degrees, absolute)
+        yaw: f32,
+    },
+    /// See [Commander::setpoint_velocity_world()]
+    VelocityWorld {
+        /// Velocity in x (meters/second, world frame)
+        vx: f32,
+        /// Velocity in y (meters/second, world frame)
+        vy: f32,
+        /// Velocity in z (meters/second, world frame)
+        vz: f32,
+        /// Yaw rate (degrees/second)
+        yawrate: f32,
+    },
+    /// See [Commander::setpoint_zdistance()]
+    ZDistance {
+        /// Roll angle (degrees)
+        roll: f32,
+        /// Pitch angle (degrees)
+        pitch: f32,
+        /// Yaw rate (degrees/second)
+        yawrate: f32,
+        /// Height above ground (meters)
+        zdistance: f32,
+    },
+    /// See [Commander::setpoint_hover()]
+    Hover {
+        /// Velocity in x (meters/second, body frame)
+        vx: f32,
+        /// Velocity in y (meters/second, body frame)
+        vy: f32,
+        /// Yaw rate (degrees/second)
+        yawrate: f32,
+        /// Height above ground (meters)
+        zdistance: f32,
+    },
+    /// See [Commander::setpoint_manual()]
+    Manual {
+        /// Roll (degrees or degrees/second, depending on `rate`)
+        roll: f32,
+        /// Pitch (degrees or degrees/second, depending on `rate`)
+        pitch: f32,
+        /// Yaw rate (degrees/second)
+        yawrate: f32,
+        /// Thrust as a percentage (0 to 100)
+        thrust_percentage: f32,
+        /// Rate mode if true, angle mode if false
+        rate: bool,
+    },
+    /// See [Commander::setpoint_full_state()]
+    FullState(FullState),
+    /// See [Commander::setpoint_alt_hold()]
+    AltHold {
+        /// Roll angle (degrees)
+        roll: f32,
+        /// Pitch angle (degrees)
+        pitch: f32,
+        /// Yaw rate (degrees/second)
+        yawrate: f32,
+        /// Vertical velocity (meters/second)
+        vz: f32,
+    },
+    /// See [Commander::setpoint_cppm()]
+    Cppm {
+        /// Roll channel (microseconds)
+        roll: u16,
+        /// Pitch channel (microseconds)
+        pitch: u16,
+        /// Yaw channel (microseconds)
+        yaw: u16,
+        /// Thrust channel (microseconds)
+        thrust: u16,
+        /// Auxiliary channels (microseconds), at most [CPPM_MAX_AUX_CHANNELS]
+        aux: Vec<u16>,
+    },
+    /// See [Commander::setpoint_stop()]
+    Stop,
+}
+
+/// # Full state setpoint
+///
+/// Complete target state of the Crazyflie, used for trajectory tracking with feed-forward. See
+/// [Commander::setpoint_full_state()].
+///
+/// The position, velocity and acceleration are sent in millimeters as 16 bits integers, which limits them to
+/// ±32.767 m, m/s and m/s². The angular rates are sent in milliradians/second as 16 bits integers, which limits them
+/// to about ±1877 degrees/second.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FullState {
+    /// Position `[x, y, z]` (meters, world frame)
+    pub position: [f32; 3],
+    /// Velocity `[vx, vy, vz]` (meters/second, world frame)
+    pub velocity: [f32; 3],
+    /// Acceleration `[ax, ay, az]` (meters/second², world frame)
+    pub acceleration: [f32; 3],
+    /// Attitude quaternion `[qx, qy, qz, qw]`, normalized before being sent
+    pub orientation: [f32; 4],
+    /// Angular rates `[roll, pitch, yaw]` (degrees/second)
+    pub angular_rates: [f32; 3],
+}
+
+impl Setpoint {
+    /// Encode the setpoint as a packet
+    ///
+    /// Return an error if the setpoint cannot be sent, see the corresponding `setpoint_*` function.
+    fn encode(&self) -> Result<Packet> {
+        let payload = match *self {
+            Setpoint::Rpyt { roll, pitch, yawrate, thrust } => {
+                let mut payload = Vec::new();
+                payload.append(&mut roll.to_le_bytes().to_vec());
+                // Pitch is negated for compatibility with crazyflie-lib-python
+                payload.append(&mut (-pitch).to_le_bytes().to_vec());
+                payload.append(&mut yawrate.to_le_bytes().to_vec());
+                payload.append(&mut thrust.to_le_bytes().to_vec());
+
+                return Ok(Packet::new(COMMANDER_PORT, RPYT_CHANNEL, payload));
+            }
+            Setpoint::Position { x, y, z, yaw } => {
+                let mut payload = Vec::with_capacity(1 + 4 * 4);
+                payload.push(TYPE_POSITION);
+                payload.extend_from_slice(&x.to_le_bytes());
+                payload.extend_from_slice(&y.to_le_bytes());
+                payload.extend_from_slice(&z.to_le_bytes());
+                payload.extend_from_slice(&yaw.to_le_bytes());
+                payload
+            }
+            Setpoint::VelocityWorld { vx, vy, vz, yawrate } => {
+                let mut payload = Vec::with_capacity(1 + 4 * 4);
+                payload.push(TYPE_VELOCITY_WORLD);
+                payload.extend_from_slice(&vx.to_le_bytes());
+                payload.extend_from_slice(&vy.to_le_bytes());
+                payload.extend_from_slice(&vz.to_le_bytes());
+                payload.extend_from_slice(&yawrate.to_le_bytes());
+                payload
+            }
+            Setpoint::ZDistance { roll, pitch, yawrate, zdistance } => {
+                let mut payload = Vec::with_capacity(1 + 4 * 4);
+                payload.push(TYPE_ZDISTANCE);
+                payload.extend_from_slice(&roll.to_le_bytes());
+                payload.extend_from_slice(&pitch.to_le_bytes());
+                payload.extend_from_slice(&yawrate.to_le_bytes());
+                payload.extend_from_slice(&zdistance.to_le_bytes());
+                payload
+            }
+            Setpoint::Hover { vx, vy, yawrate, zdistance } => {
+                let mut payload = Vec::with_capacity(1 + 4 * 4);
+                payload.push(TYPE_HOVER);
+                payload.extend_from_slice(&vx.to_le_bytes());
+                payload.extend_from_slice(&vy.to_le_bytes());
+                payload.extend_from_slice(&yawrate.to_le_bytes());
+                payload.extend_from_slice(&zdistance.to_le_bytes());
+                payload
+            }
+            Setpoint::Manual { roll, pitch, yawrate, thrust_percentage, rate } => {
+                // Map thrust percentage to Crazyflie thrust value (10001 to 60000)
+                let thrust = 10001.0 + 0.01 * thrust_percentage * (60000.0 - 10001.0);
+                let thrust_16 = thrust as u16;
+                let mut payload = Vec::with_capacity(1 + 4 * 3 + 2 + 1);
+                payload.push(TYPE_MANUAL);
+                payload.extend_from_slice(&roll.to_le_bytes());
+                payload.extend_from_slice(&pitch.to_le_bytes());
+                payload.extend_from_slice(&yawrate.to_le_bytes());
+                payload.extend_from_slice(&thrust_16.to_le_bytes());
+                payload.push(rate as u8);
+                payload
+            }
+            Setpoint::FullState(state) => {
+                let mut payload = Vec::with_capacity(1 + 9 * 2 + 4 + 3 * 2);
+                payload.push(TYPE_FULL_STATE);
+                for (vector, name) in [
+                    (state.position, "position"),
+                    (state.velocity, "velocity"),
+                    (state.acceleration, "acceleration"),
+                ] {
+                    for value in vector {
+                        payload.extend_from_slice(&to_milli_i16(value, name)?.to_le_bytes());
+                    }
+                }
+                payload.extend_from_slice(&compress_quaternion(state.orientation)?.to_le_bytes());
+                for rate in state.angular_rates {
+                    // Sent in milliradians/second
+                    payload.extend_from_slice(&to_milli_i16(rate.to_radians(), "angular rate")?.to_le_bytes());
+                }
+                payload
+            }
+            Setpoint::AltHold { roll, pitch, yawrate, vz } => {
+                let mut payload = Vec::with_capacity(1 + 4 * 4);
+                payload.push(TYPE_ALT_HOLD);
+                payload.extend_from_slice(&roll.to_le_bytes());
+                payload.extend_from_slice(&pitch.to_le_bytes());
+                // The firmware altitude-hold setpoint uses the opposite yaw rate sign than the other setpoints
+                payload.extend_from_slice(&(-yawrate).to_le_bytes());
+                payload.extend_from_slice(&vz.to_le_bytes());
+                payload
+            }
+            Setpoint::Cppm { roll, pitch, yaw, thrust, ref aux } => {
+                if aux.len() > CPPM_MAX_AUX_CHANNELS {
+                    return Err(Error::InvalidArgument(format!(
+                        "CPPM setpoint has at most {} auxiliary channels, got {}",
+                        CPPM_MAX_AUX_CHANNELS,
+                        aux.len()
+                    )));
+                }
+                let mut payload = Vec::with_capacity(1 + 1 + 2 * (4 + aux.len()));
+                payload.push(TYPE_CPPM_EMU);
+                // Header: number of auxiliary channels in the 4 low bits
+                payload.push(aux.len() as u8);
+                for channel in [roll, pitch, yaw, thrust].iter().chain(aux) {
+                    payload.extend_from_slice(&channel.to_le_bytes());
+                }
+                payload
+            }
+            Setpoint::Stop => vec![TYPE_STOP],
+        };
+
+        Ok(Packet::new(GENERIC_SETPOINT_PORT, GENERIC_SETPOINT_CHANNEL, payload))
+    }
+}
+
+/// Convert a value to the firmware 16 bits fixed-point representation, in thousandths of the unit
+fn to_milli_i16(value: f32, name: &str) -> Result<i16> {
+    let milli = (value * 1000.0).round();
+    if milli.is_nan() || milli < i16::MIN as f32 || milli > i16::MAX as f32 {
+        return Err(Error::InvalidArgument(format!(
+            "Full state {} out of range: {}",
+            name, value
+        )));
+    }
+    Ok(milli as i16)
+}
+
+/// Compress a quaternion `[x, y, z, w]` to 32 bits, as expected by the firmware
+///
+/// The largest component is dropped, its index is stored in the 2 most significant bits, and the three other
+/// components are stored as 9 bits magnitudes and a sign bit. See `quatcompress.h` in the firmware.
+fn compress_quaternion(quaternion: [f32; 4]) -> Result<u32> {
+    let norm = quaternion.iter().map(|q| q * q).sum::<f32>().sqrt();
+    if norm.is_nan() || norm == 0.0 {
+        return Err(Error::InvalidArgument(format!(
+            "Invalid orientation quaternion {:?}",
+            quaternion
+        )));
+    }
+    let mut quaternion = quaternion.map(|q| q / norm);
+
+    let mut largest = 0;
+    for i in 1..4 {
+        if quaternion[i].abs() > quaternion[largest].abs() {
+            largest = i;
+        }
+    }
+    // q and -q are the same rotation, the largest component is made positive so that its sign is implicit
+    if quaternion[largest] < 0.0 {
+        quaternion = quaternion.map(|q| -q);
+    }
+
+    let mut compressed = largest as u32;
+    for (i, q) in quaternion.iter().enumerate() {
+        if i != largest {
+            let negative = (*q < 0.0) as u32;
+            let magnitude = (((1 << 9) - 1) as f32 * (q.abs() * std::f32::consts::SQRT_2) + 0.5) as u32;
+            compressed = (compressed << 10) | (negative << 9) | magnitude;
+        }
+    }
+
+    Ok(compressed)
+}
+
+/// # Low level setpoint subsystem
+///
+/// This struct implements methods to send low level setpoints to the Crazyflie.
+/// See the [commander module documentation](crate::subsystems::commander) for more context and information.
+#[derive(Debug, Clone)]
+pub struct Commander {
+    uplink: Sender<Packet>,
+}
+
+impl Commander {
+    pub(crate) fn new(uplink: Sender<Packet>) -> Self {
+        Self { uplink }
+    }
+
+    /// Sends a [Setpoint]
+    ///
+    /// This is equivalent to calling the `setpoint_*` function matching the setpoint.
+    pub async fn send_setpoint(&self, setpoint: Setpoint) -> Result<()> {
+        self.send_packet(setpoint.encode()?).await
+    }
+
+    async fn send_packet(&self, pk: Packet) -> Result<()> {
+        self.uplink.send_async(pk).await.map_err(|_| Error::Disconnected)
+    }
+}
+
+/// # Legacy RPY+ setpoint
+///
+/// This setpoint was originally the only one present in the Crazyflie and has been (ab)used to
+/// implement the early position control and other assisted and semi-autonomous mode.
+impl Commander {
+    /// Sends a Roll, Pitch, Yawrate, and Thrust setpoint to the Crazyflie.
+    ///
+    /// By default, unless modified by [parameters](crate::subsystems::param::Param), the arguments are interpreted as:
+    /// * `roll` - Desired roll angle (degrees)
+    /// * `pitch` - Desired pitch angle (degrees)
+    /// * `yawrate` - Desired yaw rate (degrees/second)
+    /// * `thrust` - Thrust as a 16-bit value (0 = 0% thrust, 65535 = 100% thrust)
+    ///
+    /// Note: Thrust is locked by default for safety. To unlock, send a setpoint with `thrust = 0` once before sending nonzero thrust values.
+    ///
+    /// Example:
+    /// ```no_run
+    /// # fn spin(cf: crazyflie_lib::Crazyflie) {
+    /// cf.commander.setpoint_rpyt(0.0, 0.0, 0.0, 0);      // Unlocks thrust
+    /// cf.commander.setpoint_rpyt(0.0, 0.0, 0.0, 1000);   // Sets thrust to 1000
+    /// # }
+    /// ```
+    pub async fn setpoint_rpyt(&self, roll: f32, pitch: f32, yawrate: f32, thrust: u16) -> Result<()> {
+        self.send_setpoint(Setpoint::Rpyt { roll, pitch, yawrate, thrust }).await
+    }
+}
+
+/// # Generic setpoints
+///
+/// These setpoints are implemented in such a way that they are easy to add in the Crazyflie firmware
+/// and in libs like this one. So if you have a use-case not covered by any of the existing setpoint
+/// do not hesitate to implement and contribute your dream setpoint :-).
+impl Commander {
+    /// Sends an absolute position setpoint in world coordinates, with yaw as an absolute orientation.
+    ///
+    /// # Arguments
+    /// * `x` - Target x position (meters, world frame)
+    /// * `y` - Target y position (meters, world frame)
+    /// * `z` - Target z position (meters, world frame)
+    /// * `yaw` - Target yaw angle (degrees, absolute)
+    pub async fn setpoint_position(&self, x: f32, y: f32, z: f32, yaw: f32) -> Result<()> {
+        self.send_setpoint(Setpoint::Position { x, y, z, yaw }).await
+    }
+
+    /// Sends a velocity setpoint in the world frame, with yaw rate control.
+    ///
+    /// # Arguments
+    /// * `vx` - Target velocity in x (meters/second, world frame)
+    /// * `vy` - Target velocity in y (meters/second, world frame)
+    /// * `vz` - Target velocity in z (meters/second, world frame)
+    /// * `yawrate` - Target yaw rate (degrees/second)
+    pub async fn setpoint_velocity_world(&self, vx: f32, vy: f32, vz: f32, yawrate: f32) -> Result<()> {
+        self.send_setpoint(Setpoint::VelocityWorld { vx, vy, vz, yawrate }).await
+    }
+
+    /// Sends a setpoint with absolute height (distance to the surface below), roll, pitch, and yaw rate commands.
+    ///
+    /// # Arguments
+    /// * `roll` - Desired roll angle (degrees)
+    /// * `pitch` - Desired pitch angle (degrees)
+    /// * `yawrate` - Desired yaw rate (degrees/second)
+    /// * `zdistance` - Target height above ground (meters)
+    pub async fn setpoint_zdistance(&self, roll: f32, pitch: f32, yawrate: f32, zdistance: f32) -> Result<()> {
+        self.send_setpoint(Setpoint::ZDistance { roll, pitch, yawrate, zdistance }).await
+    }
+
+    /// Sends a setpoint with absolute height (distance to the surface below), and x/y velocity commands in the body-fixed frame.
+    ///
+    /// This is the setpoint to use for velocity control in the body frame, the firmware has no body-frame velocity
+    /// setpoint with a vertical velocity.
+    ///
+    /// # Arguments
+    /// * `vx` - Target velocity in x (meters/second, body frame)
+    /// * `vy` - Target velocity in y (meters/second, body frame)
+    /// * `yawrate` - Target yaw rate (degrees/second)
+    /// * `zdistance` - Target height above ground (meters)
+    pub async fn setpoint_hover(&self, vx: f32, vy: f32, yawrate: f32, zdistance: f32) -> Result<()> {
+        self.send_setpoint(Setpoint::Hover { vx, vy, yawrate, zdistance }).await
+    }
+
+    /// Sends a manual control setpoint for roll, pitch, yaw rate, and thrust percentage.
+    ///
+    /// If `rate` is false, roll and pitch are interpreted as angles (degrees). If `rate` is true, they are interpreted as rates (degrees/second).
+    ///
+    /// # Arguments
+    /// * `roll` - Desired roll (degrees or degrees/second, depending on `rate`)
+    /// * `pitch` - Desired pitch (degrees or degrees/second, depending on `rate`)
+    /// * `yawrate` - Desired yaw rate (degrees/second)
+    /// * `thrust_percentage` - Thrust as a percentage (0 to 100)
+    /// * `rate` - If true, use rate mode; if false, use angle mode
+    pub async fn setpoint_manual(&self, roll: f32, pitch: f32, yawrate: f32, thrust_percentage: f32, rate: bool) -> Result<()> {
+        self.send_setpoint(Setpoint::Manual { roll, pitch, yawrate, thrust_percentage, rate }).await
+    }
+
+    /// Sends a full state setpoint: position, velocity, acceleration, attitude and angular rates.
+    ///
+    /// This setpoint is meant for trajectory tracking: the controller of the Crazyflie uses the velocity,
+    /// acceleration and angular rates as feed-forward. The values are packed in the fixed-point format of the
+    /// firmware, see [FullState] for the resulting limits.
+    ///
+    /// Return an error if a value does not fit in the fixed-point format or if the orientation quaternion is null.
+    pub async fn setpoint_full_state(&self, state: &FullState) -> Result<()> {
+        self.send_setpoint(Setpoint::FullState(*state)).await
+    }
+
+    /// Sends an altitude-hold setpoint: roll and pitch angles, yaw rate and vertical velocity.
+    ///
+    /// # Arguments
+    /// * `roll` - Desired roll angle (degrees)
+    /// * `pitch` - Desired pitch angle (degrees)
+    /// * `yawrate` - Desired yaw rate (degrees/second)
+    /// * `vz` - Desired vertical velocity (meters/second)
+    pub async fn setpoint_alt_hold(&self, roll: f32, pitch: f32, yawrate: f32, vz: f32) -> Result<()> {
+        self.send_setpoint(Setpoint::AltHold { roll, pitch, yawrate, vz }).await
+    }
+
+    /// Sends a CPPM emulation setpoint, as if the Crazyflie was controlled by an RC transmitter.
+    ///
+    /// The channel values are pulse widths in microseconds, nominally between 1000 and 2000. The channels are
+    /// interpreted by the firmware like the channels of a CPPM receiver: the flight mode (angle or rate) is selected
+    /// with the `flightmode` parameters.
+    ///
+    /// # Arguments
+    /// * `roll` - Roll channel (microseconds)
+    /// * `pitch` - Pitch channel (microseconds)
+    /// * `yaw` - Yaw channel (microseconds)
+    /// * `thrust` - Thrust channel (microseconds)
+    /// * `aux` - Auxiliary channels (microseconds)
+    ///
+    /// Return an error if there are more than [CPPM_MAX_AUX_CHANNELS] auxiliary channels.
+    pub async fn setpoint_cppm(&self, roll: u16, pitch: u16, yaw: u16, thrust: u16, aux: &[u16]) -> Result<()> {
+        self.send_setpoint(Setpoint::Cppm { roll, pitch, yaw, thrust, aux: aux.to_vec() }).await
+    }
+
+    /// Sends a STOP setpoint, immediately stopping the motors. The Crazyflie will lose lift and may fall.
+    pub async fn setpoint_stop(&self) -> Result<()> {
+        self.send_setpoint(Setpoint::Stop).await
+    }
+
+    /// Notify the firmware that low-level setpoints have stopped.
+    ///
+    /// This tells the Crazyflie to drop the current low-level setpoint priority,
+    /// allowing the High-level commander (or other sources) to take control again.
+    ///
+    /// # Arguments
+    /// * `remain_valid_milliseconds` - How long (in ms) the last low-level setpoint
+    ///   should remain valid before it is considered stale. Use `0` to make the
+    ///   hand-off immediate; small non-zero values can smooth transitions if needed.
+    ///
+    /// # Examples
+    /// Hand control back to the High-level commander after a manual low-level burst:
+    /// ```no_run
+    /// # use crazyflie_lib::subsystems::commander::Commander;
+    /// # use crazyflie_lib::Result;
+    /// # async fn demo(cmd: &Commander) -> Result<()> {
+    /// // ... you were sending low-level setpoints here ...
+    /// cmd.notify_setpoint_stop(0).await?; // allow HL commander to resume
+    /// # Ok(()) }
+    /// ```
+    pub async fn notify_setpoint_stop(&self, remain_valid_milliseconds: u32) -> Result<()> {
+        let mut payload = Vec::with_capacity(1 + 4);
+        payload.push(TYPE_META_COMMAND_NOTIFY_SETPOINT_STOP);
+        payload.extend_from_slice(&remain_valid_milliseconds.to_le_bytes());
+        self.send_packet(Packet::new(GENERIC_SETPOINT_PORT, GENERIC_CMD_CHANNEL, payload)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quaternion_compression() {
+        // Identity: only the index of the largest component, w
+        assert_eq!(compress_quaternion([0.0, 0.0, 0.0, 1.0]).unwrap(), 3 << 30);
+        assert_eq!(compress_quaternion([0.0, 0.0, 0.0, -2.0]).unwrap(), 3 << 30);
+        // 90 degrees yaw: z is the first largest component, w is stored with the full magnitude
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert_eq!(compress_quaternion([0.0, 0.0, half, half]).unwrap(), (2 << 30) | 511);
+        // -90 degrees yaw: negative components are flagged with the sign bit
+        assert_eq!(compress_quaternion([0.0, 0.0, -half, half]).unwrap(), (2 << 30) | (1 << 9) | 511);
+        assert!(compress_quaternion([0.0; 4]).is_err());
+    }
+
+    #[test]
+    fn fixed_point_range() {
+        assert_eq!(to_milli_i16(1.5, "position").unwrap(), 1500);
+        assert_eq!(to_milli_i16(-0.0016, "position").unwrap(), -2);
+        assert!(to_milli_i16(32.8, "position").is_err());
+        assert!(to_milli_i16(f32::NAN, "position").is_err());
+    }
+}
@@ -0,0 +1,277 @@
+use super::{Commander, Setpoint};
+use crate::{Error, Result};
+use crazyflie_link::Packet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, MissedTickBehavior};
+
+/// Maximum rate of a setpoint stream, in Hz
+const MAX_STREAM_RATE: f32 = 1000.0;
+
+/// # Setpoint stream failsafe
+///
+/// Action taken by a [SetpointStream] when it is dropped, stopped or stale.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SetpointFailsafe {
+    /// Notify the Crazyflie that the setpoints have stopped, see [Commander::notify_setpoint_stop()]
+    ///
+    /// The control is handed back to the high-level commander, or to any lower priority setpoint source, after
+    /// `remain_valid_milliseconds`.
+    NotifySetpointStop {
+        /// Time the last setpoint remains valid in the Crazyflie, in milliseconds
+        remain_valid_milliseconds: u32,
+    },
+    /// Hover at the current target for `hover_duration`, then land at `landing_velocity`
+    ///
+    /// This is only possible if the last setpoint has a height target, that is a [Setpoint::Position],
+    /// [Setpoint::Hover] or [Setpoint::ZDistance] setpoint. For the other setpoints, the stream falls back to
+    /// notifying the Crazyflie that the setpoints have stopped.
+    ///
+    /// Once landed, the motors are stopped and the Crazyflie is notified that the setpoints have stopped.
+    HoverThenLand {
+        /// Time spent hovering before landing
+        hover_duration: Duration,
+        /// Landing vertical velocity (meters/second)
+        landing_velocity: f32,
+    },
+}
+
+impl Default for SetpointFailsafe {
+    fn default() -> Self {
+        SetpointFailsafe::NotifySetpointStop {
+            remain_valid_milliseconds: 0,
+        }
+    }
+}
+
+/// # Setpoint stream options
+///
+/// Options of a [SetpointStream], see [Commander::stream_with_options()].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SetpointStreamOptions {
+    /// Action taken when the stream is dropped, stopped or stale
+    pub failsafe: SetpointFailsafe,
+    /// Maximum time without a new target before the stream is stale. `None` disables the staleness detection: the
+    /// last target is then re-sent until the stream is dropped.
+    pub stale_timeout: Option<Duration>,
+}
+
+/// # Setpoint stream
+///
+/// Handle to a background task re-sending a [Setpoint] at a fixed rate, which keeps the commander watchdog of the
+/// Crazyflie fed. The target is updated with [SetpointStream::set()], which can be called from any task.
+///
+/// The stream runs its [SetpointFailsafe] and ends when:
+///  - The handle is dropped or [SetpointStream::stop()] is called.
+///  - The stream is stale: the target has not been updated for longer than the
+///    [stale timeout](SetpointStreamOptions::stale_timeout). This protects against a controlling task that hangs or
+///    dies while the handle is kept alive.
+///  - Sending a setpoint fails. The failsafe is skipped only if the Crazyflie is disconnected.
+///
+/// Created with [Commander::stream()]. The background task runs on the tokio runtime the stream was created in.
+///
+/// ```no_run
+/// # use crazyflie_lib::subsystems::commander::Setpoint;
+/// # use tokio::time::{sleep, Duration};
+/// # async fn example(cf: crazyflie_lib::Crazyflie) -> crazyflie_lib::Result<()> {
+/// let stream = cf.commander.stream(50.0)?;
+///
+/// for height in [0.2, 0.4, 0.6, 0.4, 0.2] {
+///     stream.set(Setpoint::Hover { vx: 0.0, vy: 0.0, yawrate: 0.0, zdistance: height })?;
+///     sleep(Duration::from_secs(1)).await;
+/// }
+///
+/// // Hands the control back to the high-level commander
+/// stream.stop().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SetpointStream {
+    target: watch::Sender<Option<(Setpoint, Packet)>>,
+    /// Set by the task when it stops streaming the targets, before running the failsafe
+    ended: Arc<AtomicBool>,
+    task: JoinHandle<Result<()>>,
+}
+
+impl Commander {
+    /// Start streaming setpoints at `rate` Hz with the default options
+    ///
+    /// Nothing is sent until the first target is set. When dropped, the stream notifies the Crazyflie that the
+    /// setpoints have stopped. See [SetpointStream] and [Commander::stream_with_options()].
+    ///
+    /// Return an error if the rate is not a positive number of at most 1000 Hz.
+    pub fn stream(&self, rate: f32) -> Result<SetpointStream> {
+        self.stream_with_options(rate, SetpointStreamOptions::default())
+    }
+
+    /// Start streaming setpoints at `rate` Hz
+    ///
+    /// See [Commander::stream()].
+    pub fn stream_with_options(&self, rate: f32, options: SetpointStreamOptions) -> Result<SetpointStream> {
+        if rate.is_nan() || rate <= 0.0 || rate > MAX_STREAM_RATE {
+            return Err(Error::InvalidArgument(format!(
+                "Setpoint stream rate must be between 0 and {} Hz, got {}",
+                MAX_STREAM_RATE, rate
+            )));
+        }
+        if let SetpointFailsafe::HoverThenLand { landing_velocity, .. } = options.failsafe
+            && (landing_velocity.is_nan() || landing_velocity <= 0.0)
+        {
+            return Err(Error::InvalidArgument(format!(
+                "Landing velocity must be positive, got {}",
+                landing_velocity
+            )));
+        }
+
+        let (target, receiver) = watch::channel(None);
+        let commander = Commander::new(self.uplink.clone());
+        let period = Duration::from_secs_f32(1.0 / rate);
+        let ended = Arc::new(AtomicBool::new(false));
+        let task = tokio::spawn(stream_task(commander, receiver, ended.clone(), period, options));
+
+        Ok(SetpointStream { target, ended, task })
+    }
+}
+
+impl SetpointStream {
+    /// Set the target setpoint
+    ///
+    /// The setpoint is sent right away and then re-sent at the stream rate until the next call.
+    ///
+    /// Return an error if the setpoint cannot be sent, see [Commander::send_setpoint()], in which case the stream
+    /// keeps the previous target. Return an error if the stream has ended, because it was stale or because the
+    /// Crazyflie is disconnected, including while its failsafe is running.
+    pub fn set(&self, setpoint: Setpoint) -> Result<()> {
+        if !self.is_active() {
+            return Err(Error::SystemError("Setpoint stream has ended".to_owned()));
+        }
+        // Encoded here so that an invalid setpoint is reported to the caller instead of ending the stream
+        let packet = setpoint.encode()?;
+        // The task is running so the receiver is alive
+        self.target.send_replace(Some((setpoint, packet)));
+        Ok(())
+    }
+
+    /// Return true while the background task is streaming the targets
+    ///
+    /// This is false as soon as the stream has ended, even if the failsafe is still running.
+    pub fn is_active(&self) -> bool {
+        !self.ended.load(Ordering::Acquire) && !self.task.is_finished()
+    }
+
+    /// Stop the stream
+    ///
+    /// The [SetpointFailsafe] is run and this function returns when it is done. Dropping the stream has the same
+    /// effect without waiting.
+    ///
+    /// Return [Error::Timeout] if the stream had ended because it was stale, or the error that ended the stream if
+    /// sending a setpoint failed.
+    pub async fn stop(self) -> Result<()> {
+        let SetpointStream { target, task, .. } = self;
+        drop(target);
+
+        task.await
+            .map_err(|e| Error::SystemError(format!("Setpoint stream task failed: {}", e)))?
+    }
+}
+
+async fn stream_task(
+    commander: Commander,
+    mut receiver: watch::Receiver<Option<(Setpoint, Packet)>>,
+    ended: Arc<AtomicBool>,
+    period: Duration,
+    options: SetpointStreamOptions,
+) -> Result<()> {
+    let mut interval = tokio::time::interval(period);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    let mut target: Option<(Setpoint, Packet)> = None;
+    let mut last_update = Instant::now();
+    let result = async {
+        loop {
+            tokio::select! {
+                changed = receiver.changed() => {
+                    if changed.is_err() {
+                        // The handle has been dropped
+                        return Ok(());
+                    }
+                    target = receiver.borrow_and_update().clone();
+                    last_update = Instant::now();
+                    if let Some((_, packet)) = &target {
+                        commander.send_packet(packet.clone()).await?;
+                    }
+                    interval.reset();
+                }
+                _ = interval.tick() => {
+                    if let Some(timeout) = options.stale_timeout
+                        && last_update.elapsed() > timeout
+                    {
+                        return Err(Error::Timeout);
+                    }
+                    if let Some((_, packet)) = &target {
+                        commander.send_packet(packet.clone()).await?;
+                    }
+                }
+            }
+        }
+    }
+    .await;
+    ended.store(true, Ordering::Release);
+
+    // Nothing can be sent anymore if the Crazyflie is disconnected
+    if matches!(result, Err(Error::Disconnected)) {
+        return result;
+    }
+
+    // Nothing to hand over if nothing has been sent
+    if let Some((setpoint, _)) = target {
+        run_failsafe(&commander, setpoint, period, options.failsafe).await?;
+    }
+
+    result
+}
+
+async fn run_failsafe(
+    commander: &Commander,
+    setpoint: Setpoint,
+    period: Duration,
+    failsafe: SetpointFailsafe,
+) -> Result<()> {
+    let (hover_duration, landing_velocity) = match failsafe {
+        SetpointFailsafe::NotifySetpointStop { remain_valid_milliseconds } => {
+            return commander.notify_setpoint_stop(remain_valid_milliseconds).await;
+        }
+        SetpointFailsafe::HoverThenLand { hover_duration, landing_velocity } => (hover_duration, landing_velocity),
+    };
+
+    // Setpoint holding the horizontal position at a given height
+    let (mut height, at_height): (f32, Box<dyn Fn(f32) -> Setpoint + Send>) = match setpoint {
+        Setpoint::Position { x, y, z, yaw } => (z, Box::new(move |z| Setpoint::Position { x, y, z, yaw })),
+        Setpoint::Hover { zdistance, .. } | Setpoint::ZDistance { zdistance, .. } => (
+            zdistance,
+            Box::new(|zdistance| Setpoint::Hover { vx: 0.0, vy: 0.0, yawrate: 0.0, zdistance }),
+        ),
+        _ => return commander.notify_setpoint_stop(0).await,
+    };
+
+    let mut interval = tokio::time::interval(period);
+    let hover_end = Instant::now() + hover_duration;
+    while Instant::now() < hover_end {
+        interval.tick().await;
+        commander.send_setpoint(at_height(height)).await?;
+    }
+
+    let step = landing_velocity * period.as_secs_f32();
+    while height > 0.0 {
+        interval.tick().await;
+        height = (height - step).max(0.0);
+        commander.send_setpoint(at_height(height)).await?;
+    }
+
+    commander.setpoint_stop().await?;
+    commander.notify_setpoint_stop(0).await
+}
@@ -81,7 +81,7 @@ pub const TRAJECTORY_TYPE_POLY4D_COMPRESSED: u8 = 1;
 /// # Ok(())
 /// # }
 /// ```
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct HighLevelCommander {
     uplink: Sender<Packet>,
 }
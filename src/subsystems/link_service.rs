@@ -8,6 +8,7 @@
 //! - **Sink** (channel 2): packets are dropped and ignored
 
 use crate::crazyflie::LINK_PORT;
+use crate::crtp_utils::CrtpTransport;
 use crate::{Error, Result};
 use crazyflie_link::Packet;
 use flume as channel;
@@ -67,14 +68,14 @@ pub struct LinkService {
     uplink: channel::Sender<Packet>,
     echo_downlink: Mutex<channel::Receiver<Packet>>,
     source_downlink: Mutex<channel::Receiver<Packet>>,
-    link: Arc<crazyflie_link::Connection>,
+    link: Arc<dyn CrtpTransport>,
 }
 
 impl LinkService {
     pub(crate) fn new(
         uplink: channel::Sender<Packet>,
         downlink: channel::Receiver<Packet>,
-        link: Arc<crazyflie_link::Connection>,
+        link: Arc<dyn CrtpTransport>,
     ) -> Self {
         let (echo_downlink, source_downlink, _, _) =
             crate::crtp_utils::crtp_channel_dispatcher(downlink);
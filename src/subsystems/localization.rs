@@ -230,6 +230,7 @@ fn decode_lh_angle(data: &[u8]) -> Result<LighthouseAngleData> {
 /// Emergency control interface
 ///
 /// Provides emergency stop functionality that immediately stops all motors.
+#[derive(Debug, Clone)]
 pub struct EmergencyControl {
     uplink: Sender<Packet>,
 }
@@ -0,0 +1,315 @@
+use super::{Log, LogBlock, LogPeriod, LogStream, LOG_BLOCK_MAX_PAYLOAD};
+use crate::{Error, Result, ValueType};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// # Log configuration
+///
+/// Named log block configuration: a period and a list of variables. The configurations are stored in the JSON format
+/// of the Python cfclient, they are loaded as ready-to-start log blocks with [Log::load_config()] and saved with
+/// [Log::save_config()].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogConfig {
+    /// Name of the configuration
+    pub name: String,
+    /// Period of the log block
+    pub period: LogPeriod,
+    /// Variables of the log block, in order
+    pub variables: Vec<LogConfigVariable>,
+}
+
+/// Variable of a [LogConfig]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogConfigVariable {
+    /// Log variable from the TOC, by name
+    Toc(String),
+    /// Variable in the Crazyflie memory, see [LogBlock::add_memory_variable()]
+    Memory {
+        /// Address of the variable in the Crazyflie RAM
+        address: u32,
+        /// Type of the variable
+        value_type: ValueType,
+    },
+}
+
+/// # Configured log block
+///
+/// Log block created from a [LogConfig] by [Log::load_config()], with the variables already added.
+pub struct ConfiguredLogBlock {
+    /// Name of the configuration
+    pub name: String,
+    /// Period from the configuration
+    pub period: LogPeriod,
+    /// The log block, ready to be started
+    pub block: LogBlock,
+}
+
+impl ConfiguredLogBlock {
+    /// Start the log block at the period of the configuration
+    ///
+    /// See [LogBlock::start()].
+    pub async fn start(self) -> Result<LogStream> {
+        self.block.start(self.period).await
+    }
+}
+
+/// cfclient log configuration file, `{"logconf": {"logblock": {...}}}`
+#[derive(Serialize, Deserialize)]
+struct ConfigFile {
+    logconf: ConfigLogconf,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ConfigLogconf {
+    logblock: ConfigBlock,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ConfigBlock {
+    name: String,
+    period: u64,
+    variables: Vec<ConfigVariable>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ConfigVariable {
+    name: String,
+    /// "TOC" for TOC variables, anything else is a memory variable
+    #[serde(rename = "type")]
+    variable_type: String,
+    stored_as: String,
+    fetch_as: String,
+    /// Hexadecimal address of a memory variable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    address: Option<String>,
+}
+
+/// Name of a log type in the cfclient configuration files
+fn type_name(value_type: ValueType) -> Result<&'static str> {
+    match value_type {
+        ValueType::U8 => Ok("uint8_t"),
+        ValueType::U16 => Ok("uint16_t"),
+        ValueType::U32 => Ok("uint32_t"),
+        ValueType::I8 => Ok("int8_t"),
+        ValueType::I16 => Ok("int16_t"),
+        ValueType::I32 => Ok("int32_t"),
+        ValueType::F16 => Ok("FP16"),
+        ValueType::F32 => Ok("float"),
+        _ => Err(Error::LogError(format!(
+            "Value type {:?} not handled by log",
+            value_type
+        ))),
+    }
+}
+
+fn type_from_name(name: &str) -> Result<ValueType> {
+    match name {
+        "uint8_t" => Ok(ValueType::U8),
+        "uint16_t" => Ok(ValueType::U16),
+        "uint32_t" => Ok(ValueType::U32),
+        "int8_t" => Ok(ValueType::I8),
+        "int16_t" => Ok(ValueType::I16),
+        "int32_t" => Ok(ValueType::I32),
+        "FP16" => Ok(ValueType::F16),
+        "float" => Ok(ValueType::F32),
+        _ => Err(Error::InvalidArgument(format!("Unknown log type {}", name))),
+    }
+}
+
+fn invalid_config(path: &Path, error: impl std::fmt::Display) -> Error {
+    Error::InvalidArgument(format!("Invalid log configuration {}: {}", path.display(), error))
+}
+
+/// Read a configuration file, or all the `.json` files of a directory in alphabetical order
+fn read_config_files(path: &Path) -> Result<Vec<(PathBuf, ConfigFile)>> {
+    let mut files = Vec::new();
+    if path.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            let file = entry?.path();
+            if file.extension().is_some_and(|extension| extension == "json") {
+                files.push(file);
+            }
+        }
+        files.sort();
+    } else {
+        files.push(path.to_owned());
+    }
+
+    files
+        .into_iter()
+        .map(|file| {
+            let content =
+                serde_json::from_reader(std::fs::File::open(&file)?).map_err(|e| invalid_config(&file, e))?;
+            Ok((file, content))
+        })
+        .collect()
+}
+
+impl LogConfig {
+    /// Read log configurations
+    ///
+    /// `path` is either a cfclient log configuration file or a directory, like the cfclient log configuration
+    /// directory, in which case all the `.json` files of the directory are read in alphabetical order.
+    ///
+    /// The configurations are not validated against the TOC, see [Log::load_config()] for that.
+    pub fn read(path: impl AsRef<Path>) -> Result<Vec<LogConfig>> {
+        read_config_files(path.as_ref())?
+            .into_iter()
+            .map(|(file, content)| Self::from_file(&file, content))
+            .collect()
+    }
+
+    fn from_file(path: &Path, content: ConfigFile) -> Result<LogConfig> {
+        let block = content.logconf.logblock;
+
+        let variables = block
+            .variables
+            .into_iter()
+            .map(|variable| {
+                if variable.variable_type == "TOC" {
+                    return Ok(LogConfigVariable::Toc(variable.name));
+                }
+
+                let value_type = type_from_name(&variable.fetch_as)?;
+                if type_from_name(&variable.stored_as)? != value_type {
+                    return Err(invalid_config(path, "memory variables cannot be converted"));
+                }
+                let address = variable
+                    .address
+                    .ok_or_else(|| invalid_config(path, "memory variable without address"))?;
+                let address = u32::from_str_radix(address.trim_start_matches("0x"), 16)
+                    .map_err(|e| invalid_config(path, e))?;
+                Ok(LogConfigVariable::Memory { address, value_type })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(LogConfig {
+            name: block.name,
+            period: LogPeriod::from_millis(block.period)?,
+            variables,
+        })
+    }
+}
+
+impl Log {
+    /// Load log configurations and create the log blocks
+    ///
+    /// `path` is either a cfclient log configuration file or a directory, see [LogConfig::read()]. All the
+    /// configurations are validated against the TOC before any block is created: an error is returned if a variable
+    /// does not exist, if its type in the file does not match the TOC, or if the variables of a configuration do not
+    /// fit in one log block.
+    ///
+    /// The returned log blocks have their variables added and are ready to be started.
+    pub async fn load_config(&self, path: impl AsRef<Path>) -> Result<Vec<ConfiguredLogBlock>> {
+        let mut configs = Vec::new();
+        for (file, content) in read_config_files(path.as_ref())? {
+            for variable in &content.logconf.logblock.variables {
+                if variable.variable_type != "TOC" {
+                    continue;
+                }
+                let toc_type = self.get_type(&variable.name)?;
+                if type_from_name(&variable.fetch_as)? != toc_type {
+                    return Err(Error::LogError(format!(
+                        "Type mismatch for log variable {}: the TOC type is {:?}, the configuration type is {}",
+                        variable.name, toc_type, variable.fetch_as
+                    )));
+                }
+            }
+
+            let config = LogConfig::from_file(&file, content)?;
+            let mut size = 0;
+            for variable in &config.variables {
+                size += match variable {
+                    LogConfigVariable::Toc(name) => self.get_type(name)?.byte_length(),
+                    LogConfigVariable::Memory { value_type, .. } => value_type.byte_length(),
+                };
+            }
+            if size > LOG_BLOCK_MAX_PAYLOAD {
+                return Err(invalid_config(&file, "the variables do not fit in a log block"));
+            }
+            configs.push(config);
+        }
+
+        let mut blocks = Vec::new();
+        for config in configs {
+            let mut block = self.create_block().await?;
+            for variable in &config.variables {
+                match variable {
+                    LogConfigVariable::Toc(name) => block.add_variable(name).await?,
+                    LogConfigVariable::Memory { address, value_type } => {
+                        block.add_memory_variable(*address, *value_type).await?
+                    }
+                }
+            }
+            blocks.push(ConfiguredLogBlock {
+                name: config.name,
+                period: config.period,
+                block,
+            });
+        }
+
+        Ok(blocks)
+    }
+
+    /// Save log configurations in the cfclient format
+    ///
+    /// If `path` is a directory, each configuration is written in a file named after the configuration, as the
+    /// cfclient does. Otherwise only one configuration can be written, in the file `path`. Existing files are
+    /// overwritten.
+    ///
+    /// The type of the TOC variables is written from the TOC, an error is returned if a variable does not exist.
+    pub fn save_config(&self, path: impl AsRef<Path>, configs: &[LogConfig]) -> Result<()> {
+        let path = path.as_ref();
+        let files = if path.is_dir() {
+            configs
+                .iter()
+                .map(|config| (path.join(format!("{}.json", config.name)), config))
+                .collect()
+        } else if let [config] = configs {
+            vec![(path.to_owned(), config)]
+        } else {
+            return Err(Error::InvalidArgument(
+                "Only one log configuration can be written to a file".to_owned(),
+            ));
+        };
+
+        for (file, config) in files {
+            let variables = config
+                .variables
+                .iter()
+                .map(|variable| {
+                    let (name, variable_type, value_type, address) = match variable {
+                        LogConfigVariable::Toc(name) => (name.clone(), "TOC", self.get_type(name)?, None),
+                        LogConfigVariable::Memory { address, value_type } => {
+                            let address = format!("0x{:08x}", address);
+                            (address.clone(), "Memory", *value_type, Some(address))
+                        }
+                    };
+                    Ok(ConfigVariable {
+                        name,
+                        variable_type: variable_type.to_owned(),
+                        stored_as: type_name(value_type)?.to_owned(),
+                        fetch_as: type_name(value_type)?.to_owned(),
+                        address,
+                    })
+                })
+                .collect::<Result<_>>()?;
+
+            let content = ConfigFile {
+                logconf: ConfigLogconf {
+                    logblock: ConfigBlock {
+                        name: config.name.clone(),
+                        period: config.period.as_millis(),
+                        variables,
+                    },
+                },
+            };
+            let content = serde_json::to_string_pretty(&content).map_err(|e| {
+                Error::InvalidArgument(format!("Failed to serialize log configuration: {}", e))
+            })?;
+            std::fs::write(file, content)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1166 @@
+//! # Data logging subsystem
+//!
+//! The Crazyflie log subsystem allows to asynchronously log the value of exposed Crazyflie variables from the ground.
+//!
+//! At connection time, a Table Of Content (TOC) of the log variable is fetched from the Crazyflie which allows to
+//! log variables using their names. To log variable a [LogBlock](struct@LogBlock) needs to be created. The variable
+//! to be logged are added to the LogBlock and then the LogBlock can be started returning a LogStream that will yield
+//! the log data.
+//!
+//! ```no_run
+//! # use crazyflie_lib::{Crazyflie, Value, Error, subsystems::log::LogPeriod};
+//! # use crazyflie_link::LinkContext;
+//! # async fn example() -> Result<(), Error> {
+//! # let context = LinkContext::new();
+//! # let cf = Crazyflie::connect_from_uri(
+//! #   &context,
+//! #   "radio://0/60/2M/E7E7E7E7E7",
+//! #   crazyflie_lib::NoTocCache
+//! # ).await?;
+//! // Create the log block
+//! let mut block = cf.log.create_block().await?;
+//!
+//! // Append Variables
+//! block.add_variable("stateEstimate.roll").await?;
+//! block.add_variable("stateEstimate.pitch").await?;
+//! block.add_variable("stateEstimate.yaw").await?;
+//!
+//! // Start the block
+//! let period = LogPeriod::from_millis(100)?;
+//! let stream = block.start(period).await?;
+//!
+//! // Get Data!
+//! while let Ok(data) = stream.next().await {
+//!     println!("Yaw is {:?}", data.data["stateEstimate.yaw"]);
+//! }
+//! # Ok(())
+//! # };
+//! ```
+//!
+//! Log blocks can also be described by a struct deriving [LogBlock](derive@LogBlock) and started with
+//! [Log::start_typed_block()]. The samples are then decoded directly into the struct, see [TypedLogBlock].
+//!
+//! A log block is limited to 26 bytes of variables. To log more variables at the same period, a [LogSession] splits
+//! them across as many blocks as needed and merges the samples.
+//!
+//! The samples of log streams can be recorded to CSV or MCAP files with a [LogRecorder].
+//!
+//! Log blocks can be loaded from, and saved to, the log configuration files of the Python cfclient, see
+//! [Log::load_config()].
+
+use crate::crtp_utils::{TocCache, WaitForPacket};
+use crate::events::EventSender;
+use crate::{Error, Result, Value, ValuePrimitive, ValueType};
+use crazyflie_link::Packet;
+use flume as channel;
+use futures::lock::Mutex;
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::sync::Weak;
+use std::{collections::BTreeMap, convert::TryFrom, sync::Arc, time::Duration};
+
+use crate::crazyflie::LOG_PORT;
+
+mod config;
+mod recorder;
+mod session;
+
+pub use config::*;
+pub use recorder::*;
+pub use session::*;
+
+/// # Access to the Crazyflie Log Subsystem
+///
+/// This struct provide functions to interact with the Crazyflie Log subsystem.
+///
+/// See the [log module documentation](crate::subsystems::log) for more context and information.
+#[derive(Debug)]
+pub struct Log {
+    uplink: channel::Sender<Packet>,
+    control_downlink: Arc<Mutex<channel::Receiver<Packet>>>,
+    toc: Arc<BTreeMap<String, (u16, LogItemInfo)>>,
+    next_block_id: Mutex<u8>,
+    data_channels: Arc<Mutex<BTreeMap<u8, flume::Sender<Packet>>>>,
+    active_blocks: Arc<Mutex<BTreeMap<u8, ActiveBlock>>>,
+}
+
+/// Configuration of a log block created by the lib
+///
+/// Kept to be able to re-create the block in the Crazyflie after a reconnection.
+#[derive(Debug)]
+struct ActiveBlock {
+    canary: Weak<()>,
+    /// Name and append operation of the variables, in order. The append operation is the log type followed by the
+    /// TOC ID, or by the TOC ID 0xFFFF and the memory address
+    variables: Vec<(String, Vec<u8>)>,
+    /// Period of the block if it is started
+    period: Option<u8>,
+}
+
+fn not_found(name: &str) -> Error {
+    Error::ParamError(format!("Log variable {} not found", name))
+}
+
+const CONTROL_CHANNEL: u8 = 1;
+
+/// The Crazyflie log timestamp is 24 bits long
+const TIMESTAMP_MASK: u32 = 0x00FF_FFFF;
+
+const DELETE_BLOCK: u8 = 2;
+const START_BLOCK: u8 = 3;
+const STOP_BLOCK: u8 = 4;
+const RESET: u8 = 5;
+const CREATE_BLOCK_V2: u8 = 6;
+const APPEND_BLOCK_V2: u8 = 7;
+
+/// TOC ID marking a memory variable in an append operation
+const MEMORY_VARIABLE_ID: u16 = 0xFFFF;
+
+/// Maximum size of the append operations sent in one packet, leaving room for the command and block ID
+const APPEND_MAX_PAYLOAD: usize = 28;
+
+impl Log {
+    pub(crate) async fn new<T>(
+        downlink: channel::Receiver<Packet>,
+        uplink: channel::Sender<Packet>,
+        toc_cache: T,
+        events: &EventSender,
+    ) -> Result<Self>
+    where
+        T: TocCache,
+    {
+        let (toc_downlink, control_downlink, data_downlink, _) =
+            crate::crtp_utils::crtp_channel_dispatcher(downlink);
+
+        let toc = crate::crtp_utils::fetch_toc(LOG_PORT, uplink.clone(), toc_downlink, toc_cache, events).await?;
+        let toc = Arc::new(toc);
+
+        let control_downlink = Arc::new(Mutex::new(control_downlink));
+
+        let next_block_id = Mutex::new(0);
+
+        let data_channels = Arc::new(Mutex::new(BTreeMap::new()));
+
+        let active_blocks = Arc::new(Mutex::new(BTreeMap::new()));
+
+        let log = Self {
+            uplink,
+            control_downlink,
+            toc,
+            next_block_id,
+            data_channels,
+            active_blocks,
+        };
+        log.reset().await?;
+        log.spawn_data_dispatcher(data_downlink).await;
+
+        Ok(log)
+    }
+
+    async fn reset(&self) -> Result<()> {
+        let downlink = self.control_downlink.lock().await;
+
+        let pk = Packet::new(LOG_PORT, CONTROL_CHANNEL, vec![RESET]);
+        self.uplink
+            .send_async(pk)
+            .await
+            .map_err(|_| Error::Disconnected)?;
+
+        let pk = downlink
+            .wait_packet(LOG_PORT, CONTROL_CHANNEL, &[RESET])
+            .await?;
+        assert_eq!(pk.get_data()[2], 0);
+
+        Ok(())
+    }
+
+    async fn spawn_data_dispatcher(&self, data_downlink: flume::Receiver<Packet>) {
+        let data_channels = self.data_channels.clone();
+        tokio::spawn(async move {
+            while let Ok(packet) = data_downlink.recv_async().await {
+                if packet.get_data().len() > 1 {
+                    let block_id = packet.get_data()[0];
+                    let data_channels = data_channels.lock().await;
+                    if data_channels.contains_key(&block_id)
+                        && data_channels
+                            .get(&block_id)
+                            .unwrap()
+                            .send_async(packet)
+                            .await
+                            .is_err()
+                    {
+                        break;
+                    }
+                }
+            }
+            data_channels.lock().await.clear();
+        });
+    }
+
+    /// Get the names of all the log variables
+    ///
+    /// The names contain group and name of the log variable formatted as
+    /// "group.name".
+    pub fn names(&self) -> Vec<String> {
+        self.toc.keys().cloned().collect()
+    }
+
+    /// Return the type of a log variable or an Error if the parameter does not exist.
+    pub fn get_type(&self, name: &str) -> Result<ValueType> {
+        Ok(self
+            .toc
+            .get(name)
+            .ok_or_else(|| not_found(name))?
+            .1
+            .item_type)
+    }
+
+    /// Find a free block ID, the control downlink must be locked to prevent concurrent allocations
+    ///
+    /// The IDs are allocated in a round-robin fashion so that the ID of a block that was just deleted is not re-used
+    /// right away.
+    async fn generate_next_block_id(&self) -> Result<u8> {
+        let mut next_block_id = self.next_block_id.lock().await;
+        let active_blocks = self.active_blocks.lock().await;
+
+        let id = (0..=u8::MAX)
+            .map(|offset| next_block_id.wrapping_add(offset))
+            .find(|id| !active_blocks.contains_key(id))
+            .ok_or_else(|| Error::LogError("No more block ID available!".into()))?;
+        *next_block_id = id.wrapping_add(1);
+        Ok(id)
+    }
+
+    /// Cleanup dropped LogBlocks
+    ///
+    /// The blocks are normally deleted when they are dropped, this catches the blocks that could not be deleted at
+    /// that time.
+    async fn cleanup_blocks(&self) -> Result<()> {
+        // The control downlink is always locked before the active blocks
+        let control_downlink = self.control_downlink.lock().await;
+        let mut active_blocks = self.active_blocks.lock().await;
+
+        let dropped_blocks: Vec<u8> = active_blocks
+            .iter()
+            .filter(|(_, block)| block.canary.upgrade().is_none())
+            .map(|(block_id, _)| *block_id)
+            .collect();
+
+        for block_id in dropped_blocks {
+            delete_block(
+                &self.uplink,
+                &control_downlink,
+                &mut active_blocks,
+                &self.data_channels,
+                block_id,
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the log blocks currently created in the Crazyflie by this lib
+    ///
+    /// The blocks are listed by ID. The blocks that have been dropped but not deleted yet are not listed.
+    pub async fn active_blocks(&self) -> Vec<LogBlockInfo> {
+        self.active_blocks
+            .lock()
+            .await
+            .iter()
+            .filter(|(_, block)| block.canary.upgrade().is_some())
+            .map(|(block_id, block)| LogBlockInfo {
+                id: *block_id,
+                variables: block.variables.iter().map(|(name, _)| name.clone()).collect(),
+                period: block.period.map(LogPeriod),
+            })
+            .collect()
+    }
+
+    /// Create a Log block
+    ///
+    /// This will create a log block in the Crazyflie firmware and return a
+    /// [LogBlock](struct@LogBlock) object that can be used to add variable to the block and start
+    /// logging
+    ///
+    /// Each log block is assigned a 8 bit ID by the lib, the IDs are re-used once the blocks are deleted.
+    ///
+    /// The Crazyflie firmware has a limit in number of active log block,
+    /// this function will fail if this limit is reached. The log blocks are
+    /// deleted in the Crazyflie when the [LogBlock](struct@LogBlock) object is dropped or when
+    /// [LogBlock::delete()] is called.
+    pub async fn create_block(&self) -> Result<LogBlock> {
+        self.cleanup_blocks().await?;
+
+        let control_downlink = self.control_downlink.lock().await;
+        let block_id = self.generate_next_block_id().await?;
+
+        let pk = Packet::new(LOG_PORT, CONTROL_CHANNEL, vec![CREATE_BLOCK_V2, block_id]);
+        self.uplink
+            .send_async(pk)
+            .await
+            .map_err(|_| Error::Disconnected)?;
+
+        let pk = control_downlink
+            .wait_packet(LOG_PORT, CONTROL_CHANNEL, &[CREATE_BLOCK_V2, block_id])
+            .await?;
+        let error = pk.get_data()[2];
+
+        if error != 0 {
+            return Err(Error::LogError(format!(
+                "Protocol error when creating block: {}",
+                error
+            )));
+        }
+
+        // Todo: Create data channel for the block
+        let (tx, rx) = flume::unbounded();
+        self.data_channels.lock().await.insert(block_id, tx);
+
+        let canary = Arc::new(());
+        self.active_blocks.lock().await.insert(
+            block_id,
+            ActiveBlock {
+                canary: Arc::downgrade(&canary),
+                variables: Vec::new(),
+                period: None,
+            },
+        );
+
+        Ok(LogBlock {
+            canary,
+            toc: Arc::downgrade(&self.toc),
+            uplink: self.uplink.clone(),
+            control_downlink: Arc::downgrade(&self.control_downlink),
+            active_blocks: Arc::downgrade(&self.active_blocks),
+            data_channels: Arc::downgrade(&self.data_channels),
+            runtime: tokio::runtime::Handle::current(),
+            block_id,
+            variables: Vec::new(),
+            data_channel: rx,
+        })
+    }
+
+    /// Create and start a log block from a [TypedLogBlock] struct
+    ///
+    /// The variables of the struct are added to a new log block, which is started with the given period. The type
+    /// of each variable in the TOC is checked against the type of its field: an error is returned if a variable does
+    /// not exist or if its type does not match.
+    ///
+    /// See [Log::create_block()] for the other possible errors.
+    pub async fn start_typed_block<T: TypedLogBlock>(&self, period: LogPeriod) -> Result<TypedLogStream<T>> {
+        let variables = T::variables();
+        for (name, value_type) in &variables {
+            let toc_type = self.get_type(name)?;
+            if toc_type != *value_type {
+                return Err(Error::LogError(format!(
+                    "Type mismatch for log variable {}: the TOC type is {:?}, the field type is {:?}",
+                    name, toc_type, value_type
+                )));
+            }
+        }
+
+        let mut block = self.create_block().await?;
+        for (name, _) in &variables {
+            block.add_variable(name).await?;
+        }
+
+        Ok(TypedLogStream {
+            stream: block.start(period).await?,
+            _type: PhantomData,
+        })
+    }
+
+    pub(crate) fn restorer(&self) -> LogRestorer {
+        LogRestorer {
+            uplink: self.uplink.clone(),
+            control_downlink: self.control_downlink.clone(),
+            active_blocks: self.active_blocks.clone(),
+        }
+    }
+}
+
+/// Re-creates the log blocks in the Crazyflie after a reconnection
+///
+/// The Crazyflie may have been restarted while the link was down so the log subsystem is reset and all the active
+/// blocks are created again with the same IDs. This way the existing [LogBlock] and [LogStream] objects keep working.
+pub(crate) struct LogRestorer {
+    uplink: channel::Sender<Packet>,
+    control_downlink: Arc<Mutex<channel::Receiver<Packet>>>,
+    active_blocks: Arc<Mutex<BTreeMap<u8, ActiveBlock>>>,
+}
+
+impl LogRestorer {
+    pub(crate) async fn restore(&self) -> Result<()> {
+        let control_downlink = self.control_downlink.lock().await;
+        let mut active_blocks = self.active_blocks.lock().await;
+
+        // Blocks dropped in the meantime do not need to be deleted anymore
+        active_blocks.retain(|_, block| block.canary.upgrade().is_some());
+
+        self.control_request(&control_downlink, vec![RESET]).await?;
+
+        for (block_id, block) in active_blocks.iter() {
+            self.control_request(&control_downlink, vec![CREATE_BLOCK_V2, *block_id])
+                .await?;
+
+            // Pack as many append operations as possible in each packet
+            let mut operations: Vec<u8> = Vec::new();
+            for (_, variable) in &block.variables {
+                if operations.len() + variable.len() > APPEND_MAX_PAYLOAD {
+                    let payload = [&[APPEND_BLOCK_V2, *block_id], &operations[..]].concat();
+                    self.control_request(&control_downlink, payload).await?;
+                    operations.clear();
+                }
+                operations.extend_from_slice(variable);
+            }
+            if !operations.is_empty() {
+                let payload = [&[APPEND_BLOCK_V2, *block_id], &operations[..]].concat();
+                self.control_request(&control_downlink, payload).await?;
+            }
+
+            if let Some(period) = block.period {
+                self.control_request(&control_downlink, vec![START_BLOCK, *block_id, period])
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn control_request(
+        &self,
+        control_downlink: &channel::Receiver<Packet>,
+        payload: Vec<u8>,
+    ) -> Result<()> {
+        let pattern = payload[..payload.len().min(2)].to_vec();
+
+        let pk = Packet::new(LOG_PORT, CONTROL_CHANNEL, payload);
+        self.uplink
+            .send_async(pk)
+            .await
+            .map_err(|_| Error::Disconnected)?;
+
+        let answer = control_downlink
+            .wait_packet(LOG_PORT, CONTROL_CHANNEL, &pattern)
+            .await?;
+        if answer.get_data().len() != 3 {
+            return Err(Error::ProtocolError(
+                "Malformed Log control packet".to_owned(),
+            ));
+        }
+        let error_code = answer.get_data()[2];
+        if error_code != 0 {
+            return Err(Error::LogError(format!(
+                "Error restoring log block: {}",
+                error_code
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct LogItemInfo {
+    item_type: ValueType,
+}
+
+impl TryFrom<u8> for LogItemInfo {
+    type Error = Error;
+
+    fn try_from(log_type: u8) -> Result<Self> {
+        let item_type = match log_type {
+            1 => ValueType::U8,
+            2 => ValueType::U16,
+            3 => ValueType::U32,
+            4 => ValueType::I8,
+            5 => ValueType::I16,
+            6 => ValueType::I32,
+            7 => ValueType::F32,
+            8 => ValueType::F16,
+            _ => {
+                return Err(Error::ProtocolError(format!(
+                    "Invalid log item type: {}",
+                    log_type
+                )))
+            }
+        };
+
+        Ok(LogItemInfo { item_type })
+    }
+}
+
+impl TryInto<u8> for LogItemInfo {
+    type Error = Error;
+
+    fn try_into(self) -> Result<u8> {
+        let value = match self.item_type {
+            ValueType::U8 => 1,
+            ValueType::U16 => 2,
+            ValueType::U32 => 3,
+            ValueType::I8 => 4,
+            ValueType::I16 => 5,
+            ValueType::I32 => 6,
+            ValueType::F32 => 7,
+            ValueType::F16 => 8,
+            _ => {
+                return Err(Error::LogError(format!(
+                    "Value type {:?} not handled by log",
+                    self.item_type
+                )))
+            }
+        };
+        Ok(value)
+    }
+}
+
+/// Delete a log block in the Crazyflie and forget about it
+async fn delete_block(
+    uplink: &channel::Sender<Packet>,
+    control_downlink: &channel::Receiver<Packet>,
+    active_blocks: &mut BTreeMap<u8, ActiveBlock>,
+    data_channels: &Mutex<BTreeMap<u8, flume::Sender<Packet>>>,
+    block_id: u8,
+) -> Result<()> {
+    let pk = Packet::new(LOG_PORT, CONTROL_CHANNEL, vec![DELETE_BLOCK, block_id]);
+    uplink
+        .send_async(pk)
+        .await
+        .map_err(|_| Error::Disconnected)?;
+
+    let pk = control_downlink
+        .wait_packet(LOG_PORT, CONTROL_CHANNEL, &[DELETE_BLOCK, block_id])
+        .await?;
+    let error = pk.get_data()[2];
+
+    // The block is forgotten even if the Crazyflie does not know it anymore
+    active_blocks.remove_entry(&block_id);
+    data_channels.lock().await.remove(&block_id);
+
+    if error != 0 && error != libc::ENOENT as u8 {
+        return Err(Error::LogError(format!(
+            "Protocol error when deleting block: {}",
+            error
+        )));
+    }
+
+    Ok(())
+}
+
+/// # Log block information
+///
+/// Description of a log block created in the Crazyflie, returned by [Log::active_blocks()].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogBlockInfo {
+    /// ID of the block in the Crazyflie
+    pub id: u8,
+    /// Name of the variables of the block, in order
+    pub variables: Vec<String>,
+    /// Period of the block if it is started
+    pub period: Option<LogPeriod>,
+}
+
+/// # Log Block
+///
+/// This object represent an IDLE LogBlock in the Crazyflie.
+///
+/// If the [LogBlock](struct@LogBlock) object is dropped or its associated [LogStream], the
+/// Log Block will be deleted in the Crazyflie freeing resources. The block can
+/// also be deleted explicitly with [LogBlock::delete()].
+///
+/// See the [log module documentation](crate::subsystems::log) for more context and information.
+pub struct LogBlock {
+    canary: Arc<()>,
+    toc: Weak<BTreeMap<String, (u16, LogItemInfo)>>,
+    uplink: channel::Sender<Packet>,
+    control_downlink: Weak<Mutex<channel::Receiver<Packet>>>,
+    active_blocks: Weak<Mutex<BTreeMap<u8, ActiveBlock>>>,
+    data_channels: Weak<Mutex<BTreeMap<u8, flume::Sender<Packet>>>>,
+    runtime: tokio::runtime::Handle,
+    block_id: u8,
+    variables: Vec<(String, ValueType)>,
+    data_channel: flume::Receiver<Packet>,
+}
+
+impl LogBlock {
+    /// Start log block and return a stream to read  the value
+    ///
+    /// Since a log-block cannot be modified after being started, this function
+    /// consumes the [LogBlock](struct@LogBlock) object and return a [LogStream]. The function
+    /// [LogStream::stop()] can be called on the LogStream to get back the [LogBlock](struct@LogBlock) object.
+    ///
+    /// This function can fail if there is a protocol error or an error
+    /// reported by the Crazyflie. In such case, the LogBlock object will be
+    /// dropped and the block will be deleted in the Crazyflie
+    pub async fn start(self, period: LogPeriod) -> Result<LogStream> {
+        self.send_start(period).await?;
+
+        Ok(LogStream::new(self, period))
+    }
+
+    /// Start the block in the Crazyflie, or change its period if it is already started
+    async fn send_start(&self, period: LogPeriod) -> Result<()> {
+        let control_uplink = self.control_downlink.upgrade().ok_or(Error::Disconnected)?;
+        let control_uplink = control_uplink.lock().await;
+
+        let pk = Packet::new(
+            LOG_PORT,
+            CONTROL_CHANNEL,
+            vec![START_BLOCK, self.block_id, period.0],
+        );
+        self.uplink
+            .send_async(pk)
+            .await
+            .map_err(|_| Error::Disconnected)?;
+
+        let answer = control_uplink
+            .wait_packet(LOG_PORT, CONTROL_CHANNEL, &[START_BLOCK, self.block_id])
+            .await?;
+        if answer.get_data().len() != 3 {
+            return Err(Error::ProtocolError(
+                "Malformed Log control packet".to_owned(),
+            ));
+        }
+        let error_code = answer.get_data()[2];
+        if error_code != 0 {
+            return Err(Error::LogError(format!(
+                "Error starting lock: {}",
+                error_code
+            )));
+        }
+
+        self.update_active_block(|block| block.period = Some(period.0))
+            .await;
+
+        Ok(())
+    }
+
+    /// Add a variable to the log block
+    ///
+    /// A packet will be sent to the Crazyflie to add the variable. The variable is logged in the same format as
+    /// it is stored in the Crazyflie (ie. there is no conversion done)
+    ///
+    /// This function can fail if the variable is not found in the toc or of the Crazyflie returns an error
+    /// The most common error reported by the Crazyflie would be if the log block is already too full.
+    pub async fn add_variable(&mut self, name: &str) -> Result<()> {
+        let toc = self.toc.upgrade().ok_or(Error::Disconnected)?;
+        let (variable_id, info) = toc.get(name).ok_or(Error::VariableNotFound)?;
+
+        let log_type: u8 = (*info).try_into()?;
+        let mut operation = vec![log_type];
+        operation.extend_from_slice(&variable_id.to_le_bytes());
+
+        self.append_variable(name.to_owned(), info.item_type, operation)
+            .await
+    }
+
+    /// Add a variable from the Crazyflie memory to the log block
+    ///
+    /// The value stored at `address` in the Crazyflie RAM is logged as `value_type`. This allows to log global
+    /// variables that are not declared in the log TOC, the address can be found in the map file of the firmware.
+    /// In the [LogData], the variable is named after its address formatted as `0x20001234`.
+    ///
+    /// This function fails if the type cannot be logged, 64 bits types are not supported by the log subsystem, or
+    /// if the Crazyflie returns an error.
+    pub async fn add_memory_variable(&mut self, address: u32, value_type: ValueType) -> Result<()> {
+        let log_type: u8 = LogItemInfo {
+            item_type: value_type,
+        }
+        .try_into()?;
+        // Memory variables are marked by the TOC ID 0xFFFF and followed by the address
+        let mut operation = vec![(log_type << 4) | log_type];
+        operation.extend_from_slice(&MEMORY_VARIABLE_ID.to_le_bytes());
+        operation.extend_from_slice(&address.to_le_bytes());
+
+        self.append_variable(format!("0x{:08x}", address), value_type, operation)
+            .await
+    }
+
+    async fn append_variable(&mut self, name: String, value_type: ValueType, operation: Vec<u8>) -> Result<()> {
+        // Add variable to Crazyflie
+        let control_uplink = self.control_downlink.upgrade().ok_or(Error::Disconnected)?;
+        let control_uplink = control_uplink.lock().await;
+
+        let payload = [&[APPEND_BLOCK_V2, self.block_id], &operation[..]].concat();
+        let pk = Packet::new(LOG_PORT, CONTROL_CHANNEL, payload);
+        self.uplink
+            .send_async(pk)
+            .await
+            .map_err(|_| Error::Disconnected)?;
+
+        let answer = control_uplink
+            .wait_packet(LOG_PORT, CONTROL_CHANNEL, &[APPEND_BLOCK_V2, self.block_id])
+            .await?;
+        if answer.get_data().len() != 3 {
+            return Err(Error::ProtocolError(
+                "Malformed Log control packet".to_owned(),
+            ));
+        }
+        let error_code = answer.get_data()[2];
+        if error_code != 0 {
+            return Err(Error::LogError(format!(
+                "Error appending variable to block: {}",
+                error_code
+            )));
+        }
+
+        // Add variable to local list
+        self.variables.push((name.clone(), value_type));
+        self.update_active_block(|block| block.variables.push((name, operation)))
+            .await;
+
+        Ok(())
+    }
+
+    /// Delete the log block in the Crazyflie
+    ///
+    /// This frees the block in the Crazyflie right away. Dropping the [LogBlock](struct@LogBlock) also deletes the
+    /// block, in the background, but this function allows to wait for the deletion and to get its result.
+    pub async fn delete(self) -> Result<()> {
+        let control_downlink = self.control_downlink.upgrade().ok_or(Error::Disconnected)?;
+        let active_blocks = self.active_blocks.upgrade().ok_or(Error::Disconnected)?;
+        let data_channels = self.data_channels.upgrade().ok_or(Error::Disconnected)?;
+
+        let control_downlink = control_downlink.lock().await;
+        let mut active_blocks = active_blocks.lock().await;
+        delete_block(
+            &self.uplink,
+            &control_downlink,
+            &mut active_blocks,
+            &data_channels,
+            self.block_id,
+        )
+        .await
+    }
+
+    async fn update_active_block(&self, update: impl FnOnce(&mut ActiveBlock)) {
+        if let Some(active_blocks) = self.active_blocks.upgrade()
+            && let Some(block) = active_blocks.lock().await.get_mut(&self.block_id)
+        {
+            update(block);
+        }
+    }
+}
+
+impl Drop for LogBlock {
+    fn drop(&mut self) {
+        let uplink = self.uplink.clone();
+        let control_downlink = self.control_downlink.clone();
+        let active_blocks = self.active_blocks.clone();
+        let data_channels = self.data_channels.clone();
+        let canary = Arc::downgrade(&self.canary);
+        let block_id = self.block_id;
+
+        self.runtime.spawn(async move {
+            let (Some(control_downlink), Some(active_blocks), Some(data_channels)) = (
+                control_downlink.upgrade(),
+                active_blocks.upgrade(),
+                data_channels.upgrade(),
+            ) else {
+                return;
+            };
+
+            let control_downlink = control_downlink.lock().await;
+            let mut active_blocks = active_blocks.lock().await;
+            // The block might have been deleted already and its ID re-used
+            if active_blocks
+                .get(&block_id)
+                .is_some_and(|block| Weak::ptr_eq(&block.canary, &canary))
+            {
+                // On failure, the block is deleted by the cleanup done when creating the next block
+                let _ = delete_block(
+                    &uplink,
+                    &control_downlink,
+                    &mut active_blocks,
+                    &data_channels,
+                    block_id,
+                )
+                .await;
+            }
+        });
+    }
+}
+
+/// # Log Steam
+///
+/// This object represents a started log block that is currently returning data
+/// at regular intervals.
+///
+/// Dropping this object or the associated [LogBlock](struct@LogBlock) will delete the log block
+/// in the Crazyflie.
+///
+/// The samples can be read one by one with [LogStream::next()]. The log stream also implements [Stream] so that the
+/// [StreamExt] combinators can be used, the stream ends when the Crazyflie is disconnected. As the inherent
+/// [LogStream::next()] takes precedence, [StreamExt::next()] has to be called as `StreamExt::next(&mut stream)`.
+///
+/// Samples lost on the way, for example because of radio packet loss, are detected by comparing the Crazyflie
+/// timestamp of consecutive samples with the log period. They are reported in [LogData::missed_samples] and counted
+/// in [LogStream::statistics()].
+///
+/// See the [log module documentation](crate::subsystems::log) for more context and information.
+pub struct LogStream {
+    log_block: LogBlock,
+    period: LogPeriod,
+    data_stream: channel::r#async::RecvStream<'static, Packet>,
+    gap_detector: std::sync::Mutex<GapDetector>,
+}
+
+/// # Log stream statistics
+///
+/// Counters of the samples received and missed by a [LogStream], see [LogStream::statistics()].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LogStreamStatistics {
+    /// Number of samples received
+    pub received: u64,
+    /// Number of samples missed, inferred from the timestamps of the received samples
+    pub missed: u64,
+    /// Number of gaps in the stream, a gap being one or more consecutive missed samples
+    pub gaps: u64,
+}
+
+/// Infers the missed samples from the timestamp of consecutive samples
+#[derive(Default)]
+struct GapDetector {
+    last_timestamp: Option<u32>,
+    statistics: LogStreamStatistics,
+}
+
+impl GapDetector {
+    /// Account for a received sample, returns the number of samples missed since the previous one
+    fn sample(&mut self, timestamp: u32, period: LogPeriod) -> u32 {
+        let period = period.as_millis() as u32;
+        let missed = match self.last_timestamp {
+            Some(last) => {
+                let elapsed = timestamp.wrapping_sub(last) & TIMESTAMP_MASK;
+                ((elapsed + period / 2) / period).saturating_sub(1)
+            }
+            None => 0,
+        };
+
+        self.last_timestamp = Some(timestamp);
+        self.statistics.received += 1;
+        if missed > 0 {
+            self.statistics.missed += missed as u64;
+            self.statistics.gaps += 1;
+        }
+        missed
+    }
+}
+
+impl LogStream {
+    fn new(log_block: LogBlock, period: LogPeriod) -> Self {
+        let data_stream = log_block.data_channel.clone().into_stream();
+        Self {
+            log_block,
+            period,
+            data_stream,
+            gap_detector: Default::default(),
+        }
+    }
+
+    /// Period of the log block
+    pub fn period(&self) -> LogPeriod {
+        self.period
+    }
+
+    /// Counters of the samples received and missed since the log block was started
+    pub fn statistics(&self) -> LogStreamStatistics {
+        self.gap_detector.lock().unwrap().statistics
+    }
+
+    /// Change the period of the log block
+    ///
+    /// The block keeps streaming, only at the new period: unlike stopping and starting the block again, the stream
+    /// does not need to be re-created. The missed samples detection starts again from the next sample.
+    pub async fn set_period(&mut self, period: LogPeriod) -> Result<()> {
+        self.log_block.send_start(period).await?;
+
+        self.period = period;
+        self.gap_detector.lock().unwrap().last_timestamp = None;
+        Ok(())
+    }
+
+    /// Stops the log block from streaming
+    ///
+    /// This method consumes the stream and returns back the log block object so that it can be started again later
+    /// with a different period.
+    ///
+    /// This function can only fail on unexpected protocol error. If it does, the log block is dropped and deleted in
+    /// the Crazyflie.
+    pub async fn stop(self) -> Result<LogBlock> {
+        let control_uplink = self
+            .log_block
+            .control_downlink
+            .upgrade()
+            .ok_or(Error::Disconnected)?;
+        let control_uplink = control_uplink.lock().await;
+
+        let pk = Packet::new(
+            LOG_PORT,
+            CONTROL_CHANNEL,
+            vec![STOP_BLOCK, self.log_block.block_id],
+        );
+        self.log_block
+            .uplink
+            .send_async(pk)
+            .await
+            .map_err(|_| Error::Disconnected)?;
+
+        let answer = control_uplink
+            .wait_packet(
+                LOG_PORT,
+                CONTROL_CHANNEL,
+                &[STOP_BLOCK, self.log_block.block_id],
+            )
+            .await?;
+        if answer.get_data().len() != 3 {
+            return Err(Error::ProtocolError(
+                "Malformed Log control packet".to_owned(),
+            ));
+        }
+        let error_code = answer.get_data()[2];
+        if error_code != 0 {
+            return Err(Error::LogError(format!(
+                "Error starting lock: {}",
+                error_code
+            )));
+        }
+
+        self.log_block
+            .update_active_block(|block| block.period = None)
+            .await;
+
+        Ok(self.log_block)
+    }
+
+    /// Get the next log data from the log block stream
+    ///
+    /// This function will wait for the data and only return a value when the
+    /// next data is available.
+    ///
+    /// This function will return an error if the Crazyflie gets disconnected.
+    pub async fn next(&self) -> Result<LogData> {
+        let packet = self.next_packet().await?;
+
+        self.decode_packet(&packet.get_data()[1..])
+    }
+
+    async fn next_packet(&self) -> Result<Packet> {
+        self.log_block
+            .data_channel
+            .recv_async()
+            .await
+            .map_err(|_| Error::Disconnected)
+    }
+
+    /// Account for a received sample, returns the number of samples missed since the previous one
+    fn track_sample(&self, timestamp: u32) -> u32 {
+        self.gap_detector.lock().unwrap().sample(timestamp, self.period)
+    }
+
+    fn decode_packet(&self, data: &[u8]) -> Result<LogData> {
+        let timestamp = decode_timestamp(data);
+        let missed_samples = self.track_sample(timestamp);
+
+        let mut index = 3;
+        let mut log_data = HashMap::new();
+        for (name, value_type) in &self.log_block.variables {
+            let byte_length = value_type.byte_length();
+            let bytes = data
+                .get(index..(index + byte_length))
+                .ok_or_else(|| Error::ProtocolError("Log data packet too short".to_owned()))?;
+            log_data.insert(name.clone(), Value::from_le_bytes(bytes, *value_type)?);
+            index += byte_length;
+        }
+
+        Ok(LogData {
+            timestamp,
+            data: log_data,
+            missed_samples,
+        })
+    }
+}
+
+impl Stream for LogStream {
+    type Item = Result<LogData>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.data_stream
+            .poll_next_unpin(cx)
+            .map(|packet| packet.map(|packet| self.decode_packet(&packet.get_data()[1..])))
+    }
+}
+
+fn decode_timestamp(data: &[u8]) -> u32 {
+    let mut timestamp = data[0..=2].to_vec();
+    timestamp.push(0);
+    // The timestamp is 3 bytes long, padded to 4 for u32 conversion
+    u32::from_le_bytes(timestamp.try_into().unwrap())
+}
+
+/// # Typed log block
+///
+/// Struct of log variables, decoded directly from the log data packets. This trait should be implemented with the
+/// [LogBlock](derive@LogBlock) derive macro: each field is annotated with the name of the log variable, the field
+/// type must be the Rust type matching the type of the variable in the TOC. A field of type `u32` can be annotated
+/// with `#[log(timestamp)]` to get the Crazyflie timestamp of the sample.
+///
+/// ```no_run
+/// # use crazyflie_lib::{Crazyflie, Error, subsystems::log::{LogBlock, LogPeriod}};
+/// # async fn example(cf: Crazyflie) -> Result<(), Error> {
+/// #[derive(LogBlock)]
+/// struct Attitude {
+///     #[log(timestamp)]
+///     timestamp: u32,
+///     #[log("stateEstimate.roll")]
+///     roll: f32,
+///     #[log("stateEstimate.pitch")]
+///     pitch: f32,
+/// }
+///
+/// let stream = cf.log.start_typed_block::<Attitude>(LogPeriod::from_millis(100)?).await?;
+/// while let Ok(attitude) = stream.next().await {
+///     println!("{}: roll {}, pitch {}", attitude.timestamp, attitude.roll, attitude.pitch);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub trait TypedLogBlock: Sized {
+    /// Name and type of the log variables, in the order they are added to the log block
+    fn variables() -> Vec<(&'static str, ValueType)>;
+
+    /// Decode a sample from the Crazyflie timestamp and the log block data
+    fn decode(timestamp: u32, data: &[u8]) -> Result<Self>;
+}
+
+/// Derive macro implementing [TypedLogBlock], see the trait documentation
+pub use crazyflie_lib_derive::LogBlock;
+
+/// Decode the next field of a typed log block, used by the [LogBlock](derive@LogBlock) derive macro
+#[doc(hidden)]
+pub fn decode_log_field<T: ValuePrimitive>(data: &[u8], index: &mut usize) -> Result<T> {
+    let length = T::VALUE_TYPE.byte_length();
+    let bytes = data
+        .get(*index..*index + length)
+        .ok_or_else(|| Error::ProtocolError("Log data packet too short".to_owned()))?;
+    *index += length;
+
+    Value::from_le_bytes(bytes, T::VALUE_TYPE)?.try_into()
+}
+
+/// # Typed log stream
+///
+/// Started log block yielding the samples as a [TypedLogBlock] struct. Created with [Log::start_typed_block()].
+///
+/// As for the [LogStream], dropping this object deletes the log block in the Crazyflie.
+pub struct TypedLogStream<T> {
+    stream: LogStream,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T: TypedLogBlock> TypedLogStream<T> {
+    /// Get the next sample from the log block stream
+    ///
+    /// This function will wait for the data and only return a value when the
+    /// next data is available.
+    ///
+    /// This function will return an error if the Crazyflie gets disconnected.
+    pub async fn next(&self) -> Result<T> {
+        let packet = self.stream.next_packet().await?;
+        let data = &packet.get_data()[1..];
+        let timestamp = decode_timestamp(data);
+        self.stream.track_sample(timestamp);
+
+        T::decode(timestamp, &data[3..])
+    }
+
+    /// Stops the log block from streaming
+    ///
+    /// See [LogStream::stop()].
+    pub async fn stop(self) -> Result<LogBlock> {
+        self.stream.stop().await
+    }
+}
+
+/// # Log data sample
+///
+/// This object represents a data sample coming from a started log block. It
+/// provides the Crazyflie timestamp in milliseconds when the data was sampled
+/// and a hash-table of variable name and value.
+///
+/// See the [log module documentation](crate::subsystems::log) for more context and information.
+#[derive(Debug)]
+pub struct LogData {
+    /// Timestamp in milliseconds of when the data sample was taken
+    pub timestamp: u32,
+    /// HashMap of the name of the variable vs sampled value
+    pub data: HashMap<String, Value>,
+    /// Number of samples missed between the previous sample and this one
+    ///
+    /// The missed samples are inferred from the timestamps and the log period, a non-zero value indicates that data
+    /// has been lost on the way, for example because of radio packet loss.
+    pub missed_samples: u32,
+}
+
+/// # Log block period
+///
+/// This object represent a valid log period. It implements the [`TryFrom<Duration>`]
+/// trait so it can be constructed from a [Duration] object. a [LogPeriod::from_millis()]
+/// function is provided for convenience.
+///
+/// A valid period for a Log block is between 10ms and 2550ms.
+///
+/// See the [log module documentation](crate::subsystems::log) for more context and information.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogPeriod(u8);
+
+impl LogPeriod {
+    /// Create a LogPeriod object from milliseconds
+    ///
+    /// Return an error if the millis is not valid.
+    /// A valid period for a Log block is between 10ms and 2550ms.
+    pub fn from_millis(millis: u64) -> Result<Self> {
+        Duration::from_millis(millis).try_into()
+    }
+
+    /// Period in milliseconds
+    pub fn as_millis(&self) -> u64 {
+        self.0 as u64 * 10
+    }
+}
+
+impl TryFrom<Duration> for LogPeriod {
+    type Error = Error;
+
+    fn try_from(value: Duration) -> Result<Self> {
+        let period_ms = value.as_millis();
+        let period_arg = period_ms / 10;
+        if period_arg == 0 || period_arg > 255 {
+            return Err(Error::LogError(
+                "Invalid log period, should be between 10ms and 2550ms".to_owned(),
+            ));
+        }
+        Ok(LogPeriod(period_arg as u8))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_toc_cache_format_stability() {
+        // This test pins the serialization format of LogItemInfo.
+        // If it fails, the TOC cache format has changed. Bump TOC_CACHE_VERSION
+        // and update this test.
+        let info = LogItemInfo { item_type: ValueType::U8 };
+        let json = serde_json::to_string(&info).unwrap();
+        assert_eq!(json, r#"{"item_type":"U8"}"#);
+    }
+}
@@ -0,0 +1,449 @@
+use super::{LogData, LogStream};
+use crate::{Error, Result, Value, ValueType};
+use flume as channel;
+use futures::future::select_all;
+use futures::FutureExt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+/// Format of the file written by a [LogRecorder]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogRecordFormat {
+    /// Comma separated values, one row per sample
+    ///
+    /// The columns are `host_time`, the time in seconds since the Unix epoch at which the sample has been received,
+    /// `block`, the name of the log stream quoted if needed, `timestamp`, the Crazyflie timestamp in milliseconds, followed by one
+    /// column per log variable. The cells of the variables that are not part of the sample block are left empty.
+    Csv,
+    /// [MCAP](https://mcap.dev) file with one topic per log stream
+    ///
+    /// The messages are JSON encoded and described by a JSON schema. They contain the Crazyflie timestamp in
+    /// milliseconds and the variables nested by group. The log time of the messages is the time at which the sample
+    /// has been received.
+    Mcap,
+}
+
+/// # Log recorder
+///
+/// Records the samples of one or more [LogStream] to a file, see [LogRecordFormat] for the supported formats. The
+/// recording runs in a background task until [LogRecorder::stop()] is called or until the Crazyflie is disconnected.
+/// The file is written from a blocking thread so that a slow disk does not delay the tokio tasks, the samples are
+/// queued in memory while they wait to be written.
+///
+/// ```no_run
+/// # use crazyflie_lib::{Crazyflie, Error};
+/// # use crazyflie_lib::subsystems::log::{LogPeriod, LogRecorder, LogRecordFormat};
+/// # async fn example(cf: Crazyflie) -> Result<(), Error> {
+/// let mut block = cf.log.create_block().await?;
+/// block.add_variable("stateEstimate.roll").await?;
+/// block.add_variable("stateEstimate.pitch").await?;
+/// let stream = block.start(LogPeriod::from_millis(10)?).await?;
+///
+/// let recorder = LogRecorder::start(LogRecordFormat::Mcap, "flight.mcap", [("attitude", stream)])?;
+/// // Fly ...
+/// let streams = recorder.stop().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct LogRecorder {
+    stop: oneshot::Sender<()>,
+    task: JoinHandle<(Vec<LogStream>, Result<()>)>,
+}
+
+impl LogRecorder {
+    /// Create the file and start recording the streams
+    ///
+    /// Each stream is named, the name is used as block name in CSV files and as topic in MCAP files. The file is
+    /// overwritten if it exists.
+    pub fn start<S: Into<String>>(
+        format: LogRecordFormat,
+        path: impl AsRef<Path>,
+        streams: impl IntoIterator<Item = (S, LogStream)>,
+    ) -> Result<Self> {
+        let (names, streams): (Vec<String>, Vec<LogStream>) =
+            streams.into_iter().map(|(name, stream)| (name.into(), stream)).unzip();
+        if streams.is_empty() {
+            return Err(Error::InvalidArgument("No log stream to record".to_owned()));
+        }
+
+        let blocks: Vec<_> = names
+            .into_iter()
+            .zip(&streams)
+            .map(|(name, stream)| RecordedBlock {
+                name,
+                variables: stream.log_block.variables.clone(),
+            })
+            .collect();
+
+        let file = BufWriter::new(File::create(path)?);
+        let writer = match format {
+            LogRecordFormat::Csv => RecordWriter::Csv(CsvWriter::new(file, blocks)?),
+            LogRecordFormat::Mcap => RecordWriter::Mcap(McapWriter::new(file, blocks)?),
+        };
+
+        let (stop, stop_receiver) = oneshot::channel();
+        let task = tokio::spawn(record(streams, writer, stop_receiver));
+
+        Ok(Self { stop, task })
+    }
+
+    /// Stop recording and close the file
+    ///
+    /// Returns the recorded streams, in the order they have been passed to [LogRecorder::start()]. An error is
+    /// returned if writing the file has failed or if a log stream has returned an error other than a disconnection.
+    pub async fn stop(self) -> Result<Vec<LogStream>> {
+        // The task might already have ended
+        let _ = self.stop.send(());
+        let (streams, result) = self
+            .task
+            .await
+            .map_err(|e| Error::SystemError(format!("Log recorder task failed: {}", e)))?;
+        result.map(|_| streams)
+    }
+}
+
+async fn record(
+    streams: Vec<LogStream>,
+    writer: RecordWriter,
+    mut stop: oneshot::Receiver<()>,
+) -> (Vec<LogStream>, Result<()>) {
+    let (samples, samples_receiver) = channel::unbounded();
+    let writer = tokio::task::spawn_blocking(move || write_samples(writer, samples_receiver));
+    let mut result = Ok(());
+
+    loop {
+        let next = select_all(streams.iter().map(|stream| stream.next().boxed()));
+        let (data, block) = tokio::select! {
+            _ = &mut stop => break,
+            (data, block, _) = next => (data, block),
+        };
+
+        match data {
+            Ok(data) => {
+                // Fails if the writer has stopped because of an error, which is returned below
+                if samples.send((block, host_time(), data)).is_err() {
+                    break;
+                }
+            }
+            Err(Error::Disconnected) => break,
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        }
+    }
+
+    // The writer finishes the file once all the queued samples are written
+    drop(samples);
+    let written = match writer.await {
+        Ok(written) => written.map_err(Error::from),
+        Err(e) => Err(Error::SystemError(format!("Log recorder writer failed: {}", e))),
+    };
+    (streams, result.and(written))
+}
+
+/// Write the samples to the file until the channel is closed, then finish the file
+fn write_samples(mut writer: RecordWriter, samples: channel::Receiver<(usize, u64, LogData)>) -> io::Result<()> {
+    let mut result = Ok(());
+    for (block, host_time, data) in samples.iter() {
+        if let Err(e) = writer.write(block, host_time, &data) {
+            result = Err(e);
+            break;
+        }
+    }
+    // Stops the recording task on a write error
+    drop(samples);
+    // Finished even after a write error, the first error is returned
+    result.and(writer.finish())
+}
+
+/// Host time in nanoseconds since the Unix epoch
+fn host_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+struct RecordedBlock {
+    name: String,
+    variables: Vec<(String, ValueType)>,
+}
+
+enum RecordWriter {
+    Csv(CsvWriter),
+    Mcap(McapWriter),
+}
+
+impl RecordWriter {
+    fn write(&mut self, block: usize, host_time: u64, data: &LogData) -> io::Result<()> {
+        match self {
+            RecordWriter::Csv(writer) => writer.write(block, host_time, data),
+            RecordWriter::Mcap(writer) => writer.write(block, host_time, data),
+        }
+    }
+
+    fn finish(self) -> io::Result<()> {
+        match self {
+            RecordWriter::Csv(mut writer) => writer.file.flush(),
+            RecordWriter::Mcap(writer) => writer.finish(),
+        }
+    }
+}
+
+/// Format a value as a number, non-finite floats are returned as None
+fn format_value(value: &Value) -> Option<String> {
+    match value {
+        Value::U8(v) => Some(v.to_string()),
+        Value::U16(v) => Some(v.to_string()),
+        Value::U32(v) => Some(v.to_string()),
+        Value::U64(v) => Some(v.to_string()),
+        Value::I8(v) => Some(v.to_string()),
+        Value::I16(v) => Some(v.to_string()),
+        Value::I32(v) => Some(v.to_string()),
+        Value::I64(v) => Some(v.to_string()),
+        Value::F16(v) if v.is_finite() => Some(v.to_string()),
+        Value::F32(v) if v.is_finite() => Some(v.to_string()),
+        Value::F64(v) if v.is_finite() => Some(v.to_string()),
+        _ => None,
+    }
+}
+
+/// Format a CSV field, quoted if needed as described in RFC 4180
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+struct CsvWriter {
+    file: BufWriter<File>,
+    blocks: Vec<RecordedBlock>,
+    columns: Vec<String>,
+}
+
+impl CsvWriter {
+    fn new(mut file: BufWriter<File>, blocks: Vec<RecordedBlock>) -> io::Result<Self> {
+        let mut columns: Vec<String> = Vec::new();
+        for (name, _) in blocks.iter().flat_map(|block| &block.variables) {
+            if !columns.contains(name) {
+                columns.push(name.clone());
+            }
+        }
+
+        write!(file, "host_time,block,timestamp")?;
+        for column in &columns {
+            write!(file, ",{}", csv_field(column))?;
+        }
+        writeln!(file)?;
+
+        Ok(Self {
+            file,
+            blocks,
+            columns,
+        })
+    }
+
+    fn write(&mut self, block: usize, host_time: u64, data: &LogData) -> io::Result<()> {
+        write!(
+            self.file,
+            "{}.{:06},{},{}",
+            host_time / 1_000_000_000,
+            host_time % 1_000_000_000 / 1_000,
+            csv_field(&self.blocks[block].name),
+            data.timestamp
+        )?;
+        for column in &self.columns {
+            let value = data.data.get(column).and_then(format_value);
+            write!(self.file, ",{}", value.unwrap_or_default())?;
+        }
+        writeln!(self.file)
+    }
+}
+
+const MCAP_MAGIC: &[u8] = b"\x89MCAP0\r\n";
+
+const MCAP_HEADER: u8 = 0x01;
+const MCAP_FOOTER: u8 = 0x02;
+const MCAP_SCHEMA: u8 = 0x03;
+const MCAP_CHANNEL: u8 = 0x04;
+const MCAP_MESSAGE: u8 = 0x05;
+const MCAP_DATA_END: u8 = 0x0F;
+
+/// Minimal MCAP writer: the file only has a data section, without chunks or summary
+struct McapWriter {
+    file: BufWriter<File>,
+    channels: Vec<McapChannel>,
+}
+
+struct McapChannel {
+    /// Groups of variables: group name and list of (field name, variable name)
+    groups: Vec<(String, Vec<(String, String)>)>,
+    sequence: u32,
+}
+
+/// Append a length-prefixed string to a MCAP record
+fn mcap_string(record: &mut Vec<u8>, string: &str) {
+    record.extend_from_slice(&(string.len() as u32).to_le_bytes());
+    record.extend_from_slice(string.as_bytes());
+}
+
+/// JSON encoding of a string, with quotes
+fn json_string(string: &str) -> String {
+    serde_json::Value::from(string).to_string()
+}
+
+/// Split the variables of a block by group, in order of appearance
+fn group_variables(variables: &[(String, ValueType)]) -> Vec<(String, Vec<(String, ValueType)>)> {
+    let mut groups: Vec<(String, Vec<(String, ValueType)>)> = Vec::new();
+    for (name, value_type) in variables {
+        let (group, field) = name.split_once('.').unwrap_or(("", name));
+        let field = (field.to_owned(), *value_type);
+        match groups.iter_mut().find(|(name, _)| name == group) {
+            Some((_, fields)) => fields.push(field),
+            None => groups.push((group.to_owned(), vec![field])),
+        }
+    }
+    groups
+}
+
+fn json_schema(block: &RecordedBlock) -> serde_json::Value {
+    let property = |value_type: &ValueType| match value_type {
+        ValueType::F16 | ValueType::F32 | ValueType::F64 => serde_json::json!({ "type": "number" }),
+        _ => serde_json::json!({ "type": "integer" }),
+    };
+
+    let mut properties = serde_json::Map::new();
+    properties.insert("timestamp".to_owned(), serde_json::json!({ "type": "integer" }));
+    for (group, fields) in group_variables(&block.variables) {
+        let fields: serde_json::Map<_, _> = fields
+            .iter()
+            .map(|(field, value_type)| (field.clone(), property(value_type)))
+            .collect();
+        if group.is_empty() {
+            properties.extend(fields);
+        } else {
+            properties.insert(group, serde_json::json!({ "type": "object", "properties": fields }));
+        }
+    }
+
+    serde_json::json!({
+        "title": block.name,
+        "type": "object",
+        "properties": properties,
+    })
+}
+
+impl McapWriter {
+    fn new(mut file: BufWriter<File>, blocks: Vec<RecordedBlock>) -> io::Result<Self> {
+        file.write_all(MCAP_MAGIC)?;
+
+        let mut writer = Self {
+            file,
+            channels: Vec::new(),
+        };
+
+        let mut header = Vec::new();
+        mcap_string(&mut header, "");
+        mcap_string(&mut header, &format!("crazyflie-lib {}", env!("CARGO_PKG_VERSION")));
+        writer.write_record(MCAP_HEADER, &header)?;
+
+        for (index, block) in blocks.iter().enumerate() {
+            // Schema ID 0 is reserved
+            let id = index as u16 + 1;
+
+            let mut schema = Vec::new();
+            schema.extend_from_slice(&id.to_le_bytes());
+            mcap_string(&mut schema, &block.name);
+            mcap_string(&mut schema, "jsonschema");
+            mcap_string(&mut schema, &json_schema(block).to_string());
+            writer.write_record(MCAP_SCHEMA, &schema)?;
+
+            let mut channel = Vec::new();
+            channel.extend_from_slice(&id.to_le_bytes());
+            channel.extend_from_slice(&id.to_le_bytes());
+            mcap_string(&mut channel, &block.name);
+            mcap_string(&mut channel, "json");
+            // Empty metadata map
+            channel.extend_from_slice(&0u32.to_le_bytes());
+            writer.write_record(MCAP_CHANNEL, &channel)?;
+
+            let groups = group_variables(&block.variables)
+                .into_iter()
+                .map(|(group, fields)| {
+                    let fields = fields
+                        .into_iter()
+                        .map(|(field, _)| {
+                            let name = if group.is_empty() {
+                                field.clone()
+                            } else {
+                                format!("{}.{}", group, field)
+                            };
+                            (field, name)
+                        })
+                        .collect();
+                    (group, fields)
+                })
+                .collect();
+            writer.channels.push(McapChannel { groups, sequence: 0 });
+        }
+
+        Ok(writer)
+    }
+
+    fn write_record(&mut self, opcode: u8, content: &[u8]) -> io::Result<()> {
+        self.file.write_all(&[opcode])?;
+        self.file.write_all(&(content.len() as u64).to_le_bytes())?;
+        self.file.write_all(content)
+    }
+
+    fn write(&mut self, block: usize, host_time: u64, data: &LogData) -> io::Result<()> {
+        let channel = &mut self.channels[block];
+
+        let field = |(field, name): &(String, String)| {
+            let value = data.data.get(name).and_then(format_value);
+            format!("{}:{}", json_string(field), value.as_deref().unwrap_or("null"))
+        };
+        let mut members = vec![format!("\"timestamp\":{}", data.timestamp)];
+        for (group, fields) in &channel.groups {
+            if group.is_empty() {
+                members.extend(fields.iter().map(field));
+            } else {
+                let fields: Vec<_> = fields.iter().map(field).collect();
+                members.push(format!("{}:{{{}}}", json_string(group), fields.join(",")));
+            }
+        }
+        let message_data = format!("{{{}}}", members.join(","));
+
+        let mut message = Vec::new();
+        message.extend_from_slice(&(block as u16 + 1).to_le_bytes());
+        message.extend_from_slice(&channel.sequence.to_le_bytes());
+        message.extend_from_slice(&host_time.to_le_bytes());
+        message.extend_from_slice(&host_time.to_le_bytes());
+        message.extend_from_slice(message_data.as_bytes());
+        channel.sequence = channel.sequence.wrapping_add(1);
+
+        self.write_record(MCAP_MESSAGE, &message)
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        // A CRC of 0 means that the CRC is not available
+        self.write_record(MCAP_DATA_END, &0u32.to_le_bytes())?;
+
+        // No summary section
+        let mut footer = Vec::new();
+        footer.extend_from_slice(&0u64.to_le_bytes());
+        footer.extend_from_slice(&0u64.to_le_bytes());
+        footer.extend_from_slice(&0u32.to_le_bytes());
+        self.write_record(MCAP_FOOTER, &footer)?;
+
+        self.file.write_all(MCAP_MAGIC)?;
+        self.file.flush()
+    }
+}
@@ -0,0 +1,271 @@
+use super::{Log, LogData, LogPeriod, LogStream, TIMESTAMP_MASK};
+use crate::{Error, Result, ValueType};
+use futures::FutureExt;
+use futures::future::select_all;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Maximum size of the variables of one log block, limited by the size of a CRTP packet
+pub(crate) const LOG_BLOCK_MAX_PAYLOAD: usize = 26;
+
+/// Age, in periods, after which an incomplete sample is dropped
+const MAX_PENDING_PERIODS: u32 = 4;
+
+/// Number of periods without a complete sample after which [LogSession::next()] gives up
+const STALL_PERIODS: u32 = 10;
+
+/// Minimum time without a complete sample after which [LogSession::next()] gives up
+const MIN_STALL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// # Log session
+///
+/// Log of any number of variables at the same period. The variables are packed into as many log blocks as needed and
+/// the samples of all the blocks are merged, using the Crazyflie timestamp, into one [LogData] per period.
+///
+/// The log blocks are started one after the other so their samples are not taken at exactly the same time: the
+/// samples taken within half a period are merged together, the timestamp of the merged sample is the one of the
+/// earliest block sample.
+///
+/// Incomplete samples, when a block sample has been lost, are skipped and counted in the
+/// [missed samples](LogData::missed_samples) of the next merged sample. An incomplete sample is dropped once a sample
+/// more than 4 periods newer has been received, so a block that stops delivering does not make the session
+/// accumulate samples. [LogSession::next()] returns an error if no sample can be completed for 10 periods, or at
+/// least one second.
+///
+/// Created with [Log::start_session()]. As for the [LogStream], the log blocks are deleted in the Crazyflie once this
+/// object is dropped.
+pub struct LogSession {
+    streams: Vec<LogStream>,
+    merger: SampleMerger,
+    stall_timeout: Duration,
+}
+
+/// Merges the block samples by timestamp
+struct SampleMerger {
+    block_count: usize,
+    /// Maximum timestamp distance of the block samples of a merged sample, in milliseconds
+    tolerance: u32,
+    /// Maximum age of an incomplete sample, in milliseconds
+    max_age: u32,
+    pending: Vec<PendingSample>,
+    /// Incomplete samples dropped since the last merged sample
+    dropped: u32,
+}
+
+/// Sample being merged, with the data received so far from each block
+struct PendingSample {
+    timestamp: u32,
+    blocks: Vec<Option<LogData>>,
+}
+
+impl Log {
+    /// Create and start a log session
+    ///
+    /// The variables are split in as many log blocks as needed, all started with the same period. An error is
+    /// returned if a variable does not exist in the TOC or if the blocks cannot be created, in which case the blocks
+    /// already created are deleted.
+    ///
+    /// See [LogSession] for more information.
+    pub async fn start_session<S: AsRef<str>>(
+        &self,
+        variables: &[S],
+        period: LogPeriod,
+    ) -> Result<LogSession> {
+        if variables.is_empty() {
+            return Err(Error::InvalidArgument(
+                "Log session without variables".to_owned(),
+            ));
+        }
+
+        let variables = variables
+            .iter()
+            .map(|name| Ok((name.as_ref(), self.get_type(name.as_ref())?)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut streams = Vec::new();
+        for block_variables in pack_variables(&variables) {
+            let mut block = self.create_block().await?;
+            for name in block_variables {
+                block.add_variable(name).await?;
+            }
+            streams.push(block.start(period).await?);
+        }
+
+        let period_ms = period.as_millis() as u32;
+        Ok(LogSession {
+            merger: SampleMerger::new(streams.len(), period_ms),
+            streams,
+            stall_timeout: Duration::from_millis((STALL_PERIODS * period_ms) as u64).max(MIN_STALL_TIMEOUT),
+        })
+    }
+}
+
+/// Split the variables in log blocks, in order
+fn pack_variables<'a>(variables: &[(&'a str, ValueType)]) -> Vec<Vec<&'a str>> {
+    let mut blocks: Vec<Vec<&str>> = Vec::new();
+    let mut block_size = 0;
+
+    for (name, value_type) in variables {
+        let size = value_type.byte_length();
+        match blocks.last_mut() {
+            Some(block) if block_size + size <= LOG_BLOCK_MAX_PAYLOAD => block.push(name),
+            _ => {
+                blocks.push(vec![name]);
+                block_size = 0;
+            }
+        }
+        block_size += size;
+    }
+
+    blocks
+}
+
+fn timestamp_distance(a: u32, b: u32) -> u32 {
+    let distance = a.wrapping_sub(b) & TIMESTAMP_MASK;
+    distance.min(TIMESTAMP_MASK + 1 - distance)
+}
+
+impl LogSession {
+    /// Number of log blocks used by the session
+    pub fn block_count(&self) -> usize {
+        self.streams.len()
+    }
+
+    /// Get the next merged sample
+    ///
+    /// This function will wait for the data of all the blocks and only return a value when the next sample is
+    /// complete.
+    ///
+    /// This function will return [Error::Timeout] if no sample can be completed for 10 periods, or at least one
+    /// second, for example because a block has stopped delivering data. The session can still be used after a
+    /// timeout. It will return an error if the Crazyflie gets disconnected.
+    pub async fn next(&mut self) -> Result<LogData> {
+        let merge = async {
+            loop {
+                let (data, index, _) =
+                    select_all(self.streams.iter().map(|stream| stream.next().boxed())).await;
+
+                if let Some(sample) = self.merger.merge(index, data?) {
+                    return Ok(sample);
+                }
+            }
+        };
+
+        tokio::time::timeout(self.stall_timeout, merge)
+            .await
+            .map_err(|_| Error::Timeout)?
+    }
+
+    /// Stops all the log blocks of the session
+    ///
+    /// The blocks are deleted in the Crazyflie when this function returns.
+    pub async fn stop(self) -> Result<()> {
+        for stream in self.streams {
+            stream.stop().await?.delete().await?;
+        }
+        Ok(())
+    }
+
+}
+
+impl SampleMerger {
+    fn new(block_count: usize, period_ms: u32) -> Self {
+        Self {
+            block_count,
+            tolerance: period_ms / 2,
+            max_age: MAX_PENDING_PERIODS * period_ms,
+            pending: Vec::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Add a block sample, returns the merged sample if it is complete
+    fn merge(&mut self, block: usize, data: LogData) -> Option<LogData> {
+        // Samples too old to be completed, for example because a block has stopped delivering
+        let (timestamp, max_age) = (data.timestamp, self.max_age);
+        let before = self.pending.len();
+        self.pending
+            .retain(|pending| timestamp_distance(timestamp, pending.timestamp) <= max_age);
+        self.dropped += (before - self.pending.len()) as u32;
+
+        let position = self.pending.iter().position(|pending| {
+            pending.blocks[block].is_none()
+                && timestamp_distance(pending.timestamp, data.timestamp) <= self.tolerance
+        });
+
+        let position = match position {
+            Some(position) => position,
+            None => {
+                self.pending.push(PendingSample {
+                    timestamp: data.timestamp,
+                    blocks: (0..self.block_count).map(|_| None).collect(),
+                });
+                self.pending.len() - 1
+            }
+        };
+
+        let pending = &mut self.pending[position];
+        let earlier = pending.timestamp.wrapping_sub(data.timestamp) & TIMESTAMP_MASK;
+        if earlier != 0 && earlier <= self.tolerance {
+            pending.timestamp = data.timestamp;
+        }
+        pending.blocks[block] = Some(data);
+
+        if pending.blocks.iter().all(Option::is_some) {
+            // Older incomplete samples will never be completed
+            let pending: Vec<_> = self.pending.drain(..=position).collect();
+            let dropped = self.dropped + pending.len() as u32 - 1;
+            self.dropped = 0;
+            let sample = pending.into_iter().last().unwrap();
+
+            let mut data = HashMap::new();
+            // A lost block sample is reported both by its block and as a dropped incomplete sample
+            let mut missed_samples = dropped;
+            for block in sample.blocks.into_iter().flatten() {
+                data.extend(block.data);
+                missed_samples = missed_samples.max(block.missed_samples);
+            }
+            Some(LogData {
+                timestamp: sample.timestamp,
+                data,
+                missed_samples,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_sample(timestamp: u32, name: &str) -> LogData {
+        LogData {
+            timestamp,
+            data: HashMap::from([(name.to_owned(), crate::Value::U8(0))]),
+            missed_samples: 0,
+        }
+    }
+
+    #[test]
+    fn stalled_block_does_not_accumulate() {
+        let mut merger = SampleMerger::new(2, 10);
+
+        assert!(merger.merge(0, block_sample(0, "a")).is_none());
+        let sample = merger.merge(1, block_sample(2, "b")).unwrap();
+        assert_eq!((sample.timestamp, sample.data.len(), sample.missed_samples), (0, 2, 0));
+
+        // The second block stops delivering
+        for timestamp in (10..1000).step_by(10) {
+            assert!(merger.merge(0, block_sample(timestamp, "a")).is_none());
+            assert!(merger.pending.len() <= MAX_PENDING_PERIODS as usize + 1);
+        }
+
+        // The samples dropped in between are reported
+        assert!(merger.merge(0, block_sample(1000, "a")).is_none());
+        let sample = merger.merge(1, block_sample(1001, "b")).unwrap();
+        assert_eq!(sample.timestamp, 1000);
+        assert_eq!(sample.missed_samples, 99);
+    }
+}
@@ -0,0 +1,245 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use crate::{
+    bootloader::{Release, ReleaseFile},
+    subsystems::memory::{DeckMemory, DeckMemorySection},
+    Error, Result,
+};
+use tokio::time::{sleep, Instant};
+
+const BOOTLOADER_TIMEOUT: Duration = Duration::from_secs(5);
+const BOOTLOADER_POLL_PERIOD: Duration = Duration::from_millis(100);
+
+/// Outcome of the upgrade of one deck
+#[derive(Debug)]
+pub enum DeckUpgradeStatus {
+    /// The firmware has been written, verified and the deck has been reset to firmware
+    Upgraded,
+    /// The deck has not been upgraded, the string contains the reason
+    Skipped(String),
+    /// The upgrade failed
+    Failed(Error),
+}
+
+/// Result of the upgrade of one deck, as returned by [DeckUpgrader::upgrade()]
+#[derive(Debug)]
+pub struct DeckUpgradeReport {
+    /// Name of the deck memory section, for example "bcAI:gap8"
+    pub name: String,
+    /// Outcome of the upgrade
+    pub status: DeckUpgradeStatus,
+}
+
+/// # Deck firmware upgrader
+///
+/// Flashes deck firmware through the [DeckMemory]. The firmware images are registered by deck memory section name,
+/// either as raw binaries with [DeckUpgrader::add_image()] or from a firmware release with
+/// [DeckUpgrader::add_release()].
+///
+/// For each image, [DeckUpgrader::upgrade()] checks the image against the hash and length required by the deck,
+/// resets the deck into its bootloader, writes the image, reads it back to verify it and resets the deck to
+/// firmware. By default only the decks reporting that an upgrade is required are flashed.
+///
+/// # Example
+/// ```no_run
+/// use crazyflie_lib::bootloader::Release;
+/// use crazyflie_lib::subsystems::memory::{DeckMemory, DeckUpgrader, MemoryType};
+/// use crazyflie_lib::{Crazyflie, Error};
+/// use crazyflie_link::LinkContext;
+/// async fn example() -> Result<(), Error> {
+///   let context = LinkContext::new();
+///   let cf = Crazyflie::connect_from_uri(
+///       &context,
+///       "radio://0/60/2M/E7E7E7E7E7",
+///       crazyflie_lib::NoTocCache
+///   ).await?;
+///   let device = cf.memory.get_memories(Some(MemoryType::DeckMemory))[0].clone();
+///   let deck_memory = cf.memory.open_memory::<DeckMemory>(device).await.unwrap()?;
+///
+///   let mut upgrader = DeckUpgrader::new();
+///   upgrader.add_release(&Release::open("firmware-cf2-2025.02.zip")?);
+///   for report in upgrader.upgrade(&deck_memory, |name, done, total| {
+///       println!("{}: {}/{}", name, done, total);
+///   }).await {
+///       println!("{}: {:?}", report.name, report.status);
+///   }
+///   cf.memory.close_memory(deck_memory).await?;
+///   Ok(())
+/// };
+/// ```
+#[derive(Debug, Default)]
+pub struct DeckUpgrader {
+    images: BTreeMap<String, Vec<u8>>,
+    force: bool,
+}
+
+impl DeckUpgrader {
+    /// Create an upgrader without any firmware image
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Flash the decks even if they do not report that an upgrade is required
+    pub fn force(&mut self, force: bool) -> &mut Self {
+        self.force = force;
+        self
+    }
+
+    /// Add a firmware image for the deck memory section `name`
+    ///
+    /// Replaces any image previously added for this section.
+    pub fn add_image(&mut self, name: &str, data: &[u8]) -> &mut Self {
+        self.images.insert(name.to_owned(), data.to_vec());
+        self
+    }
+
+    /// Add a deck firmware file from a release
+    ///
+    /// The file target is used as deck memory section name.
+    ///
+    /// # Errors
+    /// Returns an `Error::InvalidArgument` if the file is not a deck firmware.
+    pub fn add_release_file(&mut self, file: &ReleaseFile) -> Result<&mut Self> {
+        if file.platform != "deck" || file.file_type != "fw" {
+            return Err(Error::InvalidArgument(format!(
+                "{} is not a deck firmware",
+                file.name
+            )));
+        }
+        Ok(self.add_image(&file.target, &file.data))
+    }
+
+    /// Add all the deck firmware files of a release
+    pub fn add_release(&mut self, release: &Release) -> &mut Self {
+        for file in &release.files {
+            // Crazyflie firmware files are rejected, they are flashed with the bootloader
+            let _ = self.add_release_file(file);
+        }
+        self
+    }
+
+    /// Upgrade the decks
+    ///
+    /// The decks are upgraded one after the other, a failure does not prevent the other decks from being upgraded.
+    /// One report is returned per image added to the upgrader, in the order of the section names.
+    ///
+    /// # Arguments
+    /// * `deck_memory` - The deck memory of the Crazyflie
+    /// * `progress_callback` - A callback function called with the deck name, the number of bytes written so far
+    ///   and the total number of bytes to write.
+    pub async fn upgrade<F>(
+        &self,
+        deck_memory: &DeckMemory,
+        mut progress_callback: F,
+    ) -> Vec<DeckUpgradeReport>
+    where
+        F: FnMut(&str, usize, usize),
+    {
+        let mut reports = Vec::new();
+
+        for (name, image) in &self.images {
+            let status = match deck_memory.section(name) {
+                Some(section) => {
+                    match self
+                        .upgrade_section(section, image, |done, total| {
+                            progress_callback(name, done, total)
+                        })
+                        .await
+                    {
+                        Ok(status) => status,
+                        Err(e) => DeckUpgradeStatus::Failed(e),
+                    }
+                }
+                None => DeckUpgradeStatus::Skipped("Deck not found".to_owned()),
+            };
+
+            reports.push(DeckUpgradeReport {
+                name: name.clone(),
+                status,
+            });
+        }
+
+        reports
+    }
+
+    async fn upgrade_section<F>(
+        &self,
+        section: &DeckMemorySection,
+        image: &[u8],
+        progress_callback: F,
+    ) -> Result<DeckUpgradeStatus>
+    where
+        F: FnMut(usize, usize),
+    {
+        if !section.supports_upgrade() {
+            return Ok(DeckUpgradeStatus::Skipped(
+                "Deck does not support firmware upgrade".to_owned(),
+            ));
+        }
+        if !self.force && !section.upgrade_required().await? {
+            return Ok(DeckUpgradeStatus::Skipped(
+                "Firmware upgrade not required".to_owned(),
+            ));
+        }
+        let bootloader_active = section.bootloader_active().await?;
+        if !bootloader_active && !section.can_reset_to_bootloader() {
+            return Err(Error::MemoryError(
+                "Deck bootloader is not active and the deck cannot be reset to bootloader".to_owned(),
+            ));
+        }
+
+        if let Some(length) = section.required_length()
+            && length as usize != image.len()
+        {
+            return Err(Error::InvalidArgument(format!(
+                "Firmware length is {} bytes, the deck requires {} bytes",
+                image.len(),
+                length
+            )));
+        }
+        if let Some(hash) = section.required_hash() {
+            let image_hash = crc32fast::hash(image);
+            if image_hash != hash {
+                return Err(Error::InvalidArgument(format!(
+                    "Firmware hash is {:#010x}, the deck requires {:#010x}",
+                    image_hash, hash
+                )));
+            }
+        }
+
+        if !bootloader_active {
+            section.reset_to_bootloader().await?;
+            wait_bootloader_active(section).await?;
+        }
+
+        section.write_with_progress(0, image, progress_callback).await?;
+
+        if section.supports_read() {
+            let written = section.read(0, image.len()).await?;
+            if written != image {
+                return Err(Error::MemoryError(
+                    "Firmware verification failed".to_owned(),
+                ));
+            }
+        }
+
+        if section.can_reset_to_firmware() {
+            section.reset_to_firmware().await?;
+        }
+
+        Ok(DeckUpgradeStatus::Upgraded)
+    }
+}
+
+async fn wait_bootloader_active(section: &DeckMemorySection) -> Result<()> {
+    let deadline = Instant::now() + BOOTLOADER_TIMEOUT;
+    while !section.bootloader_active().await? {
+        if Instant::now() > deadline {
+            return Err(Error::MemoryError(
+                "Timeout waiting for the deck bootloader".to_owned(),
+            ));
+        }
+        sleep(BOOTLOADER_POLL_PERIOD).await;
+    }
+    Ok(())
+}
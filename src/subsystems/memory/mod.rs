@@ -18,6 +18,7 @@ use tokio::sync::Mutex;
 mod memory_types;
 mod eeprom_config;
 mod deckmem;
+mod deck_upgrade;
 mod raw;
 mod ow;
 mod trajectory;
@@ -30,6 +31,7 @@ use crate::crazyflie::MEMORY_PORT;
 pub use memory_types::*;
 pub use eeprom_config::*;
 pub use deckmem::*;
+pub use deck_upgrade::*;
 pub use raw::*;
 pub use ow::*;
 pub use trajectory::*;
@@ -0,0 +1,152 @@
+use super::Param;
+use crate::{Error, Result, Value, ValueType};
+
+/// # Parameter entry
+///
+/// Description and current value of a parameter, returned by [Param::group()].
+#[derive(Debug, Clone)]
+pub struct ParamEntry {
+    /// Group of the parameter
+    pub group: String,
+    /// Name of the parameter in its group
+    pub name: String,
+    /// Type of the parameter
+    pub value_type: ValueType,
+    /// True if the parameter can be written
+    pub writable: bool,
+    /// True if the parameter can be stored in persistent storage, see [Param::is_persistent()]
+    pub persistent: bool,
+    /// Current value of the parameter
+    pub value: Value,
+}
+
+impl ParamEntry {
+    /// Full name of the parameter, formatted as "group.name"
+    pub fn full_name(&self) -> String {
+        format!("{}.{}", self.group, self.name)
+    }
+}
+
+/// Match a name against a glob pattern
+///
+/// `*` matches any sequence of characters, including dots, and `?` matches exactly one character.
+fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` in the pattern and of the name when it was reached
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+impl Param {
+    /// Get the names of all the parameter groups, in alphabetical order
+    pub fn groups(&self) -> Vec<String> {
+        let mut groups: Vec<String> = Vec::new();
+        for name in self.toc.keys() {
+            let (group, _) = name.split_once('.').unwrap_or((name, ""));
+            // The TOC is sorted, so the parameters of a group are next to each other
+            if groups.last().map(String::as_str) != Some(group) {
+                groups.push(group.to_owned());
+            }
+        }
+        groups
+    }
+
+    /// Get the names of the parameters matching a glob pattern
+    ///
+    /// The pattern is matched against the full "group.name" of the parameters. `*` matches any sequence of
+    /// characters and `?` matches any single character, for example `"kalman.*"` matches all the parameters of the
+    /// `kalman` group and `"*.kp"` matches the `kp` parameter of all the groups.
+    pub fn names_matching(&self, pattern: &str) -> Vec<String> {
+        let pattern: Vec<char> = pattern.chars().collect();
+        self.toc
+            .keys()
+            .filter(|name| glob_match(&pattern, &name.chars().collect::<Vec<_>>()))
+            .cloned()
+            .collect()
+    }
+
+    /// Get all the parameters of a group
+    ///
+    /// The entries contain the description of the parameters as well as their current value. The values that are not
+    /// in the cache yet are read from the Crazyflie, see [Param::get_many()].
+    ///
+    /// Return an error if the group does not exist.
+    pub async fn group(&self, group: &str) -> Result<Vec<ParamEntry>> {
+        let prefix = format!("{}.", group);
+        let names: Vec<_> = self
+            .toc
+            .keys()
+            .filter(|name| name.starts_with(&prefix))
+            .collect();
+        if names.is_empty() {
+            return Err(Error::ParamError(format!(
+                "Parameter group {} not found",
+                group
+            )));
+        }
+
+        let mut values = self.get_many(&names).await?;
+
+        let mut entries = Vec::new();
+        for full_name in names {
+            let (_, info) = &self.toc[full_name];
+            entries.push(ParamEntry {
+                group: group.to_owned(),
+                name: full_name[prefix.len()..].to_owned(),
+                value_type: info.item_type,
+                writable: info.writable,
+                persistent: self.is_persistent(full_name).await?,
+                // All the values have been read by get_many
+                value: values.remove(full_name.as_str()).unwrap(),
+            });
+        }
+
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, name: &str) -> bool {
+        glob_match(
+            &pattern.chars().collect::<Vec<_>>(),
+            &name.chars().collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn glob_patterns() {
+        assert!(matches("kalman.*", "kalman.pNAcc_xy"));
+        assert!(!matches("kalman.*", "kalmanx.q"));
+        assert!(matches("*.kp", "pid_rate.kp"));
+        assert!(!matches("*.kp", "pid_rate.roll_kp"));
+        assert!(matches("pid_*.*_k?", "pid_rate.roll_kp"));
+        assert!(matches("*", "deck.bcLighthouse4"));
+        assert!(matches("deck.bcLighthouse4", "deck.bcLighthouse4"));
+        assert!(!matches("deck.bcLighthouse", "deck.bcLighthouse4"));
+    }
+}
@@ -13,8 +13,22 @@
 //! Parameters can also be set without reading them first. If a variable value
 //! is modified by the Crazyflie during runtime, it sends a packet with the new
 //! value which updates the local value cache.
+//!
+//! The parameters can be browsed by group with [Param::groups()] and [Param::group()], or selected with a glob
+//! pattern with [Param::names_matching()].
+//!
+//! The parameters of a group can also be mapped to a struct deriving [ParamGroup](derive@ParamGroup), which is read,
+//! written and watched as a whole with the types checked against the TOC, see [TypedParamGroup].
+//!
+//! Changes of a parameter or of a group of parameters can be followed with [Param::watch()] and
+//! [Param::watch_group()].
+//!
+//! The value of all the parameters can be saved in a [ParamSnapshot], compared and restored later, see
+//! [Param::snapshot()]. Similarly, the content of the persistent parameter storage can be exported to a
+//! [PersistentParamProfile] and imported into another Crazyflie, see [Param::persistent_export()].
 
 use crate::crtp_utils::TocCache;
+use crate::events::EventSender;
 use crate::{crtp_utils::WaitForPacket, Error, Result};
 use crate::{Value, ValueType};
 use crazyflie_link::Packet;
@@ -24,11 +38,26 @@ use serde::{Serialize, Deserialize};
 use std::{
     collections::{BTreeMap, HashMap},
     convert::{TryFrom, TryInto},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 
 use crate::crazyflie::PARAM_PORT;
 
+mod group;
+mod persistent;
+mod snapshot;
+mod typed;
+mod watch;
+
+pub use group::*;
+pub use persistent::*;
+pub use snapshot::*;
+pub use typed::*;
+
 /// State of a persistent parameter
 #[derive(Debug, Clone)]
 pub struct PersistentParamState {
@@ -86,23 +115,49 @@ impl TryFrom<u8> for ParamItemInfo {
     }
 }
 
-type ParamChangeWatchers =
-    Arc<Mutex<Vec<futures::channel::mpsc::UnboundedSender<(String, Value)>>>>;
+/// Parameters a watcher is notified for
+#[derive(Debug)]
+enum WatchFilter {
+    All,
+    Name(String),
+    /// Group prefix, including the trailing dot
+    Group(String),
+}
 
-async fn notify_watchers(watchers: &ParamChangeWatchers, name: String, value: Value) {
-    let mut to_remove = Vec::new();
-    let mut watchers = watchers.lock().await;
+impl WatchFilter {
+    /// Parameters watched by name or by group are polled, see [Param::watch()]
+    fn is_polled(&self) -> bool {
+        !matches!(self, WatchFilter::All)
+    }
 
-    for (i, watcher) in watchers.iter().enumerate() {
-        if watcher.unbounded_send((name.clone(), value)).is_err() {
-            to_remove.push(i);
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            WatchFilter::All => true,
+            WatchFilter::Name(watched) => watched == name,
+            WatchFilter::Group(prefix) => name.starts_with(prefix.as_str()),
         }
     }
+}
 
-    // Remove watchers that have dropped
-    for i in to_remove.into_iter().rev() {
-        watchers.remove(i);
-    }
+#[derive(Debug)]
+struct ParamWatcher {
+    filter: WatchFilter,
+    sender: futures::channel::mpsc::UnboundedSender<(String, Value)>,
+}
+
+type ParamChangeWatchers = Arc<Mutex<Vec<ParamWatcher>>>;
+
+async fn notify_watchers(watchers: &ParamChangeWatchers, name: String, value: Value) {
+    let mut watchers = watchers.lock().await;
+
+    // Remove the watchers that have been dropped, even if they are not interested in this parameter
+    watchers.retain(|watcher| {
+        if watcher.filter.matches(&name) {
+            watcher.sender.unbounded_send((name.clone(), value)).is_ok()
+        } else {
+            !watcher.sender.is_closed()
+        }
+    });
 }
 
 /// # Access to the Crazyflie Param Subsystem
@@ -112,19 +167,120 @@ async fn notify_watchers(watchers: &ParamChangeWatchers, name: String, value: Va
 #[derive(Debug)]
 pub struct Param {
     uplink: channel::Sender<Packet>,
-    read_downlink: channel::Receiver<Packet>,
+    /// Locked during the reads so that the answers are not taken by a concurrent read
+    read_downlink: Arc<Mutex<channel::Receiver<Packet>>>,
     write_downlink: Mutex<channel::Receiver<Packet>>,
     misc_downlink: Mutex<channel::Receiver<Packet>>,
     toc: Arc<BTreeMap<String, (u16, ParamItemInfo)>>,
     values: Arc<Mutex<HashMap<String, Option<Value>>>>,
     default_values: Arc<Mutex<HashMap<String, DefaultValueCache>>>,
     watchers: ParamChangeWatchers,
+    /// True while the task polling the watched parameters is running, only modified with the watchers locked
+    polling: Arc<AtomicBool>,
+    /// Interval at which the watched parameters are polled, `None` when the polling is disabled
+    poll_interval: Arc<Mutex<Option<Duration>>>,
+}
+
+/// Invalidates the cached parameter values after a reconnection
+///
+/// The Crazyflie may have been restarted while the link was down, the values are read again when next accessed.
+pub(crate) struct ParamRestorer {
+    values: Arc<Mutex<HashMap<String, Option<Value>>>>,
+}
+
+impl ParamRestorer {
+    pub(crate) async fn restore(&self) {
+        for value in self.values.lock().await.values_mut() {
+            *value = None;
+        }
+    }
 }
 
 fn not_found(name: &str) -> Error {
     Error::ParamError(format!("Parameter {} not found", name))
 }
 
+/// Compare values by type and binary representation, so that NaN values are equal to themselves
+fn same_value(a: &Value, b: &Value) -> bool {
+    ValueType::from(*a) == ValueType::from(*b) && Vec::<u8>::from(*a) == Vec::<u8>::from(*b)
+}
+
+/// Read the value of a parameter from the Crazyflie, bypassing the value cache
+async fn read_value(
+    uplink: &channel::Sender<Packet>,
+    read_downlink: &channel::Receiver<Packet>,
+    param_id: u16,
+    param_type: ValueType,
+) -> Result<Value> {
+    let request = Packet::new(PARAM_PORT, READ_CHANNEL, param_id.to_le_bytes().into());
+    uplink
+        .send_async(request.clone())
+        .await
+        .map_err(|_| Error::Disconnected)?;
+
+    let response = read_downlink
+        .wait_packet(
+            request.get_port(),
+            request.get_channel(),
+            request.get_data(),
+        )
+        .await?;
+
+    Value::from_le_bytes(&response.get_data()[3..], param_type)
+}
+
+/// Read the value of many parameters from the Crazyflie, bypassing the value cache
+///
+/// Several read requests are kept in flight at the same time and the answers are matched by parameter ID. The names
+/// must be in the TOC.
+async fn read_values<'a>(
+    uplink: &channel::Sender<Packet>,
+    read_downlink: &channel::Receiver<Packet>,
+    toc: &BTreeMap<String, (u16, ParamItemInfo)>,
+    names: &[&'a str],
+) -> Result<Vec<(&'a str, Value)>> {
+    let mut result = Vec::with_capacity(names.len());
+    let mut to_read = names.iter();
+    let mut pending = HashMap::new();
+    loop {
+        while pending.len() < MAX_PENDING_READS {
+            let Some(name) = to_read.next() else {
+                break;
+            };
+            let (param_id, param_info) = &toc[*name];
+            uplink
+                .send_async(Packet::new(PARAM_PORT, READ_CHANNEL, param_id.to_le_bytes().into()))
+                .await
+                .map_err(|_| Error::Disconnected)?;
+            pending.insert(*param_id, (*name, param_info.item_type));
+        }
+
+        if pending.is_empty() {
+            break;
+        }
+
+        let answer = read_downlink.recv_async().await.map_err(|_| Error::Disconnected)?;
+        let data = answer.get_data();
+        if answer.get_channel() != READ_CHANNEL || data.len() < 3 {
+            continue;
+        }
+        let param_id = u16::from_le_bytes([data[0], data[1]]);
+        let Some((name, item_type)) = pending.remove(&param_id) else {
+            continue;
+        };
+        if data[2] != 0 {
+            return Err(Error::ParamError(format!(
+                "Error reading parameter {}: parameter error code {}",
+                name, data[2]
+            )));
+        }
+
+        result.push((name, Value::from_le_bytes(&data[3..], item_type)?));
+    }
+
+    Ok(result)
+}
+
 const READ_CHANNEL: u8 = 1;
 const _WRITE_CHANNEL: u8 = 2;
 const MISC_CHANNEL: u8 = 3;
@@ -138,6 +294,9 @@ const _MISC_GET_DEFAULT_VALUE: u8 = 6; // V1 - deprecated, use V2
 const MISC_GET_EXTENDED_TYPE_V2: u8 = 7;
 const MISC_GET_DEFAULT_VALUE_V2: u8 = 8;
 
+/// Maximum number of read requests in flight in [Param::get_many()]
+const MAX_PENDING_READS: usize = 8;
+
 // Firmware protocol status codes for persistent_get_state
 const PARAM_PERSISTENT_NOT_STORED: u8 = 0;
 const PARAM_PERSISTENT_STORED: u8 = 1;
@@ -148,6 +307,7 @@ impl Param {
         downlink: channel::Receiver<Packet>,
         uplink: channel::Sender<Packet>,
         toc_cache: T,
+        events: &EventSender,
     ) -> Result<Self>
     where
         T: TocCache,
@@ -155,20 +315,22 @@ impl Param {
         let (toc_downlink, read_downlink, write_downlink, misc_downlink) =
             crate::crtp_utils::crtp_channel_dispatcher(downlink);
 
-        let toc = crate::crtp_utils::fetch_toc(PARAM_PORT, uplink.clone(), toc_downlink, toc_cache).await?;
+        let toc = crate::crtp_utils::fetch_toc(PARAM_PORT, uplink.clone(), toc_downlink, toc_cache, events).await?;
 
         // Create a channel for MISC commands (not param updates)
         let (misc_cmd_tx, misc_cmd_rx) = channel::unbounded();
 
         let mut param = Self {
             uplink,
-            read_downlink,
+            read_downlink: Arc::new(Mutex::new(read_downlink)),
             write_downlink: Mutex::new(write_downlink),
             misc_downlink: Mutex::new(misc_cmd_rx),
             toc: Arc::new(toc),
             values: Arc::new(Mutex::new(HashMap::new())),
             default_values: Arc::new(Mutex::new(HashMap::new())),
             watchers: Arc::default(),
+            polling: Arc::default(),
+            poll_interval: Arc::new(Mutex::new(Some(watch::DEFAULT_WATCH_POLL_INTERVAL))),
         };
 
         param.initialize_values().await?;
@@ -190,23 +352,10 @@ impl Param {
         Ok(())
     }
 
-    async fn read_value(&self, param_id: u16, param_type: ValueType) -> Result<Value> {
-        let request = Packet::new(PARAM_PORT, READ_CHANNEL, param_id.to_le_bytes().into());
-        self.uplink
-            .send_async(request.clone())
-            .await
-            .map_err(|_| Error::Disconnected)?;
-
-        let response = self
-            .read_downlink
-            .wait_packet(
-                request.get_port(),
-                request.get_channel(),
-                request.get_data(),
-            )
-            .await?;
-
-        Value::from_le_bytes(&response.get_data()[3..], param_type)
+    pub(crate) fn restorer(&self) -> ParamRestorer {
+        ParamRestorer {
+            values: self.values.clone(),
+        }
     }
 
     async fn spawn_misc_loop(&self, misc_downlink: channel::Receiver<Packet>, misc_cmd_tx: channel::Sender<Packet>) {
@@ -414,7 +563,8 @@ impl Param {
                     .toc
                     .get(name)
                     .ok_or_else(|| not_found(name))?;
-                let v = self.read_value(*param_id, param_info.item_type).await?;
+                let read_downlink = self.read_downlink.lock().await;
+                let v = read_value(&self.uplink, &read_downlink, *param_id, param_info.item_type).await?;
                 // Update the cache
                 *values.get_mut(name).unwrap() = Some(v.clone());
                 v
@@ -426,6 +576,50 @@ impl Param {
             .map_err(|e| Error::ParamError(format!("Type error reading param: {:?}", e)))?)
     }
 
+    /// Get the value of many parameters
+    ///
+    /// The values already in the cache are returned directly, the other values are read from the Crazyflie.
+    /// Contrary to calling [Param::get()] for each parameter, several read requests are kept in flight at the same
+    /// time and the answers are matched by parameter ID, which makes reading many parameters a lot faster.
+    ///
+    /// Return an error if one of the parameters does not exist or if a value cannot be read.
+    pub async fn get_many<S: AsRef<str>>(&self, names: &[S]) -> Result<BTreeMap<String, Value>> {
+        let mut values = self.values.lock().await;
+
+        let mut result = BTreeMap::new();
+        let mut to_read = Vec::new();
+        for name in names {
+            let name = name.as_ref();
+            match values.get(name).ok_or_else(|| not_found(name))? {
+                Some(value) => {
+                    result.insert(name.to_owned(), *value);
+                }
+                None => {
+                    if !to_read.contains(&name) {
+                        to_read.push(name);
+                    }
+                }
+            }
+        }
+
+        // The names have been found in the values so they are in the TOC
+        let read_downlink = self.read_downlink.lock().await;
+        for (name, value) in read_values(&self.uplink, &read_downlink, &self.toc, &to_read).await? {
+            *values.get_mut(name).unwrap() = Some(value);
+            result.insert(name.to_owned(), value);
+        }
+
+        Ok(result)
+    }
+
+    /// Get the value of all the parameters
+    ///
+    /// See [Param::get_many()].
+    pub async fn get_all(&self) -> Result<BTreeMap<String, Value>> {
+        let names: Vec<_> = self.toc.keys().collect();
+        self.get_many(&names).await
+    }
+
     /// Set a parameter from a f64 potentially loosing data
     ///
     /// This function is a forgiving version of the `set` function. It allows
@@ -484,17 +678,33 @@ impl Param {
     ///    notification will be generated when the Crazyflie confirms the parameter
     ///    has been set.
     ///  - Or it can be a parameter change in the Crazyflie itself. The Crazyflie
-    ///    will send notification packet for every internal parameter change.
+    ///    will send notification packet for most internal parameter changes. The
+    ///    changes that are not notified are only seen for the parameters that are
+    ///    polled because they are watched by name or by group, see [Param::watch()].
     pub async fn watch_change(&self) -> Result<impl futures::Stream<Item = (String, Value)> + use<>> {
         if self.uplink.is_disconnected() {
             return Err(Error::Disconnected);
         }
-        let (tx, rx) = futures::channel::mpsc::unbounded();
+        Ok(self.add_watcher(WatchFilter::All).await)
+    }
+
+    async fn add_watcher(
+        &self,
+        filter: WatchFilter,
+    ) -> futures::channel::mpsc::UnboundedReceiver<(String, Value)> {
+        let (sender, receiver) = futures::channel::mpsc::unbounded();
 
         let mut watchers = self.watchers.lock().await;
-        watchers.push(tx);
+        watchers.retain(|watcher| !watcher.sender.is_closed());
+        if filter.is_polled()
+            && self.poll_interval.lock().await.is_some()
+            && !self.polling.swap(true, Ordering::SeqCst)
+        {
+            self.spawn_poll_task();
+        }
+        watchers.push(ParamWatcher { filter, sender });
 
-        Ok(rx)
+        receiver
     }
 
     /// Check if a parameter supports persistent storage
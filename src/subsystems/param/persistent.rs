@@ -0,0 +1,135 @@
+use super::{not_found, same_value, Param};
+use crate::{Error, Result, Value, ValueType};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// # Persistent parameter profile
+///
+/// Content of the persistent parameter storage of a Crazyflie, exported with [Param::persistent_export()] and
+/// imported in a Crazyflie with [Param::persistent_import()]. The profile can be serialized, for example to JSON, to
+/// copy the stored configuration of a Crazyflie to other Crazyflies.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PersistentParamProfile {
+    /// Stored value of all the persistent parameters by name, None if no value is stored for the parameter
+    pub values: BTreeMap<String, Option<Value>>,
+}
+
+/// # Persistent import report
+///
+/// Result of [Param::persistent_import()]. In dry-run mode, the report lists what would have been done.
+#[derive(Debug, Default)]
+pub struct PersistentImportReport {
+    /// Parameters whose value has been set and stored
+    pub stored: Vec<String>,
+    /// Parameters whose stored value has been cleared
+    pub cleared: Vec<String>,
+    /// Number of parameters that already had the profile state
+    pub unchanged: usize,
+    /// Parameters that could not be imported, with the reason
+    pub failed: Vec<(String, Error)>,
+}
+
+/// Change needed to import a parameter
+enum PersistentChange {
+    Unchanged,
+    Store(Value),
+    Clear,
+}
+
+impl Param {
+    /// Export the persistent parameter storage
+    ///
+    /// All the persistent parameters of the TOC are listed with their stored value, or None if no value is stored
+    /// for the parameter. See [Param::persistent_get_state()].
+    pub async fn persistent_export(&self) -> Result<PersistentParamProfile> {
+        let mut values = BTreeMap::new();
+        for (name, (_, info)) in self.toc.iter() {
+            // Only the parameters with an extended type can be persistent, this avoids querying all the parameters
+            if !info.has_extended_type || !self.is_persistent(name).await? {
+                continue;
+            }
+            let state = self.persistent_get_state(name).await?;
+            values.insert(name.clone(), state.stored_value);
+        }
+
+        Ok(PersistentParamProfile { values })
+    }
+
+    /// Import a persistent parameter profile
+    ///
+    /// Each parameter of the profile with a value is set to this value and stored to persistent storage, see
+    /// [Param::persistent_store()]. The stored value of the parameters without a value in the profile is cleared.
+    /// The parameters whose stored state already matches the profile are not modified. The persistent parameters
+    /// that are not in the profile are left untouched.
+    ///
+    /// If `dry_run` is true, the Crazyflie is only queried and the returned report lists the changes that would be
+    /// made.
+    ///
+    /// A parameter that cannot be imported, because it does not exist, is not persistent or has a different type, is
+    /// reported as failed in the returned report and the import continues with the other parameters. An error is
+    /// returned only if the Crazyflie gets disconnected.
+    pub async fn persistent_import(
+        &self,
+        profile: &PersistentParamProfile,
+        dry_run: bool,
+    ) -> Result<PersistentImportReport> {
+        let mut report = PersistentImportReport::default();
+
+        for (name, value) in &profile.values {
+            let change = match self.persistent_change(name, *value).await {
+                Ok(change) => change,
+                Err(Error::Disconnected) => return Err(Error::Disconnected),
+                Err(e) => {
+                    report.failed.push((name.clone(), e));
+                    continue;
+                }
+            };
+
+            let store = matches!(change, PersistentChange::Store(_));
+            let result = match change {
+                PersistentChange::Unchanged => {
+                    report.unchanged += 1;
+                    continue;
+                }
+                _ if dry_run => Ok(()),
+                PersistentChange::Store(value) => self.set_and_store(name, value).await,
+                PersistentChange::Clear => self.persistent_clear(name).await,
+            };
+
+            match result {
+                Ok(()) if store => report.stored.push(name.clone()),
+                Ok(()) => report.cleared.push(name.clone()),
+                Err(Error::Disconnected) => return Err(Error::Disconnected),
+                Err(e) => report.failed.push((name.clone(), e)),
+            }
+        }
+
+        Ok(report)
+    }
+
+    async fn set_and_store(&self, name: &str, value: Value) -> Result<()> {
+        self.set(name, value).await?;
+        self.persistent_store(name).await
+    }
+
+    /// Change needed for the stored state of a parameter to be `value`
+    async fn persistent_change(&self, name: &str, value: Option<Value>) -> Result<PersistentChange> {
+        let (_, info) = self.toc.get(name).ok_or_else(|| not_found(name))?;
+        if let Some(value) = value
+            && ValueType::from(value) != info.item_type
+        {
+            return Err(Error::ParamError(format!(
+                "Parameter {} is type {:?}, cannot import value {:?}",
+                name, info.item_type, value
+            )));
+        }
+
+        let state = self.persistent_get_state(name).await?;
+        Ok(match (state.stored_value, value) {
+            (Some(stored), Some(value)) if same_value(&stored, &value) => PersistentChange::Unchanged,
+            (None, None) => PersistentChange::Unchanged,
+            (_, Some(value)) => PersistentChange::Store(value),
+            (Some(_), None) => PersistentChange::Clear,
+        })
+    }
+}
@@ -0,0 +1,158 @@
+use super::{not_found, same_value, Param};
+use crate::{Error, Result, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// # Parameter snapshot
+///
+/// Values of all the parameters of a Crazyflie at a given time, taken with [Param::snapshot()]. The snapshot can be
+/// serialized, for example to JSON, compared with another snapshot with [ParamSnapshot::diff()] and written back to
+/// a Crazyflie with [Param::restore()].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ParamSnapshot {
+    /// Value of the parameters by name
+    pub values: BTreeMap<String, Value>,
+}
+
+/// # Parameter difference
+///
+/// Difference of value of one parameter between two states, see [ParamSnapshot::diff()] and
+/// [Param::diff_against_defaults()].
+#[derive(Debug, Clone)]
+pub struct ParamDifference {
+    /// Name of the parameter
+    pub name: String,
+    /// Value in the first state, None if the parameter does not exist in it
+    pub old: Option<Value>,
+    /// Value in the second state, None if the parameter does not exist in it
+    pub new: Option<Value>,
+}
+
+/// # Restore report
+///
+/// Result of [Param::restore()].
+#[derive(Debug, Default)]
+pub struct ParamRestoreReport {
+    /// Parameters that have been written
+    pub written: Vec<String>,
+    /// Number of parameters that already had the snapshot value
+    pub unchanged: usize,
+    /// Parameters that could not be restored, with the reason
+    pub failed: Vec<(String, Error)>,
+}
+
+impl ParamSnapshot {
+    /// Differences from this snapshot to another one
+    ///
+    /// The differences are sorted by parameter name. The parameters that only exist in one of the snapshots are
+    /// reported with a None value for the other one.
+    pub fn diff(&self, other: &ParamSnapshot) -> Vec<ParamDifference> {
+        let mut differences: Vec<_> = self
+            .values
+            .iter()
+            .filter(|(name, value)| !other.values.get(*name).is_some_and(|other| same_value(value, other)))
+            .map(|(name, value)| ParamDifference {
+                name: name.clone(),
+                old: Some(*value),
+                new: other.values.get(name).copied(),
+            })
+            .collect();
+
+        differences.extend(
+            other
+                .values
+                .iter()
+                .filter(|(name, _)| !self.values.contains_key(*name))
+                .map(|(name, value)| ParamDifference {
+                    name: name.clone(),
+                    old: None,
+                    new: Some(*value),
+                }),
+        );
+        differences.sort_by(|a, b| a.name.cmp(&b.name));
+
+        differences
+    }
+}
+
+impl Param {
+    /// Take a snapshot of the value of all the parameters
+    ///
+    /// The values that are not in the cache yet are read from the Crazyflie, see [Param::get_all()].
+    pub async fn snapshot(&self) -> Result<ParamSnapshot> {
+        Ok(ParamSnapshot {
+            values: self.get_all().await?,
+        })
+    }
+
+    /// Differences between the default value of the parameters and their current value
+    ///
+    /// The default values are fetched with [Param::get_default_value()]. Only the writable parameters have a default
+    /// value, the other parameters are ignored. In the returned differences, `old` is the default value and `new`
+    /// is the current value.
+    pub async fn diff_against_defaults(&self) -> Result<Vec<ParamDifference>> {
+        let mut differences = Vec::new();
+        for (name, (_, info)) in self.toc.iter() {
+            if !info.writable {
+                continue;
+            }
+            let default = match self.get_default_value(name).await {
+                Ok(default) => default,
+                Err(Error::ParamError(_)) => continue,
+                Err(e) => return Err(e),
+            };
+            let value: Value = self.get(name).await?;
+            if !same_value(&default, &value) {
+                differences.push(ParamDifference {
+                    name: name.clone(),
+                    old: Some(default),
+                    new: Some(value),
+                });
+            }
+        }
+
+        Ok(differences)
+    }
+
+    /// Restore the parameter values of a snapshot
+    ///
+    /// Only the parameters whose current value differs from the snapshot are written. A parameter that cannot be
+    /// restored, because it does not exist, is read-only, has a different type or because the Crazyflie refuses
+    /// the value, is reported as failed in the returned report and the restore continues with the other parameters.
+    ///
+    /// An error is returned only if the Crazyflie gets disconnected.
+    pub async fn restore(&self, snapshot: &ParamSnapshot) -> Result<ParamRestoreReport> {
+        let mut report = ParamRestoreReport::default();
+
+        let names: Vec<_> = snapshot.values.keys().filter(|name| self.toc.contains_key(*name)).collect();
+        let current_values = self.get_many(&names).await?;
+
+        for (name, value) in &snapshot.values {
+            let Some((_, info)) = self.toc.get(name) else {
+                report.failed.push((name.clone(), not_found(name)));
+                continue;
+            };
+
+            if same_value(&current_values[name], value) {
+                report.unchanged += 1;
+                continue;
+            }
+
+            if !info.writable {
+                report.failed.push((
+                    name.clone(),
+                    Error::ParamError(format!("Parameter {} is read-only", name)),
+                ));
+                continue;
+            }
+
+            match self.set(name, *value).await {
+                Ok(()) => report.written.push(name.clone()),
+                Err(Error::Disconnected) => return Err(Error::Disconnected),
+                Err(e) => report.failed.push((name.clone(), e)),
+            }
+        }
+
+        Ok(report)
+    }
+}
@@ -0,0 +1,163 @@
+use super::{not_found, Param, WatchFilter};
+use crate::{Error, Result, Value, ValuePrimitive, ValueType};
+use futures::{Stream, StreamExt};
+use std::future::Future;
+
+/// # Typed parameter group
+///
+/// Struct mapped to the parameters of a group. This trait should be implemented with the
+/// [ParamGroup](derive@ParamGroup) derive macro: the group is given with a `#[param(group = "...")]` attribute on the
+/// struct and each field is mapped to the parameter of the same name in the group, or to the parameter given with a
+/// `#[param(name = "...")]` attribute on the field. The field type must be the Rust type matching the type of the
+/// parameter in the TOC.
+///
+/// The types of the parameters are checked against the TOC before each access: an error is returned if a parameter
+/// does not exist or if its type does not match the type of its field.
+///
+/// ```no_run
+/// # use crazyflie_lib::{Crazyflie, Error, subsystems::param::{ParamGroup, TypedParamGroup}};
+/// # async fn example(cf: Crazyflie) -> Result<(), Error> {
+/// #[derive(ParamGroup)]
+/// #[param(group = "pid_rate")]
+/// struct PidRate {
+///     roll_kp: f32,
+///     roll_ki: f32,
+///     #[param(name = "roll_kd")]
+///     roll_derivative: f32,
+/// }
+///
+/// let mut pid = PidRate::read(&cf.param).await?;
+/// pid.roll_kp *= 1.1;
+/// pid.write(&cf.param).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait TypedParamGroup: Sized + Send + Sync {
+    /// Name of the parameter group
+    const GROUP: &'static str;
+
+    /// Name in the group and type of the parameters, in the order of the fields
+    fn parameters() -> Vec<(&'static str, ValueType)>;
+
+    /// Build the struct from the values of the parameters, in the order of [TypedParamGroup::parameters()]
+    fn from_values(values: &[Value]) -> Result<Self>;
+
+    /// Values of the fields, in the order of [TypedParamGroup::parameters()]
+    fn to_values(&self) -> Vec<Value>;
+
+    /// Read the parameters of the group, see [Param::read_typed()]
+    fn read(param: &Param) -> impl Future<Output = Result<Self>> + Send {
+        param.read_typed::<Self>()
+    }
+
+    /// Write the fields to the parameters of the group, see [Param::write_typed()]
+    fn write(&self, param: &Param) -> impl Future<Output = Result<()>> + Send {
+        param.write_typed(self)
+    }
+
+    /// Watch the parameters of the group, see [Param::watch_typed()]
+    fn watch(param: &Param) -> impl Future<Output = Result<impl Stream<Item = Self> + Send + use<Self>>> + Send
+    where
+        Self: 'static,
+    {
+        param.watch_typed::<Self>()
+    }
+}
+
+/// Derive macro implementing [TypedParamGroup], see the trait documentation
+pub use crazyflie_lib_derive::ParamGroup;
+
+/// Decode a field of a typed parameter group, used by the [ParamGroup](derive@ParamGroup) derive macro
+#[doc(hidden)]
+pub fn decode_param_field<T: ValuePrimitive>(values: &[Value], index: usize) -> Result<T> {
+    let value = *values
+        .get(index)
+        .ok_or_else(|| Error::ParamError("Missing value for parameter group field".to_owned()))?;
+
+    value.try_into()
+}
+
+impl Param {
+    /// Full names of the parameters of a typed group, checked against the TOC
+    fn bind_typed<T: TypedParamGroup>(&self, write: bool) -> Result<Vec<String>> {
+        T::parameters()
+            .into_iter()
+            .map(|(name, value_type)| {
+                let name = format!("{}.{}", T::GROUP, name);
+                let (_, info) = self.toc.get(&name).ok_or_else(|| not_found(&name))?;
+                if info.item_type != value_type {
+                    return Err(Error::ParamError(format!(
+                        "Type mismatch for parameter {}: the TOC type is {:?}, the field type is {:?}",
+                        name, info.item_type, value_type
+                    )));
+                }
+                if write && !info.writable {
+                    return Err(Error::ParamError(format!(
+                        "Parameter {} is read-only",
+                        name
+                    )));
+                }
+                Ok(name)
+            })
+            .collect()
+    }
+
+    /// Read the parameters of a [TypedParamGroup] struct
+    ///
+    /// The values that are not in the cache yet are read from the Crazyflie, see [Param::get_many()].
+    ///
+    /// Return an error if a parameter does not exist or if its type does not match the type of its field.
+    pub async fn read_typed<T: TypedParamGroup>(&self) -> Result<T> {
+        let names = self.bind_typed::<T>(false)?;
+        let values = self.get_many(&names).await?;
+
+        T::from_values(&names.iter().map(|name| values[name]).collect::<Vec<_>>())
+    }
+
+    /// Write the fields of a [TypedParamGroup] struct to the parameters
+    ///
+    /// The parameters are set one after the other, see [Param::set()]. All the parameters are checked before the
+    /// first one is written: an error is returned, and nothing is written, if a parameter does not exist, is
+    /// read-only or if its type does not match the type of its field.
+    pub async fn write_typed<T: TypedParamGroup>(&self, values: &T) -> Result<()> {
+        let names = self.bind_typed::<T>(true)?;
+
+        for (name, value) in names.iter().zip(values.to_values()) {
+            self.set(name, value).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Watch the parameters of a [TypedParamGroup] struct
+    ///
+    /// The returned stream first generates the current value of the struct and then a new value each time one of
+    /// the parameters changes. See [Param::watch_group()].
+    ///
+    /// The stream ends when the Crazyflie is disconnected. Return an error if a parameter does not exist or if its
+    /// type does not match the type of its field.
+    pub async fn watch_typed<T: TypedParamGroup + 'static>(
+        &self,
+    ) -> Result<impl Stream<Item = T> + Send + use<T>> {
+        if self.uplink.is_disconnected() {
+            return Err(Error::Disconnected);
+        }
+        let names = self.bind_typed::<T>(false)?;
+
+        let changes = self.add_watcher(WatchFilter::Group(format!("{}.", T::GROUP))).await;
+        let values = self.get_many(&names).await?;
+        let mut current: Vec<_> = names.iter().map(|name| values[name]).collect();
+        let first = T::from_values(&current)?;
+
+        let updates = changes.filter_map(move |(name, value)| {
+            // Other parameters of the group are not part of the struct
+            let update = names.iter().position(|field| *field == name).and_then(|index| {
+                current[index] = value;
+                T::from_values(&current).ok()
+            });
+            futures::future::ready(update)
+        });
+
+        Ok(futures::stream::iter([first]).chain(updates))
+    }
+}
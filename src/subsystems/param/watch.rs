@@ -0,0 +1,243 @@
+use super::{not_found, notify_watchers, read_values, same_value, Param, WatchFilter};
+use crate::{Error, Result, Value};
+use futures::{Stream, StreamExt};
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+/// Default interval at which the watched parameters are read from the Crazyflie, see [Param::set_watch_poll_interval()]
+///
+/// The Crazyflie does not notify all the parameter changes, for example the read-only deck detection parameters are
+/// set by the firmware without sending an update.
+pub(super) const DEFAULT_WATCH_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Compare cached values, see [same_value()]
+fn same_option(a: &Option<Value>, b: &Option<Value>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => same_value(a, b),
+        (a, b) => a.is_none() && b.is_none(),
+    }
+}
+
+impl Param {
+    /// Watch the value of a parameter
+    ///
+    /// The returned stream first generates the current value of the parameter, read from the Crazyflie if it is not
+    /// in the cache yet, and then each new value of the parameter. See [Param::watch_change()] for the reasons of a
+    /// parameter change. Since not all changes are notified by the Crazyflie, the parameters watched by name or by
+    /// group are also read from the Crazyflie every 100ms by default, and a value that differs from the cache is
+    /// notified as a change to all the watchers. The polling interval can be changed, or the polling disabled, with
+    /// [Param::set_watch_poll_interval()].
+    ///
+    /// The stream ends when the Crazyflie is disconnected. Return an error if the parameter does not exist.
+    pub async fn watch(&self, name: &str) -> Result<impl Stream<Item = Value> + use<>> {
+        if self.uplink.is_disconnected() {
+            return Err(Error::Disconnected);
+        }
+        if !self.toc.contains_key(name) {
+            return Err(not_found(name));
+        }
+
+        // The watcher is added before reading the value so that no change can be missed in between
+        let changes = self.add_watcher(WatchFilter::Name(name.to_owned())).await;
+        let current: Value = self.get(name).await?;
+
+        Ok(futures::stream::iter([current]).chain(changes.map(|(_, value)| value)))
+    }
+
+    /// Set the interval at which the parameters watched by name or by group are read from the Crazyflie
+    ///
+    /// Each poll reads all the watched parameters, which uses radio bandwidth when large groups are watched. A longer
+    /// interval reduces the load, `None` disables the polling: only the changes notified by the Crazyflie are then
+    /// seen by the watchers. The default interval is 100ms.
+    ///
+    /// Return an error if the interval is zero.
+    pub async fn set_watch_poll_interval(&self, interval: Option<Duration>) -> Result<()> {
+        if interval == Some(Duration::ZERO) {
+            return Err(Error::InvalidArgument(
+                "Parameter watch poll interval must not be zero".to_owned(),
+            ));
+        }
+
+        let watchers = self.watchers.lock().await;
+        *self.poll_interval.lock().await = interval;
+        if interval.is_some()
+            && watchers.iter().any(|watcher| watcher.filter.is_polled() && !watcher.sender.is_closed())
+            && !self.polling.swap(true, Ordering::SeqCst)
+        {
+            self.spawn_poll_task();
+        }
+        Ok(())
+    }
+
+    /// Start the task polling the parameters watched by name or by group
+    ///
+    /// The task stops when there is no such watcher anymore or when the polling is disabled. When the Crazyflie is
+    /// disconnected all the watchers are removed, which ends the watch streams.
+    pub(super) fn spawn_poll_task(&self) {
+        let uplink = self.uplink.clone();
+        let read_downlink = self.read_downlink.clone();
+        let toc = self.toc.clone();
+        let values = self.values.clone();
+        let watchers = self.watchers.clone();
+        let polling = self.polling.clone();
+        let poll_interval = self.poll_interval.clone();
+
+        tokio::spawn(async move {
+            loop {
+                // The polling being disabled is handled with the watchers locked below
+                let interval = *poll_interval.lock().await;
+                if let Some(interval) = interval {
+                    tokio::time::sleep(interval).await;
+                }
+
+                let names: Vec<&str> = {
+                    let mut watchers = watchers.lock().await;
+                    watchers.retain(|watcher| !watcher.sender.is_closed());
+                    let names: Vec<_> = toc
+                        .keys()
+                        .filter(|name| {
+                            watchers
+                                .iter()
+                                .any(|watcher| watcher.filter.is_polled() && watcher.filter.matches(name))
+                        })
+                        .map(String::as_str)
+                        .collect();
+                    if names.is_empty() || poll_interval.lock().await.is_none() {
+                        // Stopped with the watchers locked so that the next watcher starts a new task
+                        polling.store(false, Ordering::SeqCst);
+                        return;
+                    }
+                    names
+                };
+
+                // The cache is only locked to compare and update the values, not during the reads, so that the
+                // parameter accesses are not delayed by the polling
+                let before: HashMap<&str, Option<Value>> = {
+                    let cache = values.lock().await;
+                    names.iter().map(|name| (*name, cache.get(*name).copied().flatten())).collect()
+                };
+                let read = {
+                    let read_downlink = read_downlink.lock().await;
+                    match read_values(&uplink, &read_downlink, &toc, &names).await {
+                        Ok(read) => read,
+                        Err(Error::Disconnected) => break,
+                        Err(_) => continue,
+                    }
+                };
+
+                let mut cache = values.lock().await;
+                // The values are cleared when the Crazyflie is disconnected
+                if cache.is_empty() {
+                    break;
+                }
+                let mut changes = Vec::new();
+                for (name, value) in read {
+                    let cached = cache.get_mut(name).unwrap();
+                    // A value updated while reading is more recent than the value read
+                    if !same_option(cached, &before[name]) {
+                        continue;
+                    }
+                    if !cached.is_some_and(|cached| same_value(&cached, &value)) {
+                        changes.push((name, value));
+                    }
+                    *cached = Some(value);
+                }
+                drop(cache);
+
+                for (name, value) in changes {
+                    notify_watchers(&watchers, name.to_owned(), value).await;
+                }
+            }
+
+            let mut watchers = watchers.lock().await;
+            polling.store(false, Ordering::SeqCst);
+            watchers.clear(); // Drops all tx senders, killing the streams
+        });
+    }
+
+    /// Watch the values of all the parameters of a group
+    ///
+    /// The returned stream generates tuples containing the name of the parameter, formatted as "group.name", and its
+    /// value. It first generates the current value of all the parameters of the group, see [Param::get_many()], and
+    /// then each change of a parameter of the group. The parameters of the group are polled, see [Param::watch()].
+    ///
+    /// The stream ends when the Crazyflie is disconnected. Return an error if the group does not exist.
+    pub async fn watch_group(&self, group: &str) -> Result<impl Stream<Item = (String, Value)> + use<>> {
+        if self.uplink.is_disconnected() {
+            return Err(Error::Disconnected);
+        }
+        let prefix = format!("{}.", group);
+        let names: Vec<_> = self
+            .toc
+            .keys()
+            .filter(|name| name.starts_with(&prefix))
+            .collect();
+        if names.is_empty() {
+            return Err(Error::ParamError(format!(
+                "Parameter group {} not found",
+                group
+            )));
+        }
+
+        let changes = self.add_watcher(WatchFilter::Group(prefix)).await;
+        let current = self.get_many(&names).await?;
+
+        Ok(futures::stream::iter(current).chain(changes))
+    }
+
+    /// Wait for a parameter to reach a value
+    ///
+    /// Wait until `predicate` returns true for the value of the parameter and return this value. The predicate is
+    /// first called with the current value of the parameter, so this function returns immediately if the parameter
+    /// already satisfies the condition. For example, to wait for the Lighthouse deck to be detected:
+    ///
+    /// ```no_run
+    /// # use crazyflie_lib::{Crazyflie, Error};
+    /// # use crazyflie_link::LinkContext;
+    /// # use std::time::Duration;
+    /// # async fn example() -> Result<(), Error> {
+    /// # let context = LinkContext::new();
+    /// # let cf = Crazyflie::connect_from_uri(
+    /// #   &context,
+    /// #   "radio://0/60/2M/E7E7E7E7E7",
+    /// #   crazyflie_lib::NoTocCache
+    /// # ).await?;
+    /// cf.param
+    ///     .wait_for("deck.bcLighthouse4", |detected: &u8| *detected == 1, Duration::from_secs(5))
+    ///     .await?;
+    /// # Ok(())
+    /// # };
+    /// ```
+    ///
+    /// The parameter is watched with [Param::watch()], so values that are changed by the Crazyflie without
+    /// notification are also seen. As for [Param::get()], the type of the parameter must match the type of the
+    /// predicate argument.
+    ///
+    /// Return [Error::Timeout] if the condition is not satisfied after `timeout`, or an error if the parameter does
+    /// not exist or if the Crazyflie gets disconnected.
+    pub async fn wait_for<T, F>(&self, name: &str, predicate: F, timeout: Duration) -> Result<T>
+    where
+        T: TryFrom<Value>,
+        <T as TryFrom<Value>>::Error: std::fmt::Debug,
+        F: Fn(&T) -> bool,
+    {
+        let mut values = self.watch(name).await?;
+
+        let wait = async {
+            while let Some(value) = values.next().await {
+                let value: T = value
+                    .try_into()
+                    .map_err(|e| Error::ParamError(format!("Type error reading param: {:?}", e)))?;
+                if predicate(&value) {
+                    return Ok(value);
+                }
+            }
+            Err(Error::Disconnected)
+        };
+
+        tokio::time::timeout(timeout, wait)
+            .await
+            .map_err(|_| Error::Timeout)?
+    }
+}
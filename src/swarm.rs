@@ -0,0 +1,298 @@
+//! # Swarm of Crazyflies
+//!
+//! A [Swarm] connects to many Crazyflies in parallel and gives access to each of them by URI. The same async closure
+//! can be run on all the Crazyflies, either in [parallel](Swarm::parallel) or [one after the
+//! other](Swarm::sequential). The result of each Crazyflie is collected, and the swarm [FailurePolicy] decides if
+//! the failure of some of the Crazyflies fails the whole operation.
+//!
+//! ```no_run
+//! # use crazyflie_lib::swarm::{FailurePolicy, Swarm};
+//! # async fn example() -> crazyflie_lib::Result<()> {
+//! let context = crazyflie_link::LinkContext::new();
+//! let swarm = Swarm::connect_from_uris(
+//!     &context,
+//!     ["radio://0/30/2M/E7E7E7E701", "radio://0/30/2M/E7E7E7E702"],
+//!     crazyflie_lib::NoTocCache,
+//!     FailurePolicy::AllOrNothing,
+//! ).await?;
+//!
+//! let decks = swarm
+//!     .parallel(async |_uri, cf| cf.param.get::<u8>("deck.bcLighthouse4").await)
+//!     .await?;
+//! for (uri, deck) in decks {
+//!     println!("{}: lighthouse deck {:?}", uri, deck);
+//! }
+//!
+//! swarm.disconnect().await;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{Crazyflie, Error, Result, TocCache};
+use std::collections::BTreeMap;
+use std::future::Future;
+
+/// Results of an operation run on a swarm, by Crazyflie URI
+pub type SwarmResults<R> = BTreeMap<String, Result<R>>;
+
+/// # Swarm operation failure
+///
+/// Returned by [Swarm::parallel()] and [Swarm::sequential()] when the failures are not accepted by the swarm
+/// [FailurePolicy]. It holds the result of each Crazyflie, successes included.
+///
+/// It converts to an [Error::SwarmError] listing the errors, so that it can be propagated with `?` in functions
+/// returning a [Result].
+#[derive(Debug)]
+pub struct SwarmFailure<R> {
+    /// Result of each Crazyflie by URI. The Crazyflies not run by [Swarm::sequential()] are absent.
+    pub results: SwarmResults<R>,
+    /// Number of Crazyflies in the swarm
+    pub total: usize,
+}
+
+impl<R> SwarmFailure<R> {
+    /// Iterate over the Crazyflies that failed and their error
+    pub fn errors(&self) -> impl Iterator<Item = (&str, &Error)> {
+        self.results
+            .iter()
+            .filter_map(|(uri, result)| result.as_ref().err().map(|e| (uri.as_str(), e)))
+    }
+}
+
+impl<R> From<SwarmFailure<R>> for Error {
+    fn from(failure: SwarmFailure<R>) -> Self {
+        Error::SwarmError {
+            total: failure.total,
+            errors: failure
+                .results
+                .into_iter()
+                .filter_map(|(uri, result)| result.err().map(|e| (uri, e)))
+                .collect(),
+        }
+    }
+}
+
+impl<R> std::fmt::Display for SwarmFailure<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let errors: Vec<_> = self.errors().map(|(uri, e)| format!("{}: {}", uri, e)).collect();
+        write!(f, "{} of {} Crazyflies failed ({})", errors.len(), self.total, errors.join(", "))
+    }
+}
+
+impl<R: std::fmt::Debug> std::error::Error for SwarmFailure<R> {}
+
+/// # Partial failure policy
+///
+/// Decides if an operation on a [Swarm] succeeds when it fails for some of the Crazyflies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailurePolicy {
+    /// The operation fails if it fails for any of the Crazyflies
+    AllOrNothing,
+    /// The operation always succeeds, the errors are reported per Crazyflie
+    BestEffort,
+    /// The operation succeeds if it succeeds for at least this number of Crazyflies
+    AtLeast(usize),
+}
+
+impl FailurePolicy {
+    fn is_satisfied(&self, total: usize, failed: usize) -> bool {
+        match self {
+            FailurePolicy::AllOrNothing => failed == 0,
+            FailurePolicy::BestEffort => true,
+            FailurePolicy::AtLeast(n) => total - failed >= *n,
+        }
+    }
+}
+
+/// # Swarm of Crazyflies
+///
+/// See the [swarm module documentation](crate::swarm) for more context and information.
+pub struct Swarm {
+    drones: Vec<(String, Crazyflie)>,
+    connect_errors: Vec<(String, Error)>,
+    policy: FailurePolicy,
+}
+
+impl Swarm {
+    /// Connect to Crazyflies from their URIs
+    ///
+    /// The connections are made in parallel, the TOC cache is shared by all the Crazyflies. An error is returned if
+    /// the connection failures are not accepted by the failure `policy`, in which case the Crazyflies that were
+    /// connected are disconnected. Otherwise the failed connections are available with [Swarm::connect_errors()].
+    ///
+    /// The `policy` is also used for the operations run later on the swarm.
+    pub async fn connect_from_uris<I, S, T>(
+        link_context: &crazyflie_link::LinkContext,
+        uris: I,
+        toc_cache: T,
+        policy: FailurePolicy,
+    ) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+        T: TocCache + Send + Sync + 'static,
+    {
+        let uris = uris.into_iter().map(|uri| uri.as_ref().to_owned());
+
+        Self::connect_with(
+            uris,
+            |uri| {
+                let toc_cache = toc_cache.clone();
+                async move { Crazyflie::connect_from_uri(link_context, &uri, toc_cache).await }
+            },
+            policy,
+        )
+        .await
+    }
+
+    /// Connect to Crazyflies using a custom connection function
+    ///
+    /// The `connect` function is called in parallel for each URI. This allows to build a swarm out of any kind of
+    /// connection, for example with [Crazyflie::connect_from_simulation()] or
+    /// [Crazyflie::connect_from_uri_with_reconnect()]. See [Swarm::connect_from_uris()] for the failure handling.
+    ///
+    /// Return an error, without connecting, if an URI is given more than once.
+    pub async fn connect_with<I, F, Fut>(uris: I, connect: F, policy: FailurePolicy) -> Result<Self>
+    where
+        I: IntoIterator<Item = String>,
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<Crazyflie>>,
+    {
+        let uris: Vec<String> = uris.into_iter().collect();
+        for (i, uri) in uris.iter().enumerate() {
+            if uris[..i].contains(uri) {
+                return Err(Error::InvalidArgument(format!(
+                    "URI {} is given more than once",
+                    uri
+                )));
+            }
+        }
+
+        let results =
+            futures::future::join_all(uris.iter().map(|uri| connect(uri.clone()))).await;
+
+        let mut swarm = Swarm {
+            drones: Vec::new(),
+            connect_errors: Vec::new(),
+            policy,
+        };
+        for (uri, result) in uris.into_iter().zip(results) {
+            match result {
+                Ok(cf) => swarm.drones.push((uri, cf)),
+                Err(e) => swarm.connect_errors.push((uri, e)),
+            }
+        }
+
+        let total = swarm.drones.len() + swarm.connect_errors.len();
+        if !policy.is_satisfied(total, swarm.connect_errors.len()) {
+            swarm.disconnect().await;
+            return Err(Error::SwarmError {
+                total,
+                errors: std::mem::take(&mut swarm.connect_errors),
+            });
+        }
+
+        Ok(swarm)
+    }
+
+    /// Number of connected Crazyflies
+    pub fn len(&self) -> usize {
+        self.drones.len()
+    }
+
+    /// Returns `true` if no Crazyflie is connected
+    pub fn is_empty(&self) -> bool {
+        self.drones.is_empty()
+    }
+
+    /// URIs of the connected Crazyflies, in the order they were given at connection
+    pub fn uris(&self) -> impl Iterator<Item = &str> {
+        self.drones.iter().map(|(uri, _)| uri.as_str())
+    }
+
+    /// Get a connected Crazyflie by URI
+    pub fn get(&self, uri: &str) -> Option<&Crazyflie> {
+        self.drones
+            .iter()
+            .find(|(drone_uri, _)| drone_uri == uri)
+            .map(|(_, cf)| cf)
+    }
+
+    /// Iterate over the connected Crazyflies and their URI
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Crazyflie)> {
+        self.drones.iter().map(|(uri, cf)| (uri.as_str(), cf))
+    }
+
+    /// Crazyflies that failed to connect, with their error
+    pub fn connect_errors(&self) -> &[(String, Error)] {
+        &self.connect_errors
+    }
+
+    /// Run an async closure on all the Crazyflies in parallel
+    ///
+    /// The closure is called with the URI and the Crazyflie object. The result of each Crazyflie is returned. If the
+    /// failures are not accepted by the swarm failure policy, the results are returned in a [SwarmFailure] instead.
+    /// All the Crazyflies run the closure to completion whatever the policy.
+    pub async fn parallel<F, R>(&self, f: F) -> std::result::Result<SwarmResults<R>, SwarmFailure<R>>
+    where
+        F: AsyncFn(&str, &Crazyflie) -> Result<R>,
+    {
+        let results =
+            futures::future::join_all(self.drones.iter().map(|(uri, cf)| f(uri, cf))).await;
+
+        let results = self
+            .drones
+            .iter()
+            .map(|(uri, _)| uri.clone())
+            .zip(results)
+            .collect();
+
+        self.check_results(results)
+    }
+
+    /// Run an async closure on all the Crazyflies one after the other
+    ///
+    /// The Crazyflies are handled in the order of their URIs at connection. This works like [Swarm::parallel()],
+    /// except that the remaining Crazyflies are not run anymore as soon as the failures make the swarm failure policy
+    /// impossible to satisfy.
+    pub async fn sequential<F, R>(&self, f: F) -> std::result::Result<SwarmResults<R>, SwarmFailure<R>>
+    where
+        F: AsyncFn(&str, &Crazyflie) -> Result<R>,
+    {
+        let total = self.drones.len();
+        let mut results = BTreeMap::new();
+        let mut failed = 0;
+
+        for (uri, cf) in &self.drones {
+            let result = f(uri, cf).await;
+            if result.is_err() {
+                failed += 1;
+            }
+            results.insert(uri.clone(), result);
+
+            if !self.policy.is_satisfied(total, failed) {
+                break;
+            }
+        }
+
+        self.check_results(results)
+    }
+
+    /// Disconnect all the Crazyflies in parallel
+    pub async fn disconnect(&self) {
+        futures::future::join_all(self.drones.iter().map(|(_, cf)| cf.disconnect())).await;
+    }
+
+    fn check_results<R>(&self, results: SwarmResults<R>) -> std::result::Result<SwarmResults<R>, SwarmFailure<R>> {
+        let failed = results.values().filter(|result| result.is_err()).count();
+
+        if self.policy.is_satisfied(self.drones.len(), failed) {
+            Ok(results)
+        } else {
+            Err(SwarmFailure {
+                results,
+                total: self.drones.len(),
+            })
+        }
+    }
+}
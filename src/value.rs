@@ -116,10 +116,23 @@ impl From<Value> for ValueType {
     }
 }
 
+/// # Primitive type of a value
+///
+/// Implemented by the Rust primitive types matching a [ValueType]. Allows to know the Crazyflie type of a variable
+/// from its Rust type, for example in the structs deriving [LogBlock](derive@crate::subsystems::log::LogBlock).
+pub trait ValuePrimitive: Into<Value> + TryFrom<Value, Error = crate::Error> {
+    /// Type of the values of this Rust type
+    const VALUE_TYPE: ValueType;
+}
+
 // Conversion from and to matching primitive types
 
 macro_rules! primitive_impl {
     ($ty:ident, $name:ident) => {
+        impl ValuePrimitive for $ty {
+            const VALUE_TYPE: ValueType = ValueType::$name;
+        }
+
         impl From<$ty> for Value {
             fn from(v: $ty) -> Self {
                 Value::$name(v)
@@ -0,0 +1,54 @@
+#![cfg(feature = "blocking")]
+
+use crazyflie_lib::blocking::Crazyflie;
+use crazyflie_lib::simulation::SimulatedCrazyflie;
+use crazyflie_lib::subsystems::commander::Setpoint;
+use crazyflie_lib::subsystems::log::LogPeriod;
+use crazyflie_lib::subsystems::memory::{MemoryType, RawMemory};
+use crazyflie_lib::{NoTocCache, Value, ValueType};
+
+#[test]
+fn blocking_subsystems() -> crazyflie_lib::Result<()> {
+    let sim = SimulatedCrazyflie::new();
+    sim.add_log_variable("stateEstimate.x", ValueType::F32);
+    sim.add_param("pid_rate.roll_kp", Value::F32(250.0), true);
+    sim.add_memory(MemoryType::MemoryTester, (0..100).collect());
+
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache)?;
+
+    let kp: f32 = cf.param.get("pid_rate.roll_kp")?;
+    assert_eq!(kp, 250.0);
+    let mut changes = cf.param.watch_change()?;
+    cf.param.set("pid_rate.roll_kp", 200.0f32)?;
+    let (name, value) = changes.next().unwrap();
+    assert_eq!(name, "pid_rate.roll_kp");
+    assert!(matches!(value, Value::F32(v) if v == 200.0));
+
+    let mut block = cf.log.create_block()?;
+    block.add_variable("stateEstimate.x")?;
+    let mut stream = block.start(LogPeriod::from_millis(10)?)?;
+    let samples = stream.by_ref().take(3).collect::<crazyflie_lib::Result<Vec<_>>>()?;
+    assert!(samples.windows(2).all(|s| s[0].timestamp < s[1].timestamp));
+    stream.set_period(LogPeriod::from_millis(20)?)?;
+    assert_eq!(cf.log.active_blocks().len(), 1);
+    stream.stop()?.delete()?;
+    assert!(cf.log.active_blocks().is_empty());
+
+    let device = cf.memory.get_memories(Some(MemoryType::MemoryTester))[0].clone();
+    let memory: RawMemory = cf.memory.open_memory(device).unwrap()?;
+    assert_eq!(cf.block_on(memory.read(10, 3))?, [10, 11, 12]);
+
+    cf.commander.setpoint_rpyt(0.0, 0.0, 0.0, 0)?;
+    cf.high_level_commander.take_off(0.5, None, 2.0, None)?;
+
+    let stream = cf.commander.stream(100.0)?;
+    stream.set(Setpoint::Hover { vx: 0.0, vy: 0.0, yawrate: 0.0, zdistance: 0.5 })?;
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    cf.block_on(stream.stop())?;
+    // rpyt, take off, at least one hover setpoint and notify setpoint stop
+    assert!(sim.take_commander_packets().len() >= 4);
+
+    cf.disconnect();
+    assert!(cf.param.set("pid_rate.roll_kp", 100.0f32).is_err());
+    Ok(())
+}
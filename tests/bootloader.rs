@@ -0,0 +1,196 @@
+use async_trait::async_trait;
+use crazyflie_lib::bootloader::{Bootloader, Release, ReleaseFile, Target};
+use crazyflie_lib::{CrtpTransport, Error, Result};
+use crazyflie_link::Packet;
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
+use std::sync::{Arc, Mutex};
+
+const PAGE_SIZE: usize = 100;
+const BUFFER_PAGES: usize = 2;
+const FLASH_PAGES: usize = 20;
+const START_PAGE: usize = 4;
+
+/// Minimal bootloader answering info, buffer load, flash write and flash read requests
+#[derive(Default)]
+struct FakeBootloader {
+    flash: Arc<Mutex<HashMap<u8, Vec<u8>>>>,
+    buffer: Mutex<Vec<u8>>,
+    answers: Mutex<Vec<Packet>>,
+    notify: tokio::sync::Notify,
+    /// Page size, buffer pages, flash pages and start page reported instead of the default layout
+    layout: Mutex<Option<[usize; 4]>>,
+}
+
+impl FakeBootloader {
+    fn handle(&self, data: &[u8]) -> Option<Vec<u8>> {
+        let (target, command) = (data[0], data[1]);
+        let mut flash = self.flash.lock().unwrap();
+        let flash = flash
+            .entry(target)
+            .or_insert_with(|| vec![0xFF; PAGE_SIZE * FLASH_PAGES]);
+        let mut buffer = self.buffer.lock().unwrap();
+        buffer.resize(PAGE_SIZE * BUFFER_PAGES, 0);
+        let u16_at = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]) as usize;
+
+        match command {
+            0x10 => {
+                let mut answer = vec![target, command];
+                let layout = self.layout.lock().unwrap().unwrap_or([PAGE_SIZE, BUFFER_PAGES, FLASH_PAGES, START_PAGE]);
+                for value in layout {
+                    answer.extend_from_slice(&(value as u16).to_le_bytes());
+                }
+                answer.extend_from_slice(&[0; 12]);
+                Some(answer)
+            }
+            0x14 => {
+                let start = u16_at(2) * PAGE_SIZE + u16_at(4);
+                buffer[start..start + data.len() - 6].copy_from_slice(&data[6..]);
+                None
+            }
+            0x18 => {
+                let (flash_page, count) = (u16_at(4), u16_at(6));
+                let length = count * PAGE_SIZE;
+                flash[flash_page * PAGE_SIZE..][..length].copy_from_slice(&buffer[..length]);
+                Some(vec![target, command, 1, 0])
+            }
+            0x1C => {
+                let start = u16_at(2) * PAGE_SIZE + u16_at(4);
+                let end = (start + 25).min((u16_at(2) + 1) * PAGE_SIZE);
+                let mut answer = data[..6].to_vec();
+                answer.extend_from_slice(&flash[start..end]);
+                Some(answer)
+            }
+            _ => None,
+        }
+    }
+}
+
+struct FakeLink(Arc<FakeBootloader>);
+
+#[async_trait]
+impl CrtpTransport for FakeLink {
+    async fn send_packet(&self, packet: Packet) -> Result<()> {
+        if let Some(answer) = self.0.handle(packet.get_data()) {
+            let mut packet = vec![0xFF];
+            packet.extend(answer);
+            self.0.answers.lock().unwrap().push(packet.into());
+            self.0.notify.notify_one();
+        }
+        Ok(())
+    }
+
+    async fn recv_packet(&self) -> Result<Packet> {
+        loop {
+            if let Some(packet) = self.0.answers.lock().unwrap().pop() {
+                return Ok(packet);
+            }
+            self.0.notify.notified().await;
+        }
+    }
+
+    async fn close(&self) {}
+
+    async fn wait_close(&self) -> String {
+        futures::future::pending().await
+    }
+}
+
+#[tokio::test]
+async fn flash_and_verify() -> Result<()> {
+    let fake = Arc::new(FakeBootloader::default());
+    let bootloader = Bootloader::from_transport(FakeLink(fake.clone()));
+
+    let info = bootloader.flash_info(Target::Stm32).await?;
+    assert_eq!(info.page_size as usize, PAGE_SIZE);
+    assert_eq!(info.start_page as usize, START_PAGE);
+
+    let image: Vec<u8> = (0..450).map(|i| i as u8).collect();
+    let mut progress = Vec::new();
+    bootloader
+        .flash(Target::Stm32, &image, |done, total| progress.push((done, total)))
+        .await?;
+
+    let flash = fake.flash.lock().unwrap()[&0xFF].clone();
+    assert_eq!(flash[START_PAGE * PAGE_SIZE..][..image.len()], image);
+    assert_eq!(progress.first(), Some(&(0, 450)));
+    assert_eq!(progress.last(), Some(&(450, 450)));
+
+    assert!(matches!(
+        bootloader.write_pages(Target::Stm32, 0, &image, |_, _| ()).await,
+        Err(Error::InvalidArgument(_))
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn flash_release() -> Result<()> {
+    let manifest = r#"{
+        "version": 1,
+        "release": "2025.02",
+        "files": {
+            "cf2.bin": {"platform": "cf2", "target": "stm32", "type": "fw"},
+            "cf2_nrf.bin": {"platform": "cf2", "target": "nrf51", "type": "fw"},
+            "bolt.bin": {"platform": "bolt", "target": "stm32", "type": "fw"},
+            "aideck.bin": {"platform": "deck", "target": "bcAI:gap8", "type": "fw"}
+        }
+    }"#;
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, content) in [
+        ("manifest.json", manifest.as_bytes()),
+        ("cf2.bin", &[1; 250]),
+        ("cf2_nrf.bin", &[2; 120]),
+        ("bolt.bin", &[4; 200]),
+        ("aideck.bin", &[3; 10]),
+    ] {
+        zip.start_file(name, zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(content)?;
+    }
+    let release = Release::from_reader(zip.finish().unwrap())?;
+    assert_eq!(release.release.as_deref(), Some("2025.02"));
+    assert_eq!(release.files.len(), 4);
+
+    let fake = Arc::new(FakeBootloader::default());
+    let bootloader = Bootloader::from_transport(FakeLink(fake.clone()));
+    let mut last_progress = (0, 0);
+    bootloader
+        .flash_release(&release, "cf2", |done, total| last_progress = (done, total))
+        .await?;
+
+    assert_eq!(last_progress, (370, 370));
+    {
+        let flash = fake.flash.lock().unwrap();
+        assert_eq!(flash[&0xFF][START_PAGE * PAGE_SIZE..][..250], [1; 250]);
+        assert_eq!(flash[&0xFE][START_PAGE * PAGE_SIZE..][..120], [2; 120]);
+    }
+
+    assert!(matches!(
+        bootloader.flash_release(&release, "tag", |_, _| ()).await,
+        Err(Error::InvalidArgument(_))
+    ));
+    let mut duplicated = release.clone();
+    let stm32 = release.files.iter().find(|file| file.name == "cf2.bin").unwrap();
+    duplicated.files.push(ReleaseFile { name: "cf2_copy.bin".to_owned(), ..stm32.clone() });
+    assert!(matches!(
+        bootloader.flash_release(&duplicated, "cf2", |_, _| ()).await,
+        Err(Error::InvalidArgument(_))
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn invalid_flash_layout() -> Result<()> {
+    let fake = Arc::new(FakeBootloader::default());
+    let bootloader = Bootloader::from_transport(FakeLink(fake.clone()));
+
+    for layout in [[0, BUFFER_PAGES, FLASH_PAGES, START_PAGE], [PAGE_SIZE, BUFFER_PAGES, 0, 0]] {
+        *fake.layout.lock().unwrap() = Some(layout);
+        assert!(matches!(bootloader.flash_info(Target::Stm32).await, Err(Error::ProtocolError(_))));
+        assert!(matches!(
+            bootloader.write_pages(Target::Stm32, 0, &[0; 10], |_, _| ()).await,
+            Err(Error::ProtocolError(_))
+        ));
+    }
+    Ok(())
+}
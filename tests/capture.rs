@@ -0,0 +1,44 @@
+use crazyflie_lib::capture::{CaptureReader, Direction, ReplayTransport};
+use crazyflie_lib::simulation::SimulatedCrazyflie;
+use crazyflie_lib::subsystems::log::LogPeriod;
+use crazyflie_lib::{Crazyflie, NoTocCache, Value, ValueType};
+
+async fn session(cf: &Crazyflie) -> crazyflie_lib::Result<(f32, Vec<u32>)> {
+    let kp: f32 = cf.param.get("pid_rate.roll_kp").await?;
+
+    let mut block = cf.log.create_block().await?;
+    block.add_variable("stateEstimate.x").await?;
+    let stream = block.start(LogPeriod::from_millis(10)?).await?;
+    let mut timestamps = Vec::new();
+    for _ in 0..5 {
+        timestamps.push(stream.next().await?.timestamp);
+    }
+
+    Ok((kp, timestamps))
+}
+
+#[tokio::test]
+async fn capture_and_replay() -> crazyflie_lib::Result<()> {
+    let path = std::env::temp_dir().join(format!("crazyflie-lib-test-{}.crtp", std::process::id()));
+
+    let sim = SimulatedCrazyflie::new();
+    sim.add_log_variable("stateEstimate.x", ValueType::F32);
+    sim.add_param("pid_rate.roll_kp", Value::F32(250.0), true);
+
+    let cf = Crazyflie::connect_from_transport_with_capture(sim.open_link(), NoTocCache, &path).await?;
+    let captured = session(&cf).await?;
+    cf.disconnect().await;
+
+    let records = CaptureReader::open(&path)?.collect::<crazyflie_lib::Result<Vec<_>>>()?;
+    assert_eq!(records[0].direction, Direction::Uplink);
+    assert!(records.iter().any(|r| r.direction == Direction::Downlink));
+    assert!(records.windows(2).all(|r| r[0].timestamp <= r[1].timestamp));
+
+    let cf = Crazyflie::connect_from_transport(ReplayTransport::open(&path)?, NoTocCache).await?;
+    let replayed = session(&cf).await?;
+    assert_eq!(replayed, captured);
+    assert_eq!(cf.wait_disconnect().await, "End of capture");
+
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
@@ -0,0 +1,1344 @@
+use crazyflie_lib::safety::{
+    BreachAction, Geofence, SafetyAction, SafetyEnvelope, SafetyLimits, SafetyViolation, ViolationPolicy,
+};
+use crazyflie_lib::simulation::SimulatedCrazyflie;
+use crazyflie_lib::subsystems::commander::{
+    FullState, Setpoint, SetpointFailsafe, SetpointStreamOptions,
+};
+use crazyflie_lib::subsystems::log::{
+    LogBlock, LogConfig, LogConfigVariable, LogPeriod, LogRecordFormat, LogRecorder,
+};
+use crazyflie_lib::subsystems::memory::{
+    DeckMemory, DeckUpgradeStatus, DeckUpgrader, MemoryType, RawMemory,
+};
+use crazyflie_lib::subsystems::param::{
+    ParamGroup, ParamSnapshot, PersistentParamProfile, TypedParamGroup,
+};
+use crazyflie_lib::{
+    ConnectionEvent, Crazyflie, CrtpTransport, Error, NoTocCache, ReconnectPolicy, Value, ValueType,
+};
+use futures::StreamExt;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn simulation() -> SimulatedCrazyflie {
+    let sim = SimulatedCrazyflie::new();
+    sim.add_log_variable("stateEstimate.x", ValueType::F32);
+    sim.add_log_variable_with("pm.vbat", ValueType::F32, Arc::new(|_| Value::F32(3.7)));
+    sim.add_log_variable("radio.rssi", ValueType::U8);
+    sim.add_param("pid_rate.roll_kp", Value::F32(250.0), true);
+    sim.add_param("deck.bcLighthouse4", Value::U8(0), false);
+    sim
+}
+
+#[tokio::test]
+async fn platform_versions() -> crazyflie_lib::Result<()> {
+    let sim = simulation();
+    sim.set_firmware_version("2025.02");
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    assert_eq!(cf.platform.firmware_version().await?, "2025.02");
+    assert_eq!(cf.platform.device_type_name().await?, "Simulated Crazyflie");
+    Ok(())
+}
+
+#[tokio::test]
+async fn unsupported_protocol_version() {
+    let sim = simulation();
+    sim.set_protocol_version(2);
+
+    let result = Crazyflie::connect_from_simulation(&sim, NoTocCache).await;
+    assert!(matches!(
+        result,
+        Err(Error::ProtocolVersionNotSupported { found: 2, .. })
+    ));
+}
+
+#[tokio::test]
+async fn log_block_data() -> crazyflie_lib::Result<()> {
+    let sim = simulation();
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    assert_eq!(cf.log.names().len(), 3);
+    assert_eq!(cf.log.get_type("radio.rssi")?, ValueType::U8);
+
+    let mut block = cf.log.create_block().await?;
+    block.add_variable("stateEstimate.x").await?;
+    block.add_variable("pm.vbat").await?;
+    let stream = block.start(LogPeriod::from_millis(10)?).await?;
+
+    let first = stream.next().await?;
+    let second = stream.next().await?;
+    assert!(second.timestamp > first.timestamp);
+    assert_eq!(second.data.len(), 2);
+    assert!(matches!(second.data["pm.vbat"], Value::F32(v) if v == 3.7));
+
+    let block = stream.stop().await?;
+    assert_eq!(sim.log_blocks().len(), 1);
+    drop(block);
+    Ok(())
+}
+
+#[tokio::test]
+async fn log_block_full() -> crazyflie_lib::Result<()> {
+    let sim = SimulatedCrazyflie::new();
+    for i in 0..7 {
+        sim.add_log_variable(&format!("test.var{}", i), ValueType::F32);
+    }
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    let mut block = cf.log.create_block().await?;
+    for i in 0..6 {
+        block.add_variable(&format!("test.var{}", i)).await?;
+    }
+    assert!(matches!(
+        block.add_variable("test.var6").await,
+        Err(Error::LogError(_))
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn log_block_lifecycle() -> crazyflie_lib::Result<()> {
+    let sim = simulation();
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    let mut block = cf.log.create_block().await?;
+    block.add_variable("stateEstimate.x").await?;
+    let mut stream = block.start(LogPeriod::from_millis(100)?).await?;
+    let blocks = cf.log.active_blocks().await;
+    assert_eq!(blocks.len(), 1);
+    assert_eq!(blocks[0].variables, ["stateEstimate.x"]);
+    assert_eq!(blocks[0].period, Some(LogPeriod::from_millis(100)?));
+
+    stream.set_period(LogPeriod::from_millis(10)?).await?;
+    assert_eq!(cf.log.active_blocks().await[0].period, Some(LogPeriod::from_millis(10)?));
+    let first = stream.next().await?;
+    let second = stream.next().await?;
+    assert!(second.timestamp - first.timestamp < 50);
+
+    stream.stop().await?.delete().await?;
+    assert!(sim.log_blocks().is_empty());
+    assert!(cf.log.active_blocks().await.is_empty());
+
+    // Dropped blocks are deleted in the background
+    let block = cf.log.create_block().await?;
+    assert_eq!(sim.log_blocks().len(), 1);
+    drop(block);
+    tokio::time::timeout(Duration::from_secs(1), async {
+        while !sim.log_blocks().is_empty() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("Log block not deleted");
+
+    // The block IDs are re-used
+    for _ in 0..300 {
+        cf.log.create_block().await?.delete().await?;
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn log_malformed_commands() -> crazyflie_lib::Result<()> {
+    let sim = simulation();
+    let link = sim.open_link();
+
+    // Append and start commands without block ID or with a missing or zero period are answered with EINVAL
+    for command in [vec![7], vec![3, 0], vec![3, 0, 0], vec![2]] {
+        link.send_packet(crazyflie_link::Packet::new(5, 1, command.clone())).await?;
+        let answer = link.recv_packet().await?;
+        assert_eq!(answer.get_data(), &vec![command[0], command.get(1).copied().unwrap_or(0), 22]);
+    }
+
+    // The simulator is still running
+    let cf = Crazyflie::connect_from_transport(link, NoTocCache).await?;
+    cf.log.create_block().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn log_config_files() -> crazyflie_lib::Result<()> {
+    let sim = simulation();
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    let directory = std::env::temp_dir().join(format!("crazyflie-lib-log-config-{}", std::process::id()));
+    std::fs::create_dir_all(&directory)?;
+
+    // File written by the cfclient
+    std::fs::write(
+        directory.join("Power.json"),
+        r#"{
+  "logconf": {
+    "logblock": {
+      "variables": [
+        {
+          "name": "pm.vbat",
+          "stored_as": "float",
+          "fetch_as": "float",
+          "type": "TOC"
+        },
+        {
+          "name": "radio.rssi",
+          "stored_as": "uint8_t",
+          "fetch_as": "uint8_t",
+          "type": "TOC"
+        }
+      ],
+      "name": "Power",
+      "period": 20
+    }
+  }
+}"#,
+    )?;
+    let configs = vec![LogConfig {
+        name: "Position".to_owned(),
+        period: LogPeriod::from_millis(10)?,
+        variables: vec![
+            LogConfigVariable::Toc("stateEstimate.x".to_owned()),
+            LogConfigVariable::Memory {
+                address: 0x2000_1000,
+                value_type: ValueType::U16,
+            },
+        ],
+    }];
+    cf.log.save_config(&directory, &configs)?;
+    let written: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(directory.join("Position.json"))?)
+        .map_err(|e| Error::InvalidArgument(e.to_string()))?;
+    assert_eq!(written["logconf"]["logblock"]["name"], "Position");
+
+    let read = LogConfig::read(&directory)?;
+    assert_eq!(read.len(), 2);
+    assert_eq!(read[0], configs[0]);
+    assert_eq!(read[1].name, "Power");
+    assert_eq!(read[1].period, LogPeriod::from_millis(20)?);
+
+    let blocks = cf.log.load_config(&directory).await?;
+    assert_eq!(blocks.len(), 2);
+    let mut blocks = blocks.into_iter();
+    let position = blocks.next().unwrap().start().await?;
+    let data = position.next().await?;
+    assert!(data.data.contains_key("stateEstimate.x") && data.data.contains_key("0x20001000"));
+    drop(blocks);
+
+    // Variables are validated against the TOC
+    let unknown = LogConfig {
+        name: "Unknown".to_owned(),
+        period: LogPeriod::from_millis(10)?,
+        variables: vec![LogConfigVariable::Toc("unknown.var".to_owned())],
+    };
+    assert!(cf.log.save_config(directory.join("unknown.json"), &[unknown]).is_err());
+    std::fs::write(
+        directory.join("Wrong.json"),
+        r#"{"logconf": {"logblock": {"variables": [
+            {"name": "pm.vbat", "stored_as": "uint8_t", "fetch_as": "uint8_t", "type": "TOC"}
+        ], "name": "Wrong", "period": 20}}}"#,
+    )?;
+    assert!(matches!(
+        cf.log.load_config(&directory).await,
+        Err(Error::LogError(_))
+    ));
+
+    std::fs::remove_dir_all(&directory)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn log_memory_variable() -> crazyflie_lib::Result<()> {
+    let sim = simulation();
+    sim.set_ram_value(0x2000_1000, Value::U16(1234));
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    let mut block = cf.log.create_block().await?;
+    block.add_variable("pm.vbat").await?;
+    block.add_memory_variable(0x2000_1000, ValueType::U16).await?;
+    block.add_memory_variable(0x2000_2000, ValueType::I8).await?;
+    assert!(matches!(
+        block.add_memory_variable(0x2000_3000, ValueType::U64).await,
+        Err(Error::LogError(_))
+    ));
+    let stream = block.start(LogPeriod::from_millis(10)?).await?;
+
+    let data = stream.next().await?;
+    assert_eq!(data.data.len(), 3);
+    assert!(matches!(data.data["pm.vbat"], Value::F32(v) if v == 3.7));
+    assert!(matches!(data.data["0x20001000"], Value::U16(1234)));
+    assert!(matches!(data.data["0x20002000"], Value::I8(0)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn log_memory_variable_packet() -> crazyflie_lib::Result<()> {
+    let sim = simulation();
+    sim.set_ram_value(0x2000_1000, Value::U16(1234));
+    let link = sim.open_link();
+
+    // Memory variable: u16 marked with the TOC ID 0xFFFF and followed by the address, then a TOC variable with the
+    // storage type in the upper nibble as sent by the python lib, pm.vbat has the TOC ID 1
+    let commands = [
+        vec![6, 0],
+        vec![7, 0, 0x22, 0xff, 0xff, 0x00, 0x10, 0x00, 0x20],
+        vec![7, 0, 0x77, 0x01, 0x00],
+        vec![3, 0, 1],
+    ];
+    for command in commands {
+        link.send_packet(crazyflie_link::Packet::new(5, 1, command.clone())).await?;
+        let answer = link.recv_packet().await?;
+        assert_eq!(answer.get_data(), &vec![command[0], 0, 0]);
+    }
+
+    let data = link.recv_packet().await?;
+    assert_eq!((data.get_port(), data.get_channel()), (5, 2));
+    let data = data.get_data();
+    assert_eq!(data.len(), 1 + 3 + 2 + 4);
+    assert_eq!(u16::from_le_bytes([data[4], data[5]]), 1234);
+    assert_eq!(f32::from_le_bytes(data[6..10].try_into().unwrap()), 3.7);
+    Ok(())
+}
+
+#[tokio::test]
+async fn log_stream_missed_samples() -> crazyflie_lib::Result<()> {
+    let sim = simulation();
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    let mut block = cf.log.create_block().await?;
+    block.add_variable("pm.vbat").await?;
+    let mut stream = block.start(LogPeriod::from_millis(50)?).await?;
+    assert_eq!(stream.period(), LogPeriod::from_millis(50)?);
+
+    let first = StreamExt::next(&mut stream).await.unwrap()?;
+    assert_eq!(first.missed_samples, 0);
+    sim.drop_log_packets(2);
+    let second = StreamExt::next(&mut stream).await.unwrap()?;
+    assert_eq!(second.missed_samples, 2);
+
+    let statistics = stream.statistics();
+    assert_eq!(statistics.received, 2);
+    assert_eq!(statistics.missed, 2);
+    assert_eq!(statistics.gaps, 1);
+
+    let samples: Vec<_> = stream.by_ref().take(2).collect().await;
+    assert!(samples.iter().all(|s| matches!(s, Ok(data) if data.missed_samples == 0)));
+
+    sim.disconnect("test");
+    assert!(stream.all(|s| async move { s.is_ok() }).await);
+    Ok(())
+}
+
+#[tokio::test]
+async fn log_recorder() -> crazyflie_lib::Result<()> {
+    let sim = simulation();
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    for format in [LogRecordFormat::Csv, LogRecordFormat::Mcap] {
+        let mut block = cf.log.create_block().await?;
+        block.add_variable("stateEstimate.x").await?;
+        block.add_variable("pm.vbat").await?;
+        let position = block.start(LogPeriod::from_millis(10)?).await?;
+        let mut block = cf.log.create_block().await?;
+        block.add_variable("radio.rssi").await?;
+        let radio = block.start(LogPeriod::from_millis(20)?).await?;
+
+        let path = std::env::temp_dir().join(format!("crazyflie-lib-recorder-{}", std::process::id()));
+        let recorder = LogRecorder::start(format, &path, [("position", position), ("radio, \"2\"", radio)])?;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let streams = recorder.stop().await?;
+        assert_eq!(streams.len(), 2);
+
+        let content = std::fs::read(&path)?;
+        std::fs::remove_file(&path)?;
+        if format == LogRecordFormat::Csv {
+            let content = String::from_utf8(content).unwrap();
+            let mut lines = content.lines();
+            assert_eq!(
+                lines.next(),
+                Some("host_time,block,timestamp,stateEstimate.x,pm.vbat,radio.rssi")
+            );
+            // The stream name is quoted
+            let rows: Vec<Vec<String>> = lines
+                .map(|line| line.replace("\"radio, \"\"2\"\"\"", "radio").split(',').map(str::to_owned).collect())
+                .collect();
+            assert!(rows.iter().all(|row| row.len() == 6));
+            assert!(rows.iter().any(|row| row[1] == "position" && row[4] == "3.7" && row[5].is_empty()));
+            assert!(rows.iter().any(|row| row[1] == "radio" && row[4].is_empty() && !row[5].is_empty()));
+        } else {
+            let magic = b"\x89MCAP0\r\n";
+            assert!(content.starts_with(magic) && content.ends_with(magic));
+
+            // Walk the records: opcode, length and content
+            let mut records = Vec::new();
+            let mut index = magic.len();
+            while index < content.len() - magic.len() {
+                let length = u64::from_le_bytes(content[index + 1..index + 9].try_into().unwrap());
+                let end = index + 9 + length as usize;
+                records.push((content[index], &content[index + 9..end]));
+                index = end;
+            }
+            assert_eq!(records.iter().filter(|(opcode, _)| *opcode == 0x03).count(), 2);
+            assert_eq!(records.iter().rev().nth(1).unwrap().0, 0x0F);
+            assert_eq!(records.last().unwrap().0, 0x02);
+
+            // First message of the position channel
+            let message = records
+                .iter()
+                .find(|(opcode, record)| *opcode == 0x05 && record[0..2] == [1, 0])
+                .unwrap()
+                .1;
+            let json: serde_json::Value = serde_json::from_slice(&message[22..]).unwrap();
+            assert!(json["timestamp"].is_u64());
+            assert_eq!(json["pm"]["vbat"], 3.7);
+        }
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn log_session() -> crazyflie_lib::Result<()> {
+    let sim = SimulatedCrazyflie::new();
+    let names: Vec<String> = (0..10).map(|i| format!("test.var{}", i)).collect();
+    for name in &names {
+        sim.add_log_variable(name, ValueType::F32);
+    }
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    let mut session = cf
+        .log
+        .start_session(&names, LogPeriod::from_millis(20)?)
+        .await?;
+    assert_eq!(session.block_count(), 2);
+
+    let first = session.next().await?;
+    let second = session.next().await?;
+    assert!(second.timestamp > first.timestamp);
+    assert_eq!(second.data.len(), 10);
+    assert!(names.iter().all(|name| second.data.contains_key(name)));
+
+    // Stalled blocks make the session time out instead of hanging
+    sim.drop_log_packets(usize::MAX);
+    assert!(matches!(session.next().await, Err(Error::Timeout)));
+    sim.drop_log_packets(0);
+    let sample = session.next().await?;
+    assert!(sample.missed_samples > 0);
+    session.stop().await?;
+
+    assert!(matches!(
+        cf.log
+            .start_session(&["unknown.var"], LogPeriod::from_millis(20)?)
+            .await,
+        Err(Error::ParamError(_))
+    ));
+    Ok(())
+}
+
+#[derive(LogBlock)]
+struct Power {
+    #[log(timestamp)]
+    timestamp: u32,
+    #[log("pm.vbat")]
+    vbat: f32,
+    #[log("radio.rssi")]
+    rssi: u8,
+}
+
+#[derive(LogBlock)]
+struct WrongType {
+    #[log("pm.vbat")]
+    _vbat: u32,
+}
+
+#[tokio::test]
+async fn typed_log_block() -> crazyflie_lib::Result<()> {
+    let sim = simulation();
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    let stream = cf
+        .log
+        .start_typed_block::<Power>(LogPeriod::from_millis(10)?)
+        .await?;
+    let first = stream.next().await?;
+    let second = stream.next().await?;
+    assert!(second.timestamp > first.timestamp);
+    assert_eq!(second.vbat, 3.7);
+    let _ = second.rssi;
+    stream.stop().await?;
+
+    assert!(matches!(
+        cf.log
+            .start_typed_block::<WrongType>(LogPeriod::from_millis(10)?)
+            .await,
+        Err(Error::LogError(_))
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn param_get_and_set() -> crazyflie_lib::Result<()> {
+    let sim = simulation();
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    assert!(cf.param.is_writable("pid_rate.roll_kp")?);
+    assert!(!cf.param.is_writable("deck.bcLighthouse4")?);
+
+    let kp: f32 = cf.param.get("pid_rate.roll_kp").await?;
+    assert_eq!(kp, 250.0);
+
+    cf.param.set("pid_rate.roll_kp", 200.0f32).await?;
+    assert!(matches!(sim.param("pid_rate.roll_kp"), Some(Value::F32(v)) if v == 200.0));
+
+    assert!(matches!(
+        cf.param.set("deck.bcLighthouse4", 1u8).await,
+        Err(Error::ParamError(_))
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn param_update_from_crazyflie() -> crazyflie_lib::Result<()> {
+    let sim = simulation();
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    let mut changes = cf.param.watch_change().await?;
+    sim.set_param("deck.bcLighthouse4", Value::U8(1))?;
+
+    let (name, value) = changes.next().await.unwrap();
+    assert_eq!(name, "deck.bcLighthouse4");
+    assert!(matches!(value, Value::U8(1)));
+
+    let value: u8 = cf.param.get("deck.bcLighthouse4").await?;
+    assert_eq!(value, 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn param_watch() -> crazyflie_lib::Result<()> {
+    let sim = simulation();
+    sim.add_param("deck.bcFlow2", Value::U8(0), false);
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    let mut kp = cf.param.watch("pid_rate.roll_kp").await?;
+    let mut decks = cf.param.watch_group("deck").await?;
+
+    // The streams start with the current values
+    assert!(matches!(kp.next().await, Some(Value::F32(v)) if v == 250.0));
+    let (name, _) = decks.next().await.unwrap();
+    assert_eq!(name, "deck.bcFlow2");
+    let (name, _) = decks.next().await.unwrap();
+    assert_eq!(name, "deck.bcLighthouse4");
+
+    sim.set_param("deck.bcLighthouse4", Value::U8(1))?;
+    cf.param.set("pid_rate.roll_kp", 200.0f32).await?;
+    assert!(matches!(kp.next().await, Some(Value::F32(v)) if v == 200.0));
+    let (name, value) = decks.next().await.unwrap();
+    assert_eq!(name, "deck.bcLighthouse4");
+    assert!(matches!(value, Value::U8(1)));
+
+    // Changes that are not notified by the Crazyflie are polled and seen by all the watchers
+    let mut lighthouse = cf.param.watch("deck.bcLighthouse4").await?;
+    assert!(matches!(lighthouse.next().await, Some(Value::U8(1))));
+    let (detected, _) = tokio::join!(
+        cf.param.wait_for("deck.bcLighthouse4", |detected: &u8| *detected == 0, Duration::from_secs(1)),
+        async {
+            sim.set_param_silently("deck.bcLighthouse4", Value::U8(0)).unwrap();
+            assert!(matches!(lighthouse.next().await, Some(Value::U8(0))));
+            let (name, value) = decks.next().await.unwrap();
+            assert_eq!(name, "deck.bcLighthouse4");
+            assert!(matches!(value, Value::U8(0)));
+        }
+    );
+    assert_eq!(detected?, 0u8);
+
+    assert!(matches!(cf.param.watch("deck.unknown").await, Err(Error::ParamError(_))));
+    assert!(matches!(cf.param.watch_group("dec").await, Err(Error::ParamError(_))));
+    Ok(())
+}
+
+#[tokio::test]
+async fn param_watch_poll_interval() -> crazyflie_lib::Result<()> {
+    let sim = simulation();
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    let mut lighthouse = cf.param.watch("deck.bcLighthouse4").await?;
+    assert!(matches!(lighthouse.next().await, Some(Value::U8(0))));
+
+    // Without polling, only the notified changes are seen
+    cf.param.set_watch_poll_interval(None).await?;
+    sim.set_param_silently("deck.bcLighthouse4", Value::U8(1))?;
+    let polled = tokio::time::timeout(Duration::from_millis(300), lighthouse.next()).await;
+    assert!(polled.is_err());
+
+    cf.param.set_watch_poll_interval(Some(Duration::from_millis(20))).await?;
+    let polled = tokio::time::timeout(Duration::from_millis(300), lighthouse.next()).await;
+    assert!(matches!(polled, Ok(Some(Value::U8(1)))));
+
+    assert!(matches!(
+        cf.param.set_watch_poll_interval(Some(Duration::ZERO)).await,
+        Err(Error::InvalidArgument(_))
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn param_wait_for() -> crazyflie_lib::Result<()> {
+    let sim = simulation();
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    let timeout = Duration::from_millis(500);
+    let result = cf
+        .param
+        .wait_for("deck.bcLighthouse4", |detected: &u8| *detected == 1, Duration::from_millis(50))
+        .await;
+    assert!(matches!(result, Err(Error::Timeout)));
+
+    let booting = sim.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        booting.set_param("deck.bcLighthouse4", Value::U8(1)).unwrap();
+    });
+    let detected: u8 = cf
+        .param
+        .wait_for("deck.bcLighthouse4", |detected: &u8| *detected == 1, timeout)
+        .await?;
+    assert_eq!(detected, 1);
+
+    // Read-only parameters are changed by the firmware without notification
+    let booting = sim.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        booting.set_param_silently("deck.bcLighthouse4", Value::U8(0)).unwrap();
+    });
+    let detected: u8 = cf
+        .param
+        .wait_for("deck.bcLighthouse4", |detected: &u8| *detected == 0, timeout)
+        .await?;
+    assert_eq!(detected, 0);
+
+    // Already satisfied
+    let kp: f32 = cf.param.wait_for("pid_rate.roll_kp", |kp: &f32| *kp > 100.0, timeout).await?;
+    assert_eq!(kp, 250.0);
+
+    // The wait ends when the Crazyflie is disconnected
+    let (result, _) = tokio::join!(
+        cf.param.wait_for("deck.bcLighthouse4", |detected: &u8| *detected == 2, Duration::from_secs(5)),
+        async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            sim.disconnect("Simulated link loss");
+        }
+    );
+    assert!(matches!(result, Err(Error::Disconnected)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn param_get_many() -> crazyflie_lib::Result<()> {
+    let sim = simulation();
+    for i in 0..40 {
+        sim.add_param(&format!("bulk.param{}", i), Value::U16(i * 10), i % 2 == 0);
+    }
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    // Mix of cached and not yet read values
+    cf.param.set("bulk.param4", 1234u16).await?;
+    let names = ["bulk.param4", "bulk.param5", "pid_rate.roll_kp", "bulk.param5"];
+    let values = cf.param.get_many(&names).await?;
+    assert_eq!(values.len(), 3);
+    assert!(matches!(values["bulk.param4"], Value::U16(1234)));
+    assert!(matches!(values["bulk.param5"], Value::U16(50)));
+    assert!(matches!(values["pid_rate.roll_kp"], Value::F32(v) if v == 250.0));
+
+    let values = cf.param.get_all().await?;
+    assert_eq!(values.len(), 42);
+    for i in 0..40 {
+        let expected = if i == 4 { 1234 } else { i * 10 };
+        assert!(matches!(values[&format!("bulk.param{}", i)], Value::U16(v) if v == expected));
+    }
+    let value: u16 = cf.param.get("bulk.param39").await?;
+    assert_eq!(value, 390);
+
+    assert!(matches!(
+        cf.param.get_many(&["bulk.param1", "bulk.unknown"]).await,
+        Err(Error::ParamError(_))
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn param_groups() -> crazyflie_lib::Result<()> {
+    let sim = simulation();
+    sim.add_param("pid_rate.pitch_kp", Value::F32(250.0), true);
+    sim.add_param("pid_attitude.roll_kp", Value::F32(6.0), true);
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    assert_eq!(cf.param.groups(), vec!["deck", "pid_attitude", "pid_rate"]);
+    assert_eq!(
+        cf.param.names_matching("pid_rate.*"),
+        vec!["pid_rate.pitch_kp", "pid_rate.roll_kp"]
+    );
+    assert_eq!(
+        cf.param.names_matching("pid_*.roll_kp"),
+        vec!["pid_attitude.roll_kp", "pid_rate.roll_kp"]
+    );
+    assert!(cf.param.names_matching("kalman.*").is_empty());
+
+    let entries = cf.param.group("pid_rate").await?;
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[1].group, "pid_rate");
+    assert_eq!(entries[1].name, "roll_kp");
+    assert_eq!(entries[1].full_name(), "pid_rate.roll_kp");
+    assert_eq!(entries[1].value_type, ValueType::F32);
+    assert!(entries[1].writable);
+    assert!(!entries[1].persistent);
+    assert!(matches!(entries[1].value, Value::F32(v) if v == 250.0));
+
+    let entries = cf.param.group("deck").await?;
+    assert!(!entries[0].writable);
+    assert!(matches!(
+        cf.param.group("pid").await,
+        Err(Error::ParamError(_))
+    ));
+    Ok(())
+}
+
+#[derive(ParamGroup)]
+#[param(group = "pid_rate")]
+struct PidRate {
+    roll_kp: f32,
+    #[param(name = "pitch_kp")]
+    pitch: f32,
+}
+
+#[derive(ParamGroup)]
+#[param(group = "deck")]
+struct Decks {
+    #[param(name = "bcLighthouse4")]
+    lighthouse: u8,
+}
+
+#[derive(ParamGroup)]
+#[param(group = "pid_rate")]
+struct WrongParamType {
+    _roll_kp: u16,
+}
+
+#[tokio::test]
+async fn typed_param_group() -> crazyflie_lib::Result<()> {
+    let sim = simulation();
+    sim.add_param("pid_rate.pitch_kp", Value::F32(240.0), true);
+    sim.add_param("pid_rate.yaw_kp", Value::F32(120.0), true);
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    let mut pid = PidRate::read(&cf.param).await?;
+    assert_eq!(pid.roll_kp, 250.0);
+    assert_eq!(pid.pitch, 240.0);
+
+    let mut changes = PidRate::watch(&cf.param).await?;
+    assert_eq!(changes.next().await.unwrap().pitch, 240.0);
+
+    pid.pitch = 200.0;
+    pid.write(&cf.param).await?;
+    assert!(matches!(sim.param("pid_rate.pitch_kp"), Some(Value::F32(v)) if v == 200.0));
+    // Only pitch_kp has changed, roll_kp is written with the same value
+    let pid = changes.next().await.unwrap();
+    assert_eq!((pid.roll_kp, pid.pitch), (250.0, 240.0));
+    let pid = changes.next().await.unwrap();
+    assert_eq!((pid.roll_kp, pid.pitch), (250.0, 200.0));
+
+    // Parameters of the group that are not in the struct do not generate values
+    cf.param.set("pid_rate.yaw_kp", 100.0f32).await?;
+    sim.set_param("pid_rate.roll_kp", Value::F32(260.0))?;
+    let pid = changes.next().await.unwrap();
+    assert_eq!((pid.roll_kp, pid.pitch), (260.0, 200.0));
+
+    let decks = Decks::read(&cf.param).await?;
+    assert_eq!(decks.lighthouse, 0);
+    assert!(matches!(decks.write(&cf.param).await, Err(Error::ParamError(_))));
+    assert!(matches!(WrongParamType::read(&cf.param).await, Err(Error::ParamError(_))));
+    Ok(())
+}
+
+#[tokio::test]
+async fn param_snapshot_and_restore() -> crazyflie_lib::Result<()> {
+    let sim = simulation();
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    let snapshot = cf.param.snapshot().await?;
+    assert_eq!(snapshot.values.len(), 2);
+    assert!(cf.param.diff_against_defaults().await?.is_empty());
+
+    // The snapshot survives a round trip through JSON
+    let json = serde_json::to_string(&snapshot).unwrap();
+    let snapshot: ParamSnapshot = serde_json::from_str(&json).unwrap();
+
+    cf.param.set("pid_rate.roll_kp", 200.0f32).await?;
+    sim.set_param("deck.bcLighthouse4", Value::U8(1))?;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let differences = snapshot.diff(&cf.param.snapshot().await?);
+    assert_eq!(differences.len(), 2);
+    assert_eq!(differences[0].name, "deck.bcLighthouse4");
+    assert!(matches!(differences[1].old, Some(Value::F32(v)) if v == 250.0));
+    assert!(matches!(differences[1].new, Some(Value::F32(v)) if v == 200.0));
+
+    let differences = cf.param.diff_against_defaults().await?;
+    assert_eq!(differences.len(), 1);
+    assert_eq!(differences[0].name, "pid_rate.roll_kp");
+
+    // The read-only parameter cannot be restored
+    let report = cf.param.restore(&snapshot).await?;
+    assert_eq!(report.written, vec!["pid_rate.roll_kp".to_owned()]);
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(report.failed[0].0, "deck.bcLighthouse4");
+    assert!(matches!(sim.param("pid_rate.roll_kp"), Some(Value::F32(v)) if v == 250.0));
+
+    let report = cf.param.restore(&snapshot).await?;
+    assert!(report.written.is_empty());
+    assert_eq!(report.unchanged, 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn param_persistent_export_and_import() -> crazyflie_lib::Result<()> {
+    let tuned = simulation();
+    tuned.add_persistent_param("ring.effect", Value::U8(0));
+    tuned.add_persistent_param("led.bitmask", Value::U8(0));
+    let cf = Crazyflie::connect_from_simulation(&tuned, NoTocCache).await?;
+
+    cf.param.set("ring.effect", 10u8).await?;
+    cf.param.persistent_store("ring.effect").await?;
+    assert!(matches!(tuned.stored_param("ring.effect"), Some(Value::U8(10))));
+
+    let profile = cf.param.persistent_export().await?;
+    assert_eq!(profile.values.len(), 2);
+    assert!(profile.values["led.bitmask"].is_none());
+    assert!(matches!(profile.values["ring.effect"], Some(Value::U8(10))));
+
+    let json = serde_json::to_string(&profile).unwrap();
+    let mut profile: PersistentParamProfile = serde_json::from_str(&json).unwrap();
+    profile.values.insert("ring.unknown".to_owned(), Some(Value::U8(1)));
+
+    let replacement = simulation();
+    replacement.add_persistent_param("ring.effect", Value::U8(0));
+    replacement.add_persistent_param("led.bitmask", Value::U8(0));
+    let cf = Crazyflie::connect_from_simulation(&replacement, NoTocCache).await?;
+    cf.param.set("led.bitmask", 5u8).await?;
+    cf.param.persistent_store("led.bitmask").await?;
+
+    let report = cf.param.persistent_import(&profile, true).await?;
+    assert_eq!(report.stored, vec!["ring.effect".to_owned()]);
+    assert_eq!(report.cleared, vec!["led.bitmask".to_owned()]);
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(report.failed[0].0, "ring.unknown");
+    assert!(replacement.stored_param("ring.effect").is_none());
+    assert!(matches!(replacement.stored_param("led.bitmask"), Some(Value::U8(5))));
+
+    let report = cf.param.persistent_import(&profile, false).await?;
+    assert_eq!(report.stored, vec!["ring.effect".to_owned()]);
+    assert_eq!(report.cleared, vec!["led.bitmask".to_owned()]);
+    assert!(matches!(replacement.stored_param("ring.effect"), Some(Value::U8(10))));
+    assert!(matches!(replacement.param("ring.effect"), Some(Value::U8(10))));
+    assert!(replacement.stored_param("led.bitmask").is_none());
+
+    let report = cf.param.persistent_import(&profile, false).await?;
+    assert_eq!(report.unchanged, 2);
+    assert!(report.stored.is_empty() && report.cleared.is_empty());
+    Ok(())
+}
+
+/// Type of the generic setpoints in recorded commander packets, `None` for the other packets
+fn setpoint_types(packets: &[crazyflie_link::Packet]) -> Vec<Option<u8>> {
+    packets
+        .iter()
+        .map(|packet| match (packet.get_port(), packet.get_channel()) {
+            (7, 0) => Some(packet.get_data()[0]),
+            _ => None,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn setpoint_stream() -> crazyflie_lib::Result<()> {
+    let sim = simulation();
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    assert!(matches!(cf.commander.stream(0.0), Err(Error::InvalidArgument(_))));
+
+    let stream = cf.commander.stream(100.0)?;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(sim.take_commander_packets().is_empty());
+
+    stream.set(Setpoint::Hover { vx: 0.0, vy: 0.0, yawrate: 0.0, zdistance: 0.5 })?;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let packets = sim.take_commander_packets();
+    assert!(packets.len() >= 10, "only {} setpoints sent", packets.len());
+    // Hover setpoints
+    assert!(setpoint_types(&packets).iter().all(|t| *t == Some(10)));
+
+    // An invalid setpoint is rejected and the stream keeps the previous target
+    let invalid = Setpoint::Cppm { roll: 1500, pitch: 1500, yaw: 1500, thrust: 1000, aux: vec![1000; 11] };
+    assert!(matches!(stream.set(invalid), Err(Error::InvalidArgument(_))));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(stream.is_active());
+    let packets = sim.take_commander_packets();
+    assert!(!packets.is_empty());
+    assert!(setpoint_types(&packets).iter().all(|t| *t == Some(10)));
+
+    stream.stop().await?;
+    let packets = sim.take_commander_packets();
+    let notify = packets.last().unwrap();
+    assert_eq!((notify.get_port(), notify.get_channel(), notify.get_data()[0]), (7, 1, 0));
+
+    // Dropping the stream also hands the control back
+    let stream = cf.commander.stream(100.0)?;
+    stream.set(Setpoint::Position { x: 0.0, y: 0.0, z: 1.0, yaw: 0.0 })?;
+    drop(stream);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let packets = sim.take_commander_packets();
+    assert_eq!(setpoint_types(&packets), vec![Some(7), None]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn setpoint_stream_stale_landing() -> crazyflie_lib::Result<()> {
+    let sim = simulation();
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    let options = SetpointStreamOptions {
+        failsafe: SetpointFailsafe::HoverThenLand {
+            hover_duration: Duration::from_millis(100),
+            landing_velocity: 2.0,
+        },
+        stale_timeout: Some(Duration::from_millis(100)),
+    };
+    let stream = cf.commander.stream_with_options(50.0, options)?;
+    stream.set(Setpoint::Position { x: 1.0, y: 2.0, z: 0.4, yaw: 0.0 })?;
+
+    for _ in 0..100 {
+        if !stream.is_active() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    // The failsafe is still running, new targets are rejected
+    assert!(!stream.is_active());
+    assert!(stream.set(Setpoint::Stop).is_err());
+    assert!(matches!(stream.stop().await, Err(Error::Timeout)));
+
+    let packets = sim.take_commander_packets();
+    let types = setpoint_types(&packets);
+    // Position setpoints, then stop setpoint and notify setpoint stop
+    assert_eq!(types[types.len() - 2..], [Some(0), None]);
+    let heights: Vec<f32> = packets[..packets.len() - 2]
+        .iter()
+        .map(|packet| f32::from_le_bytes(packet.get_data()[9..13].try_into().unwrap()))
+        .collect();
+    assert!(heights.windows(2).all(|h| h[1] <= h[0]));
+    assert_eq!(heights[0], 0.4);
+    assert_eq!(*heights.last().unwrap(), 0.0);
+    let x = f32::from_le_bytes(packets[0].get_data()[1..5].try_into().unwrap());
+    assert_eq!(x, 1.0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn full_state_and_legacy_setpoints() -> crazyflie_lib::Result<()> {
+    let sim = simulation();
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    let state = FullState {
+        position: [1.0, -2.0, 0.5],
+        velocity: [0.25, 0.0, -0.1],
+        acceleration: [0.0, 9.81, 0.0],
+        orientation: [0.0, 0.0, 0.0, 1.0],
+        angular_rates: [0.0, 0.0, 90.0],
+    };
+    cf.commander.send_setpoint(Setpoint::FullState(state)).await?;
+    cf.commander.setpoint_alt_hold(1.0, 2.0, 10.0, 0.3).await?;
+    cf.commander.setpoint_cppm(1500, 1500, 1500, 1000, &[2000, 1000]).await?;
+    assert!(matches!(
+        cf.commander.setpoint_cppm(1500, 1500, 1500, 1000, &[1000; 11]).await,
+        Err(Error::InvalidArgument(_))
+    ));
+    let out_of_range = FullState { position: [40.0, 0.0, 0.0], ..state };
+    assert!(matches!(
+        cf.commander.setpoint_full_state(&out_of_range).await,
+        Err(Error::InvalidArgument(_))
+    ));
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let packets = sim.take_commander_packets();
+    assert_eq!(setpoint_types(&packets), vec![Some(6), Some(4), Some(3)]);
+
+    let data = packets[0].get_data();
+    assert_eq!(data.len(), 29);
+    let i16_at = |i: usize| i16::from_le_bytes(data[1 + 2 * i..3 + 2 * i].try_into().unwrap());
+    assert_eq!((0..9).map(i16_at).collect::<Vec<_>>(), [1000, -2000, 500, 250, 0, -100, 0, 9810, 0]);
+    assert_eq!(u32::from_le_bytes(data[19..23].try_into().unwrap()), 3 << 30);
+    // 90 degrees/second in milliradians/second
+    assert_eq!(i16::from_le_bytes(data[27..29].try_into().unwrap()), 1571);
+
+    let data = packets[1].get_data();
+    let floats: Vec<f32> = data[1..]
+        .chunks(4)
+        .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+        .collect();
+    assert_eq!(floats, [1.0, 2.0, -10.0, 0.3]);
+
+    let data = packets[2].get_data();
+    assert_eq!(data[1], 2);
+    let channels: Vec<u16> = data[2..]
+        .chunks(2)
+        .map(|b| u16::from_le_bytes(b.try_into().unwrap()))
+        .collect();
+    assert_eq!(channels, [1500, 1500, 1500, 1000, 2000, 1000]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn safety_envelope() -> crazyflie_lib::Result<()> {
+    let sim = simulation();
+    sim.add_log_variable("stateEstimate.y", ValueType::F32);
+    sim.add_log_variable("stateEstimate.z", ValueType::F32);
+    for (name, value) in [("stateEstimate.x", 0.0), ("stateEstimate.y", 0.0), ("stateEstimate.z", 0.5)] {
+        sim.set_log_value(name, Value::F32(value));
+    }
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    let limits = SafetyLimits {
+        geofence: Some(Geofence::Box { min: [-1.0, -1.0, 0.0], max: [1.0, 1.0, 2.0] }),
+        max_altitude: Some(1.5),
+        max_velocity: Some(1.0),
+        on_breach: BreachAction::Land { duration: 2.0 },
+        monitor_period: Some(Duration::from_millis(20)),
+        ..Default::default()
+    };
+    let envelope = SafetyEnvelope::new(&cf, limits.clone()).await?;
+    let mut reports = envelope.reports();
+
+    let violations = envelope
+        .send_setpoint(Setpoint::Position { x: 3.0, y: 0.0, z: 1.8, yaw: 0.0 })
+        .await?;
+    assert_eq!(violations.len(), 2);
+    let violations = envelope
+        .send_setpoint(Setpoint::VelocityWorld { vx: 0.0, vy: 2.0, vz: 0.0, yawrate: 0.0 })
+        .await?;
+    assert_eq!(violations, vec![SafetyViolation::Velocity { velocity: 2.0 }]);
+    assert!(envelope.send_setpoint(Setpoint::Stop).await?.is_empty());
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let packets = sim.take_commander_packets();
+    assert_eq!(setpoint_types(&packets), vec![Some(7), Some(8), Some(0)]);
+    let floats = |data: &[u8]| -> Vec<f32> {
+        data[1..17].chunks(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect()
+    };
+    assert_eq!(floats(packets[0].get_data()), [1.0, 0.0, 1.5, 0.0]);
+    assert_eq!(floats(packets[1].get_data()), [0.0, 1.0, 0.0, 0.0]);
+
+    let report = reports.next().await.unwrap();
+    assert_eq!(report.action, SafetyAction::Clamped);
+    assert!(matches!(report.violation, SafetyViolation::Geofence { .. }));
+
+    // The go_to duration is extended to stay under the maximum velocity
+    assert_eq!(envelope.position(), Some([0.0, 0.0, 0.5]));
+    envelope.go_to(0.0, 1.0, 0.0, 0.0, 0.5, true, false, None).await?;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let packets = sim.take_commander_packets();
+    let duration = f32::from_le_bytes(packets[0].get_data()[20..24].try_into().unwrap());
+    assert_eq!(duration, 1.0);
+
+    // Invalid limits and durations are rejected
+    let zero_velocity = SafetyLimits { max_velocity: Some(0.0), ..limits.clone() };
+    assert!(matches!(SafetyEnvelope::new(&cf, zero_velocity).await, Err(Error::InvalidArgument(_))));
+    for duration in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+        assert!(matches!(envelope.take_off(1.0, None, duration, None).await, Err(Error::InvalidArgument(_))));
+        assert!(matches!(
+            envelope.go_to(0.0, 0.5, 0.0, 0.0, duration, true, false, None).await,
+            Err(Error::InvalidArgument(_))
+        ));
+    }
+
+    // Non-finite values cannot be checked against the limits and are rejected whatever the policy
+    let altitude_only = SafetyEnvelope::new(
+        &cf,
+        SafetyLimits { max_altitude: Some(1.5), max_tilt: Some(10.0), monitor_period: None, ..Default::default() },
+    )
+    .await?;
+    let non_finite = [
+        (&altitude_only, Setpoint::Position { x: 0.0, y: 0.0, z: f32::NAN, yaw: 0.0 }),
+        (&envelope, Setpoint::Position { x: f32::NAN, y: 0.0, z: 1.0, yaw: 0.0 }),
+        (&envelope, Setpoint::VelocityWorld { vx: f32::INFINITY, vy: 0.0, vz: 0.0, yawrate: 0.0 }),
+        (&envelope, Setpoint::Hover { vx: f32::NAN, vy: 0.0, yawrate: 0.0, zdistance: 0.5 }),
+        (&altitude_only, Setpoint::Rpyt { roll: f32::NAN, pitch: 0.0, yawrate: 0.0, thrust: 0 }),
+    ];
+    for (envelope, setpoint) in non_finite {
+        assert!(matches!(envelope.send_setpoint(setpoint).await, Err(Error::InvalidArgument(_))));
+    }
+    assert!(matches!(
+        envelope.go_to(f32::NAN, 0.0, 1.0, 0.0, 1.0, false, false, None).await,
+        Err(Error::InvalidArgument(_))
+    ));
+    assert!(matches!(
+        envelope.go_to(0.0, 0.0, f32::INFINITY, 0.0, 1.0, true, false, None).await,
+        Err(Error::InvalidArgument(_))
+    ));
+    assert!(matches!(
+        altitude_only.take_off(f32::NAN, None, 1.0, None).await,
+        Err(Error::InvalidArgument(_))
+    ));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(sim.take_commander_packets().is_empty());
+
+    // Rejected commands are not sent
+    let strict = SafetyEnvelope::new(
+        &cf,
+        SafetyLimits { on_violation: ViolationPolicy::Reject, monitor_period: None, ..limits },
+    )
+    .await?;
+    let result = strict.send_setpoint(Setpoint::Position { x: 0.0, y: 0.0, z: 1.8, yaw: 0.0 }).await;
+    assert!(matches!(result, Err(Error::SafetyError(_))));
+    assert!(matches!(
+        strict.go_to(0.0, 0.0, 1.0, 0.0, 1.0, true, false, None).await,
+        Err(Error::SafetyError(_))
+    ));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(sim.take_commander_packets().is_empty());
+
+    // Leaving the geofence lands the Crazyflie
+    sim.set_log_value("stateEstimate.x", Value::F32(1.5));
+    let report = loop {
+        let report = reports.next().await.unwrap();
+        if report.action == SafetyAction::Land {
+            break report;
+        }
+    };
+    assert_eq!(report.violation, SafetyViolation::Geofence { position: [1.5, 0.0, 0.5] });
+    assert!(envelope.is_tripped());
+    assert!(matches!(envelope.send_setpoint(Setpoint::Stop).await, Err(Error::SafetyError(_))));
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let packets = sim.take_commander_packets();
+    let commands: Vec<_> = packets
+        .iter()
+        .map(|packet| (packet.get_port(), packet.get_channel(), packet.get_data()[0]))
+        .collect();
+    // Notify setpoint stop, then high-level land
+    assert_eq!(commands, vec![(7, 1, 0), (8, 0, 8)]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn memory_read_and_write() -> crazyflie_lib::Result<()> {
+    let sim = SimulatedCrazyflie::new();
+    let content: Vec<u8> = (0..100).collect();
+    let memory_id = sim.add_memory(MemoryType::MemoryTester, content.clone());
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    let memories = cf.memory.get_memories(Some(MemoryType::MemoryTester));
+    assert_eq!(memories.len(), 1);
+    assert_eq!(memories[0].size, 100);
+
+    let memory: RawMemory = cf
+        .memory
+        .open_memory(memories[0].clone())
+        .await
+        .unwrap()?;
+    assert_eq!(memory.read(0, 100).await?, content);
+
+    memory.write(50, &[0xAA; 40]).await?;
+    assert_eq!(sim.memory(memory_id).unwrap()[50..90], [0xAA; 40]);
+
+    assert!(matches!(
+        memory.read(90, 20).await,
+        Err(Error::MemoryError(_))
+    ));
+    Ok(())
+}
+
+/// Deck memory info of one section: valid, read, write, upgrade, upgrade required and bootloader active
+fn deck_section_info(name: &str, image: &[u8], base_address: u32) -> Vec<u8> {
+    let mut info = vec![0x7D, 0x03];
+    info.extend_from_slice(&crc32fast::hash(image).to_le_bytes());
+    info.extend_from_slice(&(image.len() as u32).to_le_bytes());
+    info.extend_from_slice(&base_address.to_le_bytes());
+    info.extend_from_slice(name.as_bytes());
+    info.resize(0x20, 0);
+    info
+}
+
+#[tokio::test]
+async fn deck_upgrade() -> crazyflie_lib::Result<()> {
+    let ai_image: Vec<u8> = (0..300).map(|i| i as u8).collect();
+    let lh_image = vec![0x42; 200];
+
+    let mut deck_memory = vec![0; 0x4000];
+    deck_memory[0] = 3;
+    deck_memory[0x01..0x21].copy_from_slice(&deck_section_info("bcAI:gap8", &ai_image, 0x2000));
+    deck_memory[0x21..0x41].copy_from_slice(&deck_section_info("bcLighthouse4", &lh_image, 0x3000));
+    // Bootloader not active and no reset to bootloader capability
+    let mut dwm_info = deck_section_info("bcDWM1k", &lh_image, 0x3800);
+    dwm_info[0] &= !0x40;
+    dwm_info[1] &= !0x02;
+    deck_memory[0x41..0x61].copy_from_slice(&dwm_info);
+
+    let sim = SimulatedCrazyflie::new();
+    let memory_id = sim.add_memory(MemoryType::DeckMemory, deck_memory);
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    let device = cf.memory.get_memories(Some(MemoryType::DeckMemory))[0].clone();
+    let deck_memory: DeckMemory = cf.memory.open_memory(device).await.unwrap()?;
+
+    let mut upgrader = DeckUpgrader::new();
+    upgrader
+        .add_image("bcAI:gap8", &ai_image)
+        .add_image("bcLighthouse4", &[0x43; 200])
+        .add_image("bcDWM1k", &lh_image)
+        .add_image("bcFlow2", &[0; 10]);
+    let mut progress = Vec::new();
+    let reports = upgrader
+        .upgrade(&deck_memory, |name, done, total| {
+            progress.push((name.to_owned(), done, total))
+        })
+        .await;
+
+    let status: Vec<_> = reports.iter().map(|r| (r.name.as_str(), &r.status)).collect();
+    assert!(matches!(
+        status[..],
+        [
+            ("bcAI:gap8", DeckUpgradeStatus::Upgraded),
+            ("bcDWM1k", DeckUpgradeStatus::Failed(Error::MemoryError(_))),
+            ("bcFlow2", DeckUpgradeStatus::Skipped(_)),
+            ("bcLighthouse4", DeckUpgradeStatus::Failed(Error::InvalidArgument(_))),
+        ]
+    ));
+    assert_eq!(progress.last(), Some(&("bcAI:gap8".to_owned(), 300, 300)));
+
+    let content = sim.memory(memory_id).unwrap();
+    assert_eq!(content[0x2000..0x2000 + 300], ai_image);
+    assert_eq!(content[0x3000..0x3000 + 200], [0; 200]);
+    assert_eq!(content[0x3800..0x3800 + 200], [0; 200]);
+    // Reset to firmware command
+    assert_eq!(content[0x1004], 0x01);
+    Ok(())
+}
+
+#[tokio::test]
+async fn link_service() -> crazyflie_lib::Result<()> {
+    let sim = SimulatedCrazyflie::new();
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    cf.link_service.ping().await?;
+    cf.link_service.test_echo_bandwidth(10).await?;
+    cf.link_service.test_downlink_bandwidth(10).await?;
+    assert!(cf.link_service.get_statistics().await.link_quality.is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn connection_loss() -> crazyflie_lib::Result<()> {
+    let sim = simulation();
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    let mut block = cf.log.create_block().await?;
+    block.add_variable("stateEstimate.x").await?;
+    let stream = block.start(LogPeriod::from_millis(10)?).await?;
+    stream.next().await?;
+
+    sim.disconnect("Simulated link loss");
+    assert_eq!(cf.wait_disconnect().await, "Simulated link loss");
+
+    assert!(matches!(
+        cf.param.set("pid_rate.roll_kp", 1.0f32).await,
+        Err(Error::Disconnected)
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn reconnection() -> crazyflie_lib::Result<()> {
+    let sim = simulation();
+    let sim_link = sim.clone();
+    let cf = Crazyflie::connect_with_reconnect(
+        move || {
+            let link = sim_link.open_link();
+            async move { Ok(link) }
+        },
+        NoTocCache,
+        ReconnectPolicy {
+            initial_delay: Duration::from_millis(50),
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    let mut block = cf.log.create_block().await?;
+    block.add_variable("stateEstimate.x").await?;
+    block.add_memory_variable(0x2000_1000, ValueType::U32).await?;
+    let stream = block.start(LogPeriod::from_millis(10)?).await?;
+    stream.next().await?;
+
+    // The simulation forgets its log blocks when the link is lost
+    sim.disconnect("Simulated link loss");
+    let wait_blocks = async |count| {
+        while sim.log_blocks().len() != count {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    };
+    let restored = async {
+        wait_blocks(0).await;
+        wait_blocks(1).await;
+    };
+    tokio::time::timeout(Duration::from_secs(1), restored)
+        .await
+        .expect("Log block not restored");
+
+    // More samples than could have been buffered before the link loss
+    for _ in 0..20 {
+        let data = tokio::time::timeout(Duration::from_secs(1), stream.next())
+            .await
+            .expect("Log stream not restored")?;
+        assert_eq!(data.data.len(), 2);
+    }
+
+    cf.param.set("pid_rate.roll_kp", 1.0f32).await?;
+    assert!(matches!(sim.param("pid_rate.roll_kp"), Some(Value::F32(v)) if v == 1.0));
+
+    // Only the last event since the initial connection is replayed
+    let mut events = cf.events();
+    let replayed: Vec<_> = events.by_ref().take(5).collect().await;
+    assert!(matches!(replayed[3], ConnectionEvent::Connected { .. }));
+    assert!(matches!(replayed[4], ConnectionEvent::Connected { .. }));
+    assert!(futures::FutureExt::now_or_never(events.next()).is_none());
+
+    cf.disconnect().await;
+    assert_eq!(cf.wait_disconnect().await, "Link closed");
+    Ok(())
+}
+
+#[tokio::test]
+async fn connection_events() -> crazyflie_lib::Result<()> {
+    let sim = simulation();
+    let cf = Crazyflie::connect_from_simulation(&sim, NoTocCache).await?;
+
+    sim.disconnect("Simulated link loss");
+    cf.wait_disconnect().await;
+
+    let events: Vec<_> = cf.events().collect().await;
+    assert_eq!(events.len(), 5);
+    assert_eq!(events[0], ConnectionEvent::Connecting);
+    // The log and param TOCs are fetched in parallel
+    for port in [2, 5] {
+        assert!(events[1..3].contains(&ConnectionEvent::TocFetched { port, from_cache: false }));
+    }
+    assert_eq!(
+        events[3],
+        ConnectionEvent::Connected {
+            protocol_version: crazyflie_lib::MAX_SUPPORTED_PROTOCOL_VERSION
+        }
+    );
+    assert_eq!(
+        events[4],
+        ConnectionEvent::Disconnected {
+            reason: "Simulated link loss".to_owned()
+        }
+    );
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use crazyflie_lib::simulation::SimulatedCrazyflie;
+use crazyflie_lib::swarm::{FailurePolicy, Swarm};
+use crazyflie_lib::{Crazyflie, Error, NoTocCache, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Three simulated Crazyflies, the last one cannot be connected
+fn simulations() -> HashMap<String, SimulatedCrazyflie> {
+    (1..=3)
+        .map(|i| {
+            let sim = SimulatedCrazyflie::new();
+            sim.add_param("pid_rate.roll_kp", Value::F32(100.0 * i as f32), true);
+            if i == 3 {
+                sim.set_protocol_version(2);
+            }
+            (format!("sim://{}", i), sim)
+        })
+        .collect()
+}
+
+async fn connect(
+    sims: &HashMap<String, SimulatedCrazyflie>,
+    policy: FailurePolicy,
+) -> crazyflie_lib::Result<Swarm> {
+    let mut uris: Vec<String> = sims.keys().cloned().collect();
+    uris.sort();
+    Swarm::connect_with(
+        uris,
+        |uri| Crazyflie::connect_from_simulation(&sims[&uri], NoTocCache),
+        policy,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn connect_policies() -> crazyflie_lib::Result<()> {
+    let sims = simulations();
+
+    assert!(matches!(
+        connect(&sims, FailurePolicy::AllOrNothing).await,
+        Err(Error::SwarmError { total: 3, .. })
+    ));
+    assert!(matches!(
+        connect(&sims, FailurePolicy::AtLeast(3)).await,
+        Err(Error::SwarmError { ref errors, .. }) if errors.len() == 1 && errors[0].0 == "sim://3"
+    ));
+
+    let swarm = connect(&sims, FailurePolicy::AtLeast(2)).await?;
+    assert_eq!(swarm.uris().collect::<Vec<_>>(), ["sim://1", "sim://2"]);
+    assert_eq!(swarm.connect_errors().len(), 1);
+    assert_eq!(swarm.connect_errors()[0].0, "sim://3");
+    assert!(swarm.get("sim://2").is_some());
+    assert!(swarm.get("sim://3").is_none());
+
+    swarm.disconnect().await;
+    for (_, cf) in swarm.iter() {
+        tokio::time::timeout(Duration::from_secs(1), cf.wait_disconnect())
+            .await
+            .expect("Crazyflie not disconnected");
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn parallel_and_sequential() -> crazyflie_lib::Result<()> {
+    let sims = simulations();
+    let swarm = connect(&sims, FailurePolicy::BestEffort).await?;
+
+    let results = swarm
+        .parallel(async |_, cf| cf.param.get::<f32>("pid_rate.roll_kp").await)
+        .await?;
+    assert!(matches!(results["sim://1"], Ok(v) if v == 100.0));
+    assert!(matches!(results["sim://2"], Ok(v) if v == 200.0));
+
+    let results = swarm
+        .parallel(async |uri, cf| match uri {
+            "sim://1" => cf.param.get::<f32>("unknown.param").await,
+            _ => Ok(0.0),
+        })
+        .await?;
+    assert!(results["sim://1"].is_err());
+    assert!(results["sim://2"].is_ok());
+
+    let swarm = Swarm::connect_with(
+        swarm.uris().map(str::to_owned).collect::<Vec<_>>(),
+        |uri| Crazyflie::connect_from_simulation(&sims[&uri], NoTocCache),
+        FailurePolicy::AllOrNothing,
+    )
+    .await?;
+    let visited = Mutex::new(Vec::new());
+    let result = swarm
+        .sequential(async |uri, _| {
+            visited.lock().unwrap().push(uri.to_owned());
+            Err::<(), _>(Error::Timeout)
+        })
+        .await;
+    let failure = result.unwrap_err();
+    assert_eq!(failure.results.len(), 1);
+    assert!(matches!(failure.results["sim://1"], Err(Error::Timeout)));
+    assert_eq!(*visited.lock().unwrap(), ["sim://1"]);
+
+    // The results of the Crazyflies that succeeded are kept
+    let failure = swarm
+        .parallel(async |uri, _| match uri {
+            "sim://1" => Err(Error::Timeout),
+            _ => Ok(uri.len()),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(failure.total, 2);
+    assert!(matches!(failure.results["sim://2"], Ok(7)));
+    assert_eq!(failure.errors().map(|(uri, _)| uri).collect::<Vec<_>>(), ["sim://1"]);
+    let error: Error = failure.into();
+    assert!(matches!(error, Error::SwarmError { total: 2, ref errors } if errors.len() == 1));
+    Ok(())
+}
+
+#[tokio::test]
+async fn duplicate_uris() {
+    let sims = simulations();
+    let result = Swarm::connect_with(
+        ["sim://1".to_owned(), "sim://2".to_owned(), "sim://1".to_owned()],
+        |uri| Crazyflie::connect_from_simulation(&sims[&uri], NoTocCache),
+        FailurePolicy::BestEffort,
+    )
+    .await;
+    assert!(matches!(result, Err(Error::InvalidArgument(_))));
+}
@@ -0,0 +1,59 @@
+use async_trait::async_trait;
+use crazyflie_lib::simulation::SimulatedCrazyflie;
+use crazyflie_lib::{Crazyflie, CrtpTransport, NoTocCache, Result, Value};
+use crazyflie_link::Packet;
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::sync::Arc;
+
+/// Transport that counts the packets going through an inner transport
+struct CountingTransport<L> {
+    inner: L,
+    sent: Arc<AtomicUsize>,
+    received: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl<L: CrtpTransport> CrtpTransport for CountingTransport<L> {
+    async fn send_packet(&self, packet: Packet) -> Result<()> {
+        self.sent.fetch_add(1, Relaxed);
+        self.inner.send_packet(packet).await
+    }
+
+    async fn recv_packet(&self) -> Result<Packet> {
+        let packet = self.inner.recv_packet().await?;
+        self.received.fetch_add(1, Relaxed);
+        Ok(packet)
+    }
+
+    async fn close(&self) {
+        self.inner.close().await
+    }
+
+    async fn wait_close(&self) -> String {
+        self.inner.wait_close().await
+    }
+}
+
+#[tokio::test]
+async fn connect_from_custom_transport() -> Result<()> {
+    let sim = SimulatedCrazyflie::new();
+    sim.add_param("ring.effect", Value::U8(6), true);
+
+    let sent = Arc::new(AtomicUsize::new(0));
+    let received = Arc::new(AtomicUsize::new(0));
+    let transport = CountingTransport {
+        inner: sim.open_link(),
+        sent: sent.clone(),
+        received: received.clone(),
+    };
+
+    let cf = Crazyflie::connect_from_transport(transport, NoTocCache).await?;
+    let effect: u8 = cf.param.get("ring.effect").await?;
+    assert_eq!(effect, 6);
+
+    assert!(sent.load(Relaxed) > 0);
+    assert!(received.load(Relaxed) > 0);
+
+    cf.disconnect().await;
+    Ok(())
+}